@@ -2,16 +2,20 @@
 //! CLI commands/arguments.
 
 use crate::{
-    compat_port_path,
     configs::get_config,
     create_recursive,
-    debug::run_debug_output,
+    debug::{run_debug_output, run_trace_stderr},
     map_miette,
-    screen_buffer::UICommand,
+    path_utils::resolve_file_template,
+    screen_buffer::{UICommand, UIState},
     serial_actor::{
-        SerialActor, SerialEvent, SerialMessage,
-        tasks::{run_file_output, run_stdin_input, run_stdout_output},
+        DeviceReset, SerialMessage,
+        tasks::{
+            InputOptions, run_auto_login, run_device_reset, run_macro, run_response_check,
+            run_stdin_input, run_stdout_output,
+        },
     },
+    session::SessionBuilder,
 };
 use crossterm::{
     cursor, event, execute,
@@ -22,102 +26,378 @@ use miette::{Context, IntoDiagnostic};
 use serial2_tokio::SerialPort;
 use std::{
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 use tracing::{Level, trace};
 
+/// Options for an [`interactive_session`] that aren't part of the [`Config`][crate::configs::Config].
+#[derive(Debug, Default)]
+pub struct SessionOptions {
+    /// Ends the session after it has been running for this long.
+    pub max_duration: Option<std::time::Duration>,
+    /// Ends the session once no data has been received from the device for this long.
+    pub idle_timeout: Option<std::time::Duration>,
+    /// Writes the lines sent to the device to the output file.
+    pub echo_sent: bool,
+    /// Sends a carriage return once connected and warns when the device
+    /// doesn't respond within this long.
+    pub require_response: Option<std::time::Duration>,
+    /// Writes what is typed after password prompts to the output files
+    /// instead of masking it with asterisks.
+    pub insecure_log_passwords: bool,
+    /// Only writes the output files when an error occurred during the session,
+    /// see [`Defaults::error_patterns`][crate::configs::Defaults::error_patterns].
+    pub output_on_error_only: bool,
+    /// Prints a summary of the data received to stderr.
+    pub trace_stderr: bool,
+    /// Never sends anything to the device, only displaying and writing what is
+    /// received. The terminal's title is prefixed with `[RO]`.
+    pub read_only: bool,
+    /// Reopens the port when the connection is lost, i.e. when the device reboots
+    /// or a USB-serial adapter is unplugged, see [`SessionBuilder::reconnect()`].
+    pub reconnect: bool,
+    /// Keeps the lines typed in a local history, re-sent with
+    /// [`Keybindings::history_prev`][crate::configs::Keybindings::history_prev].
+    pub local_history: bool,
+    /// The name of a [`Macro`][crate::configs::Macro] to run once connected.
+    pub run_macro: Option<String>,
+    /// Resets the device with its DTR and RTS lines once connected, before
+    /// running [`SessionOptions::run_macro`].
+    pub device_reset: Option<DeviceReset>,
+}
+
+/// A limit that caused sericom to end a session.
+#[derive(Debug)]
+enum SessionLimit {
+    MaxDuration(std::time::Duration),
+    IdleTimeout(std::time::Duration),
+}
+
+impl std::fmt::Display for SessionLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MaxDuration(d) => write!(
+                f,
+                "Session ended: reached the maximum duration of {}s",
+                d.as_secs()
+            ),
+            Self::IdleTimeout(d) => write!(
+                f,
+                "Session ended: no data was received for {}s",
+                d.as_secs()
+            ),
+        }
+    }
+}
+
 /// Spawns all of the tasks responsible for maintaining an interactive terminal session.
 pub async fn interactive_session(
     connection: SerialPort,
     file_path: Option<Option<PathBuf>>,
     debug: bool,
     port_name: &str,
+    options: SessionOptions,
 ) -> miette::Result<()> {
     let span = tracing::span!(Level::TRACE, "Interactive Session");
     let _enter = span.enter();
-    // Setup terminal
-    let mut stdout = io::stdout();
-    terminal::enable_raw_mode()
-        .into_diagnostic()
-        .wrap_err("Failed to enable raw mode.".red())?;
-    execute!(
-        stdout,
-        terminal::EnterAlternateScreen,
-        terminal::SetTitle(port_name),
-        terminal::Clear(ClearType::All),
-        event::EnableBracketedPaste,
-        event::EnableMouseCapture,
-        cursor::MoveTo(0, 0)
-    )
-    .into_diagnostic()
-    .wrap_err("Failed to setup the terminal.".red())?;
     let config = get_config();
+    // Resolve the file path before setting up the terminal so
+    // any errors are printed to a normal screen
+    let file_path = file_path
+        .or_else(|| config.always_capture().then_some(None))
+        .map(|maybe_path| resolve_file_path(config, maybe_path, port_name))
+        .transpose()?;
+    let capture_paths = config
+        .captures
+        .iter()
+        .map(|capture| Ok((resolve_capture_path(config, capture, port_name)?, capture)))
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    let startup_macro = options
+        .run_macro
+        .as_deref()
+        .map(|name| {
+            config.macros.get(name).ok_or_else(|| {
+                miette::miette!(
+                    help = "Macros are defined as `[macros.<NAME>]` tables in the config",
+                    "No macro named '{name}'"
+                )
+            })
+        })
+        .transpose()?;
+
+    let title = match options.read_only {
+        true => format!("[RO] {port_name}"),
+        false => port_name.to_string(),
+    };
+    let stdout = setup_terminal(config, &title)?;
+    let baud = connection
+        .get_configuration()
+        .and_then(|settings| settings.get_baud_rate())
+        .unwrap_or_default();
+    run_entry_script(config, port_name, baud).await;
 
     trace!("Creating channels");
     // Create channels
-    let (command_tx, command_rx) = tokio::sync::mpsc::channel::<SerialMessage>(100);
-    let (ui_tx, ui_rx) = tokio::sync::mpsc::channel::<UICommand>(100);
-    let (broadcast_event_tx, _) = tokio::sync::broadcast::channel::<SerialEvent>(128);
-    let stdout_rx = broadcast_event_tx.subscribe();
+    let defaults = &config.defaults;
+    let (ui_tx, ui_rx) = tokio::sync::mpsc::channel::<UICommand>(defaults.ui_channel_capacity);
+
+    let mut builder = SessionBuilder::new()
+        .port(port_name)
+        .reconnect(options.reconnect)
+        .read_only(options.read_only)
+        .echo_sent(options.echo_sent)
+        .mask_passwords(!options.insecure_log_passwords)
+        .output_on_error_only(options.output_on_error_only)
+        .exit_script(true);
+    if let Some(file_path) = file_path {
+        builder = builder.file(file_path);
+    }
+    for (capture_path, capture) in capture_paths {
+        builder = builder.capture(capture_path, capture.filter.clone());
+    }
+
+    // Subscribe before the session starts so that no data is missed
+    let stdout_rx = builder.subscribe();
+    let debug_rx = debug.then(|| builder.subscribe());
+    let trace_rx = options.trace_stderr.then(|| builder.subscribe());
+    // Logging in would send the credentials to the device
+    let login = config
+        .active_profile()
+        .and_then(|profile| profile.login.as_ref())
+        .filter(|login| login.enabled && !options.read_only);
+    let login_rx = login.map(|login| (builder.subscribe(), login));
+    let idle_rx = options
+        .idle_timeout
+        .map(|timeout| (builder.subscribe(), timeout));
+    let response_rx = options
+        .require_response
+        .filter(|_| !options.read_only)
+        .map(|timeout| (builder.subscribe(), timeout));
+    let command_tx = builder.command_sender();
 
     // Create tasks
-    let mut tasks = tokio::task::JoinSet::new();
-
-    if let Some(maybe_path) = file_path {
-        let default_out_dir = PathBuf::from(&config.defaults.out_dir);
-        let file_path = match maybe_path {
-            Some(path) => {
-                // If given an absolute path - override the `default_out_dir`
-                if path.is_absolute() {
-                    let parent = path.parent().unwrap_or(&default_out_dir);
-                    create_recursive!(parent);
-                    path
-                } else {
-                    let joined_path = default_out_dir.join(&path);
-                    let parent_path = joined_path.parent().expect("Does not have root");
-                    create_recursive!(parent_path);
-                    joined_path
-                }
+    let mut session = builder.run_with_port(connection);
+
+    if let Some(debug_rx) = debug_rx {
+        session.spawn(run_debug_output(debug_rx));
+    }
+
+    if let Some(trace_rx) = trace_rx {
+        session.spawn(run_trace_stderr(trace_rx));
+    }
+
+    if let Some((login_rx, login)) = login_rx {
+        session.spawn(run_auto_login(login_rx, command_tx.clone(), login));
+    }
+
+    let sent_tx = session.sent_sender();
+    if startup_macro.is_some() || options.device_reset.is_some() {
+        let command_tx = command_tx.clone();
+        let sent_tx = sent_tx.clone();
+        let device_reset = options.device_reset;
+        session.spawn(async move {
+            if let Some(reset) = device_reset {
+                run_device_reset(command_tx.clone(), reset).await;
             }
-            None => {
-                let default_out_dir = PathBuf::from(&config.defaults.out_dir);
-                compat_port_path!(default_out_dir, port_name)
+            if let Some(m) = startup_macro {
+                run_macro(command_tx, sent_tx, m).await;
             }
-        };
-        let file_rx = broadcast_event_tx.subscribe();
-        tasks.spawn(async move {
-            run_file_output(file_rx, file_path.clone()).await;
-            run_file_exit_script(config, file_path);
         });
-    };
+    }
 
-    if debug {
-        let debug_rx = broadcast_event_tx.subscribe();
-        tasks.spawn(run_debug_output(debug_rx));
+    // The session limits and the response check are spawned outside of the
+    // session, as `session.join()` waits for all of its tasks and these would
+    // keep it alive until they time out
+    let limit_reached = std::sync::Arc::new(std::sync::OnceLock::<SessionLimit>::new());
+    if let Some(max_duration) = options.max_duration {
+        let shutdown_tx = command_tx.clone();
+        let limit_reached = limit_reached.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(max_duration).await;
+            let _ = limit_reached.set(SessionLimit::MaxDuration(max_duration));
+            let _ = shutdown_tx.send(SerialMessage::Shutdown).await;
+        });
+    }
+    if let Some((idle_rx, idle_timeout)) = idle_rx {
+        let shutdown_tx = command_tx.clone();
+        let limit_reached = limit_reached.clone();
+        tokio::spawn(async move {
+            if wait_for_idle(idle_rx, idle_timeout).await {
+                let _ = limit_reached.set(SessionLimit::IdleTimeout(idle_timeout));
+                let _ = shutdown_tx.send(SerialMessage::Shutdown).await;
+            }
+        });
     }
 
-    let actor = SerialActor::new(connection, command_rx, broadcast_event_tx);
-    tasks.spawn(actor.run());
+    let response_check = response_rx.map(|(response_rx, timeout)| {
+        tokio::spawn(run_response_check(
+            response_rx,
+            command_tx.clone(),
+            ui_tx.clone(),
+            timeout,
+        ))
+    });
 
-    tasks.spawn(run_stdout_output(stdout_rx, ui_rx));
-    tasks.spawn(run_stdin_input(command_tx, ui_tx));
+    let (dtr, rts) = options
+        .device_reset
+        .map_or((true, true), DeviceReset::final_lines);
+    let input_options = InputOptions::new()
+        .read_only(options.read_only)
+        .local_history(options.local_history)
+        .control_lines(dtr, rts);
+    let ui_state = std::sync::Arc::new(UIState::default());
+    session.spawn(run_stdout_output(stdout_rx, ui_rx, ui_state.clone()));
+    session.spawn(run_stdin_input(
+        command_tx,
+        ui_tx,
+        ui_state,
+        sent_tx,
+        input_options,
+    ));
 
-    tasks.join_all().await;
+    session.join().await;
     ensure_terminal_cleanup(stdout);
+    if let Some(limit) = limit_reached.get() {
+        println!("{limit}");
+    }
+    if let Some(check) = response_check
+        && check.is_finished()
+        && let Ok(false) = check.await
+    {
+        println!("Warning: no response from device after connecting");
+    }
     Ok(())
 }
 
-/// Opens a serial `port` for communication with the specified `baud`.
+/// Waits until no [`SerialEvent::Data`][crate::serial_actor::SerialEvent] has been
+/// received on `rx` for `timeout`, returning `false` if the connection is closed first.
+async fn wait_for_idle(
+    mut rx: tokio::sync::broadcast::Receiver<crate::serial_actor::SerialEvent>,
+    timeout: std::time::Duration,
+) -> bool {
+    use crate::serial_actor::SerialEvent;
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Err(_) => return true,
+            Ok(Ok(SerialEvent::Data(_)) | Err(RecvError::Lagged(_))) => {
+                deadline = tokio::time::Instant::now() + timeout;
+            }
+            Ok(Ok(SerialEvent::ConnectionClosed) | Err(RecvError::Closed)) => return false,
+            Ok(Ok(_)) => {}
+        }
+    }
+}
+
+/// Resolves the path of the file that the session's output is written to.
+///
+/// When given a path, relative paths are joined to [`Defaults::out_dir`][crate::configs::Defaults]
+/// and absolute paths are used as-is. Otherwise, the file is named from
+/// [`Defaults::file_template`][crate::configs::Defaults] within the `out_dir`.
+/// If [`Defaults::append_date_to_dir`][crate::configs::Defaults] is set, a
+/// subdirectory named after the current date is inserted after the `out_dir`.
+/// Creates any of the missing parent directories.
+fn resolve_file_path(
+    config: &crate::configs::Config,
+    maybe_path: Option<PathBuf>,
+    port_name: &str,
+) -> miette::Result<PathBuf> {
+    let mut default_out_dir = PathBuf::from(&config.defaults.out_dir);
+    if config.defaults.append_date_to_dir {
+        let date = chrono::Utc::now().format(&config.defaults.dir_date_format);
+        default_out_dir.push(date.to_string());
+    }
+    // The dated directory is only created for the files within it, the
+    // relative paths create it along with their own parent directories
+    let file_path = match maybe_path {
+        Some(path) => {
+            // If given an absolute path - override the `default_out_dir`
+            if path.is_absolute() {
+                let parent = path.parent().unwrap_or(&default_out_dir);
+                create_recursive!(parent);
+                path
+            } else {
+                let joined_path = default_out_dir.join(&path);
+                let parent_path = joined_path.parent().expect("Does not have root");
+                create_recursive!(parent_path);
+                joined_path
+            }
+        }
+        None => {
+            if config.defaults.append_date_to_dir {
+                create_recursive!(default_out_dir.as_path());
+            }
+            default_out_dir.join(resolve_file_template(
+                &config.defaults.file_template,
+                port_name,
+                config.profile.as_deref(),
+            )?)
+        }
+    };
+    Ok(file_path)
+}
+
+/// Resolves the path of a [`Capture`][crate::configs::Capture]'s file.
+///
+/// The file's name is resolved like the [`Defaults::file_template`][crate::configs::Defaults]
+/// and the path is then resolved like a path given with `-f`, see [`resolve_file_path`].
+fn resolve_capture_path(
+    config: &crate::configs::Config,
+    capture: &crate::configs::Capture,
+    port_name: &str,
+) -> miette::Result<PathBuf> {
+    let file_name = capture
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let file_name = resolve_file_template(&file_name, port_name, config.profile.as_deref())?;
+    resolve_file_path(
+        config,
+        Some(capture.path.with_file_name(file_name)),
+        port_name,
+    )
+}
+
+/// The settings of a serial port other than its baud rate, defaults to 8N1
+/// (8 data bits, no parity, 1 stop bit) without flow control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortSettings {
+    pub data_bits: serial2_tokio::CharSize,
+    pub parity: serial2_tokio::Parity,
+    pub stop_bits: serial2_tokio::StopBits,
+    pub flow_control: serial2_tokio::FlowControl,
+}
+
+impl Default for PortSettings {
+    fn default() -> Self {
+        Self {
+            data_bits: serial2_tokio::CharSize::Bits8,
+            parity: serial2_tokio::Parity::None,
+            stop_bits: serial2_tokio::StopBits::One,
+            flow_control: serial2_tokio::FlowControl::None,
+        }
+    }
+}
+
+/// Opens a serial `port` for communication with the specified `baud` and `settings`.
 ///
 /// Returns `Ok(SerialPort)` or errors if unable to set the baud rate or open the `port`.
-pub fn open_connection(baud: u32, port: &str) -> miette::Result<SerialPort> {
+pub fn open_connection(
+    baud: u32,
+    port: &str,
+    settings: &PortSettings,
+) -> miette::Result<SerialPort> {
     let settings = |mut s: serial2_tokio::Settings| -> std::io::Result<serial2_tokio::Settings> {
         s.set_raw();
         s.set_baud_rate(baud)?;
-        s.set_char_size(serial2_tokio::CharSize::Bits8);
-        s.set_stop_bits(serial2_tokio::StopBits::One);
-        s.set_parity(serial2_tokio::Parity::None);
-        s.set_flow_control(serial2_tokio::FlowControl::None);
+        s.set_char_size(settings.data_bits);
+        s.set_stop_bits(settings.stop_bits);
+        s.set_parity(settings.parity);
+        s.set_flow_control(settings.flow_control);
         Ok(s)
     };
     let con = map_miette!(
@@ -136,11 +416,17 @@ pub fn open_connection(baud: u32, port: &str) -> miette::Result<SerialPort> {
     Ok(con)
 }
 
-/// Gets the settings for the `port` with the specified `baud`.
-pub fn get_settings(baud: u32, port: &str) -> miette::Result<()> {
+/// Gets the settings for the `port` after opening it with the specified `baud`
+/// and `port_settings`, to verify which settings were actually applied.
+pub fn get_settings(
+    baud: u32,
+    port: &str,
+    port_settings: &PortSettings,
+    format: OutputFormat,
+) -> miette::Result<()> {
     // https://www.contec.com/support/basic-knowledge/daq-control/serial-communicatin/
     let mut stdout = io::stdout();
-    let con = open_connection(baud, port)?;
+    let con = open_connection(baud, port, port_settings)?;
     let settings = map_miette!(
         con.get_configuration(),
         format!("Failed to get settings for port '{}'", port),
@@ -243,6 +529,25 @@ pub fn get_settings(baud: u32, port: &str) -> miette::Result<()> {
         )
     )?;
 
+    if format == OutputFormat::Json {
+        let settings = SettingsJson {
+            port,
+            baud_rate: b,
+            char_size: c.as_u8(),
+            stop_bits: s.as_u8(),
+            parity: p.as_str(),
+            flow_control: f.as_str(),
+            cts,
+            dsr,
+            ri,
+            cd,
+        };
+        let json = serde_json::to_string_pretty(&settings).into_diagnostic()?;
+        return writeln!(stdout, "{json}")
+            .into_diagnostic()
+            .wrap_err("Failed to write to stdout.".red());
+    }
+
     write!(stdout, "Baud rate: {b}\r\n")
         .into_diagnostic()
         .wrap_err("Failed to write to stdout.".red())?;
@@ -274,26 +579,558 @@ pub fn get_settings(baud: u32, port: &str) -> miette::Result<()> {
     Ok(())
 }
 
+/// How often [`watch_settings`] reads the modem control lines.
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// The states of the modem control lines that are read from a port.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct LineStates {
+    cts: bool,
+    dsr: bool,
+    ri: bool,
+    cd: bool,
+}
+
+impl LineStates {
+    fn read(con: &SerialPort) -> io::Result<Self> {
+        Ok(Self {
+            cts: con.read_cts()?,
+            dsr: con.read_dsr()?,
+            ri: con.read_ri()?,
+            cd: con.read_cd()?,
+        })
+    }
+
+    /// Returns the lines shown by [`watch_settings`] and whether each changed
+    /// since `previous`.
+    fn rows(&self, previous: Option<&Self>) -> [(String, bool); 4] {
+        let row = |name: &str, state: bool, previous: Option<bool>| {
+            (
+                format!("{name}: {state}"),
+                previous.is_some_and(|previous| previous != state),
+            )
+        };
+        [
+            row("Clear To Send line", self.cts, previous.map(|p| p.cts)),
+            row("Data Set Ready line", self.dsr, previous.map(|p| p.dsr)),
+            row("Ring Indicator line", self.ri, previous.map(|p| p.ri)),
+            row("Carrier Detect line", self.cd, previous.map(|p| p.cd)),
+        ]
+    }
+}
+
+/// Opens the `port` like [`get_settings`] and shows the states of its modem control
+/// lines (CTS, DSR, RI and CD) until <kbd>Ctrl</kbd> + <kbd>c</kbd> is pressed,
+/// reading them every 250ms. The lines that changed since the last
+/// read are highlighted.
+pub fn watch_settings(baud: u32, port: &str, port_settings: &PortSettings) -> miette::Result<()> {
+    let con = open_connection(baud, port, port_settings)?;
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()
+        .into_diagnostic()
+        .wrap_err("Failed to enable raw mode.".red())?;
+    let result = execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)
+        .into_diagnostic()
+        .wrap_err("Failed to setup the terminal.".red())
+        .and_then(|_| watch_lines(&con, port, &mut stdout));
+    ensure_terminal_cleanup(stdout);
+    result
+}
+
+fn watch_lines(con: &SerialPort, port: &str, stdout: &mut io::Stdout) -> miette::Result<()> {
+    use crossterm::{
+        event::{Event, KeyCode, KeyEvent, KeyModifiers},
+        queue, style,
+    };
+
+    let mut previous: Option<LineStates> = None;
+    loop {
+        let states = map_miette!(
+            LineStates::read(con),
+            format!("Failed to read the modem control lines for port '{}'", port),
+            format!(
+                "{} {} [OPTIONS] {} <PORT> {}",
+                "USAGE:".bold().underlined(),
+                "sericom settings".bold(),
+                "--port".bold(),
+                "--watch".bold()
+            )
+        )?;
+        queue!(
+            stdout,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0),
+            style::Print(format!("Watching '{port}', Ctrl+C to quit\r\n\r\n"))
+        )
+        .into_diagnostic()?;
+        for (row, changed) in states.rows(previous.as_ref()) {
+            let row = match changed {
+                true => row.black().on_yellow(),
+                false => row.stylize(),
+            };
+            queue!(stdout, style::PrintStyledContent(row), style::Print("\r\n"))
+                .into_diagnostic()?;
+        }
+        stdout
+            .flush()
+            .into_diagnostic()
+            .wrap_err("Failed to write to stdout.".red())?;
+        previous = Some(states);
+
+        if event::poll(WATCH_INTERVAL).into_diagnostic()?
+            && let Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) = event::read().into_diagnostic()?
+            && (code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL)
+                || code == KeyCode::Esc
+                || code == KeyCode::Char('q'))
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Sends the contents of the file at `path` to the device over `connection`,
+/// line by line with `delay` between the lines, printing the progress to stdout.
+///
+/// With `crlf`, the file's line endings (`\n` or `\r\n`) are sent as a carriage
+/// return (`\r`), which is what most devices expect when a line is typed.
+pub async fn send_file<C: crate::serial_actor::SerialConnection + Send + 'static>(
+    connection: C,
+    path: &std::path::Path,
+    delay: std::time::Duration,
+    crlf: bool,
+) -> miette::Result<()> {
+    let contents = std::fs::read(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to read '{}'", path.display()))?;
+    let lines = file_lines(&contents, crlf);
+    let lines_len = lines.len();
+    let session = SessionBuilder::new().run_with(connection);
+    let mut stdout = io::stdout();
+    for (sent, line) in lines.into_iter().enumerate() {
+        if sent > 0 && !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        session.write(line).await?;
+        write!(stdout, "\rSent {}/{} lines", sent + 1, lines_len)
+            .and_then(|_| stdout.flush())
+            .into_diagnostic()
+            .wrap_err("Failed to write to stdout.".red())?;
+    }
+    writeln!(stdout)
+        .into_diagnostic()
+        .wrap_err("Failed to write to stdout.".red())?;
+    session.shutdown().await;
+    Ok(())
+}
+
+/// Bridges `connection` to a TCP socket listening on `listen` (i.e. `0.0.0.0:7000`),
+/// so that the device can be reached over the network, i.e. with `nc` or `telnet`.
+///
+/// One client is served at a time: the bytes it sends are written to the device
+/// and the data received from the device is sent to it. Once it disconnects, the
+/// next client is accepted. The data received while no client is connected is
+/// dropped. The bridge ends once the connection is closed or on <kbd>Ctrl</kbd> + <kbd>c</kbd>.
+pub async fn bridge<C: crate::serial_actor::SerialConnection + Send + 'static>(
+    connection: C,
+    listen: &str,
+) -> miette::Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to listen on '{listen}'").red())?;
+    if let Ok(addr) = listener.local_addr() {
+        println!("Listening on {addr}, press Ctrl+C to stop");
+    }
+    let builder = SessionBuilder::new();
+    let events = builder.subscribe();
+    let session = builder.run_with(connection);
+    let result = tokio::select! {
+        result = run_bridge(&session, events, listener) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    session.shutdown().await;
+    result
+}
+
+/// Why [`serve_bridge_client()`] stopped serving a client.
+#[derive(Debug, PartialEq, Eq)]
+enum BridgeEnd {
+    ClientDisconnected,
+    ConnectionClosed,
+}
+
+/// Accepts the clients of [`bridge()`] one at a time until the connection is closed.
+async fn run_bridge(
+    session: &crate::session::Session,
+    mut events: tokio::sync::broadcast::Receiver<crate::serial_actor::SerialEvent>,
+    listener: tokio::net::TcpListener,
+) -> miette::Result<()> {
+    use crate::serial_actor::SerialEvent;
+    use tokio::sync::broadcast::error::RecvError;
+
+    loop {
+        let (socket, peer) = tokio::select! {
+            accepted = listener.accept() => accepted
+                .into_diagnostic()
+                .wrap_err("Failed to accept a client".red())?,
+            event = events.recv() => match event {
+                Ok(SerialEvent::ConnectionClosed) | Err(RecvError::Closed) => {
+                    println!("Connection closed");
+                    return Ok(());
+                }
+                _ => continue,
+            },
+        };
+        println!("{peer} connected");
+        match serve_bridge_client(session, &mut events, socket).await {
+            BridgeEnd::ClientDisconnected => println!("{peer} disconnected"),
+            BridgeEnd::ConnectionClosed => {
+                println!("Connection closed");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Forwards the bytes between `socket` and the session until either one closes.
+async fn serve_bridge_client(
+    session: &crate::session::Session,
+    events: &mut tokio::sync::broadcast::Receiver<crate::serial_actor::SerialEvent>,
+    socket: tokio::net::TcpStream,
+) -> BridgeEnd {
+    use crate::serial_actor::SerialEvent;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::broadcast::error::RecvError;
+
+    let (mut reader, mut writer) = socket.into_split();
+    let mut buf = vec![0; 4096];
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buf) => match read {
+                Ok(0) | Err(_) => return BridgeEnd::ClientDisconnected,
+                Ok(n) => {
+                    if session.write(&buf[..n]).await.is_err() {
+                        return BridgeEnd::ConnectionClosed;
+                    }
+                }
+            },
+            event = events.recv() => match event {
+                Ok(SerialEvent::Data(data)) => {
+                    if writer.write_all(&data).await.is_err() {
+                        return BridgeEnd::ClientDisconnected;
+                    }
+                }
+                Ok(SerialEvent::ConnectionClosed) | Err(RecvError::Closed) => {
+                    return BridgeEnd::ConnectionClosed;
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Bridge lagged, skipped {skipped} messages");
+                }
+                Ok(_) => {}
+            },
+        }
+    }
+}
+
+/// The rate at which [`replay()`] plays a file back with a `speed` of `1.0`,
+/// the bytes per second of a 9600 baud connection.
+const REPLAY_BYTES_PER_SEC: f64 = 960.0;
+
+/// The longest [`replay()`] waits between chunks, for a tiny `speed`.
+const MAX_REPLAY_CHUNK_DELAY: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Returns how long [`replay()`] waits after a chunk of `chunk_len` bytes at `speed`.
+fn replay_chunk_delay(chunk_len: usize, speed: f64) -> std::time::Duration {
+    std::time::Duration::try_from_secs_f64(chunk_len as f64 / (REPLAY_BYTES_PER_SEC * speed))
+        .unwrap_or(MAX_REPLAY_CHUNK_DELAY)
+        .min(MAX_REPLAY_CHUNK_DELAY)
+}
+
+/// Replays a file written by a session (i.e. with `-f`) in the terminal, through
+/// the same [`ScreenBuffer`][crate::screen_buffer::ScreenBuffer] as a session so
+/// that it can be scrolled, searched and copied from. Nothing is sent anywhere and
+/// the replay ends with <kbd>Ctrl</kbd> + <kbd>q</kbd>.
+///
+/// Without a `speed`, the whole file is shown at once. Otherwise it is played
+/// back at `speed` times the rate of a 9600 baud connection.
+pub async fn replay(path: &std::path::Path, speed: Option<f64>) -> miette::Result<()> {
+    use crate::serial_actor::SerialEvent;
+
+    let contents = std::fs::read(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to read '{}'", path.display()))?;
+    let config = get_config();
+    let defaults = &config.defaults;
+    let title = format!("[Replay] {}", path.display());
+    let stdout = setup_terminal(config, &title)?;
+
+    let (event_tx, stdout_rx) =
+        tokio::sync::broadcast::channel::<SerialEvent>(defaults.event_channel_capacity);
+    let (ui_tx, ui_rx) = tokio::sync::mpsc::channel::<UICommand>(defaults.ui_channel_capacity);
+    let (command_tx, mut command_rx) =
+        tokio::sync::mpsc::channel::<SerialMessage>(defaults.command_channel_capacity);
+    let ui_state = std::sync::Arc::new(UIState::default());
+    let output = tokio::spawn(run_stdout_output(stdout_rx, ui_rx, ui_state.clone()));
+    let input = tokio::spawn(run_stdin_input(
+        command_tx,
+        ui_tx,
+        ui_state,
+        None,
+        InputOptions::new().read_only(true),
+    ));
+
+    let chunk_len = 256;
+    let chunk_delay = speed.map(|speed| replay_chunk_delay(chunk_len, speed));
+    let feed = async {
+        for chunk in contents.chunks(chunk_len) {
+            // Waits for the screen to catch up instead of it skipping data
+            while event_tx.len() >= defaults.event_channel_capacity / 2 {
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+            let _ = event_tx.send(SerialEvent::Data(chunk.into()));
+            if let Some(delay) = chunk_delay {
+                tokio::time::sleep(delay).await;
+            }
+        }
+        std::future::pending::<()>().await;
+    };
+    // Runs until the user quits, everything else is dropped by `run_stdin_input`
+    tokio::select! {
+        _ = feed => {}
+        _ = async {
+            while let Some(msg) = command_rx.recv().await {
+                if matches!(msg, SerialMessage::Shutdown) {
+                    break;
+                }
+            }
+        } => {}
+    }
+    drop(command_rx);
+    let _ = event_tx.send(SerialEvent::ConnectionClosed);
+    let _ = output.await;
+    let _ = input.await;
+    ensure_terminal_cleanup(stdout);
+    Ok(())
+}
+
+/// Splits `contents` into the lines sent by [`send_file()`], keeping their line
+/// endings or replacing them with `\r` when `crlf` is set.
+fn file_lines(contents: &[u8], crlf: bool) -> Vec<Vec<u8>> {
+    contents
+        .split_inclusive(|&byte| byte == b'\n')
+        .map(|line| match line.strip_suffix(b"\n") {
+            Some(line) if crlf => {
+                let mut line = line.strip_suffix(b"\r").unwrap_or(line).to_vec();
+                line.push(b'\r');
+                line
+            }
+            _ => line.to_vec(),
+        })
+        .collect()
+}
+
 /// Prints a list of available serial ports to stdout.
 ///
 /// Ultimately a wrapper around [`SerialPort::available_ports()`] and may error
 /// if it is called on an unsupported platform as per [`SerialPort::available_ports()]s docs
-pub fn list_serial_ports() -> miette::Result<()> {
+pub fn list_serial_ports(format: OutputFormat) -> miette::Result<()> {
     let mut stdout = io::stdout();
     let ports = map_miette!(
         SerialPort::available_ports(),
         "Could not list available ports."
     )?;
-    for path in ports {
-        if let Some(path) = path.to_str() {
-            let line = [path, "\r\n"].concat();
-            stdout
-                .write(line.as_bytes())
-                .into_diagnostic()
-                .wrap_err("Failed to write to stdout.".red())?
-        } else {
-            continue;
+    if format == OutputFormat::Json {
+        let paths: Vec<&str> = ports.iter().filter_map(|path| path.to_str()).collect();
+        let json = serde_json::to_string_pretty(&paths).into_diagnostic()?;
+        return writeln!(stdout, "{json}")
+            .into_diagnostic()
+            .wrap_err("Failed to write to stdout.".red());
+    }
+    let ports: Vec<(&str, PortMetadata)> = ports
+        .iter()
+        .filter_map(|path| Some((path.to_str()?, PortMetadata::of(path))))
+        .collect();
+    let path_width = ports.iter().map(|(path, _)| path.len()).max().unwrap_or(0);
+    for (path, metadata) in ports {
+        let line = match (metadata.vid_pid, metadata.description) {
+            (None, None) => path.to_string(),
+            (vid_pid, description) => {
+                let vid_pid = vid_pid
+                    .map(|(vid, pid)| format!("{vid:04x}:{pid:04x}"))
+                    .unwrap_or_default();
+                let description = description.unwrap_or_default();
+                format!("{path:<path_width$}  {vid_pid:<9}  {description}")
+                    .trim_end()
+                    .to_string()
+            }
         };
+        write!(stdout, "{line}\r\n")
+            .into_diagnostic()
+            .wrap_err("Failed to write to stdout.".red())?;
+    }
+    Ok(())
+}
+
+/// What the platform tells about a serial port's device, see [`list_serial_ports`].
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PortMetadata {
+    /// The USB vendor and product IDs.
+    vid_pid: Option<(u16, u16)>,
+    /// The device's manufacturer and product, i.e. `FTDI FT232R USB UART`.
+    description: Option<String>,
+}
+
+impl PortMetadata {
+    /// Reads the metadata of the USB device behind `port` from sysfs, nothing
+    /// is known about other devices or on other platforms.
+    #[cfg(target_os = "linux")]
+    fn of(port: &Path) -> Self {
+        let Some(name) = port.file_name() else {
+            return Self::default();
+        };
+        match Path::new("/sys/class/tty")
+            .join(name)
+            .join("device")
+            .canonicalize()
+        {
+            Ok(device) => Self::from_sysfs(&device),
+            Err(_) => Self::default(),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn of(_port: &Path) -> Self {
+        Self::default()
+    }
+
+    /// Finds the USB device among the ancestors of the tty's `device` directory,
+    /// which is either the USB interface or a child of it.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    fn from_sysfs(device: &Path) -> Self {
+        let read = |dir: &Path, file: &str| {
+            std::fs::read_to_string(dir.join(file))
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let Some(usb_device) = device
+            .ancestors()
+            .take(4)
+            .find(|dir| dir.join("idVendor").is_file())
+        else {
+            return Self::default();
+        };
+        let id =
+            |file: &str| read(usb_device, file).and_then(|id| u16::from_str_radix(&id, 16).ok());
+        let description = [
+            read(usb_device, "manufacturer"),
+            read(usb_device, "product"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+        Self {
+            vid_pid: id("idVendor").zip(id("idProduct")),
+            description: Some(description).filter(|d| !d.is_empty()),
+        }
+    }
+}
+
+/// The format of the output of [`list_serial_ports`] and [`get_settings`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Meant for reading in a terminal.
+    #[default]
+    Human,
+    /// Pretty-printed JSON, meant for scripts.
+    Json,
+}
+
+/// Used as a [`value_parser`](https://docs.rs/clap/latest/clap/struct.Arg.html#method.value_parser)
+/// for the [`OutputFormat`], `human` or `json`.
+pub fn output_format_parser(s: &str) -> Result<OutputFormat, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "human" => Ok(OutputFormat::Human),
+        "json" => Ok(OutputFormat::Json),
+        _ => Err(format!(
+            "'{s}' isn't a valid format, expected 'human' or 'json'"
+        )),
+    }
+}
+
+/// Used as a [`value_parser`](https://docs.rs/clap/latest/clap/struct.Arg.html#method.value_parser)
+/// for the [`DeviceReset`], currently only `esp32`.
+pub fn device_reset_parser(s: &str) -> Result<DeviceReset, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "esp32" | "esp8266" => Ok(DeviceReset::Esp32),
+        _ => Err(format!(
+            "'{s}' isn't a known reset sequence, expected 'esp32'"
+        )),
+    }
+}
+
+/// The settings and line states of a port, written by [`get_settings()`] with
+/// [`OutputFormat::Json`].
+#[derive(Debug, serde::Serialize)]
+struct SettingsJson<'a> {
+    port: &'a str,
+    baud_rate: u32,
+    char_size: u8,
+    stop_bits: u8,
+    parity: &'a str,
+    flow_control: &'a str,
+    cts: bool,
+    dsr: bool,
+    ri: bool,
+    cd: bool,
+}
+
+/// Prints information about sericom's build, the platform and its environment
+/// to stdout, to help with troubleshooting and reporting issues.
+///
+/// `version` is the version of the `sericom` binary and `config_file` is the
+/// path given with `--config`, if any.
+pub fn print_diagnostics(version: &str, config_file: Option<PathBuf>) -> miette::Result<()> {
+    use crate::configs::{conf_dir_path, config_file_path};
+
+    let config_file = config_file_path(config_file);
+    let config_status = if config_file.is_file() {
+        "found"
+    } else {
+        "not found, using the defaults"
+    };
+    let ports = match SerialPort::available_ports() {
+        Ok(ports) if ports.is_empty() => "none found".to_string(),
+        Ok(ports) => ports
+            .iter()
+            .map(|port| port.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        Err(e) => format!("could not be listed ({e})"),
+    };
+
+    let lines = [
+        format!("sericom: {version}"),
+        format!("sericom-core: {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "platform: {} ({})",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ),
+        format!("config dir: {}", conf_dir_path().display()),
+        format!("config file: {} ({config_status})", config_file.display()),
+        format!("serial ports: {ports}"),
+        format!("clipboard: {}", crate::screen_buffer::clipboard_status()),
+    ];
+
+    let mut stdout = io::stdout();
+    for line in lines {
+        write!(stdout, "{line}\r\n")
+            .into_diagnostic()
+            .wrap_err("Failed to write to stdout.".red())?;
     }
     Ok(())
 }
@@ -308,21 +1145,117 @@ pub fn valid_baud_rate(s: &str) -> Result<u32, String> {
         Ok(baud)
     } else {
         Err(format!(
-            "'{}' is not a valid baud rate; valid baud rates include {:?}",
+            "'{}' is not a valid baud rate; valid baud rates include {:?}, use `--baud-custom` for other rates",
             baud,
             serial2_tokio::COMMON_BAUD_RATES
         ))
     }
 }
 
+/// Used as a [`value_parser`](https://docs.rs/clap/latest/clap/struct.Arg.html#method.value_parser)
+/// for baud rates that aren't in [`serial2_tokio::COMMON_BAUD_RATES`], i.e. `250000`.
+/// Any rate above `0` is accepted, opening the port fails if the platform or
+/// the adapter doesn't support it.
+pub fn custom_baud_rate(s: &str) -> Result<u32, String> {
+    match s.parse() {
+        Ok(0) => Err("The baud rate must be greater than 0".to_string()),
+        Ok(baud) => Ok(baud),
+        Err(_) => Err(format!("`{s}` isn't a valid baud rate")),
+    }
+}
+
+/// Used as a [`value_parser`](https://docs.rs/clap/latest/clap/struct.Arg.html#method.value_parser)
+/// for the number of data bits, `5` to `8`.
+pub fn data_bits_parser(s: &str) -> Result<serial2_tokio::CharSize, String> {
+    use serial2_tokio::CharSize;
+    match s {
+        "5" => Ok(CharSize::Bits5),
+        "6" => Ok(CharSize::Bits6),
+        "7" => Ok(CharSize::Bits7),
+        "8" => Ok(CharSize::Bits8),
+        _ => Err(format!(
+            "'{s}' isn't a valid number of data bits; expected 5, 6, 7 or 8"
+        )),
+    }
+}
+
+/// Used as a [`value_parser`](https://docs.rs/clap/latest/clap/struct.Arg.html#method.value_parser)
+/// for the parity, `none`, `odd` or `even`.
+pub fn parity_parser(s: &str) -> Result<serial2_tokio::Parity, String> {
+    use serial2_tokio::Parity;
+    match s.to_lowercase().as_str() {
+        "none" | "n" => Ok(Parity::None),
+        "odd" | "o" => Ok(Parity::Odd),
+        "even" | "e" => Ok(Parity::Even),
+        _ => Err(format!(
+            "'{s}' isn't a valid parity; expected none, odd or even"
+        )),
+    }
+}
+
+/// Used as a [`value_parser`](https://docs.rs/clap/latest/clap/struct.Arg.html#method.value_parser)
+/// for the number of stop bits, `1` or `2`.
+pub fn stop_bits_parser(s: &str) -> Result<serial2_tokio::StopBits, String> {
+    use serial2_tokio::StopBits;
+    match s {
+        "1" => Ok(StopBits::One),
+        "2" => Ok(StopBits::Two),
+        _ => Err(format!(
+            "'{s}' isn't a valid number of stop bits; expected 1 or 2"
+        )),
+    }
+}
+
+/// Used as a [`value_parser`](https://docs.rs/clap/latest/clap/struct.Arg.html#method.value_parser)
+/// for the flow control, `none`, `software` (XON/XOFF) or `hardware` (RTS/CTS).
+pub fn flow_control_parser(s: &str) -> Result<serial2_tokio::FlowControl, String> {
+    use serial2_tokio::FlowControl;
+    match s.to_lowercase().as_str() {
+        "none" => Ok(FlowControl::None),
+        "software" | "xon-xoff" | "xonxoff" => Ok(FlowControl::XonXoff),
+        "hardware" | "rts-cts" | "rtscts" => Ok(FlowControl::RtsCts),
+        _ => Err(format!(
+            "'{s}' isn't a valid flow control; expected none, software or hardware"
+        )),
+    }
+}
+
 /// Used as a [`value_parser`](https://docs.rs/clap/latest/clap/struct.Arg.html#method.value_parser) for [`sericom`](https://crates.io/crates/sericom)s [`clap`](https://docs.rs/clap) CLI
 /// struct to validate and parse args into a [`SeriColor`][`crate::configs::SeriColor`].
 pub fn color_parser(input: &str) -> Result<crate::configs::SeriColor, String> {
     use crate::configs::{NORMALIZER, SeriColor};
     match SeriColor::parse_from_str(input, NORMALIZER) {
         Ok(c) => Ok(c),
-        Err(valid_colors) => Err(format!("\n\nExpected one of: {}", valid_colors.join(", "))),
+        Err(valid_colors) => Err(format!(
+            "\n\nExpected one of: {}, or a color as `#RRGGBB`, `rgb(r, g, b)` or `ansi(N)`",
+            valid_colors.join(", ")
+        )),
+    }
+}
+
+/// Switches the terminal to the alternate screen in raw mode for a session,
+/// undone by [`ensure_terminal_cleanup()`].
+fn setup_terminal(config: &crate::configs::Config, title: &str) -> miette::Result<io::Stdout> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()
+        .into_diagnostic()
+        .wrap_err("Failed to enable raw mode.".red())?;
+    execute!(
+        stdout,
+        terminal::EnterAlternateScreen,
+        terminal::SetTitle(title),
+        terminal::Clear(ClearType::All),
+        event::EnableBracketedPaste,
+        cursor::MoveTo(0, 0)
+    )
+    .into_diagnostic()
+    .wrap_err("Failed to setup the terminal.".red())?;
+    if config.defaults.mouse != crate::configs::MouseMode::Off {
+        execute!(stdout, event::EnableMouseCapture)
+            .into_diagnostic()
+            .wrap_err("Failed to setup the terminal.".red())?;
     }
+    Ok(stdout)
 }
 
 fn ensure_terminal_cleanup(mut stdout: io::Stdout) {
@@ -342,7 +1275,29 @@ fn ensure_terminal_cleanup(mut stdout: io::Stdout) {
     let _ = stdout.flush();
 }
 
-fn run_file_exit_script(config: &'static crate::configs::Config, file_path: PathBuf) {
+/// Describes a session to the exit script, see [`run_file_exit_script()`].
+#[derive(Debug)]
+pub(crate) struct SessionMetadata {
+    pub(crate) port: Option<String>,
+    pub(crate) baud: u32,
+    pub(crate) start: chrono::DateTime<chrono::Local>,
+    pub(crate) end: chrono::DateTime<chrono::Local>,
+}
+
+/// Runs the config's [`Defaults::exit_script`][crate::configs::Defaults::exit_script]
+/// once `file_path` has been written.
+///
+/// The script is passed the following environment variables:
+/// - `SERICOM_OUT_FILE`: The absolute path to the file
+/// - `SERICOM_PORT`: The name of the port, unset when the session wasn't over a port
+/// - `SERICOM_BAUD`: The baud rate of the port
+/// - `SERICOM_SESSION_START` and `SERICOM_SESSION_END`: When the session started and
+///   ended, in RFC 3339 format (i.e. `2025-06-01T14:03:44.123+02:00`)
+pub(crate) fn run_file_exit_script(
+    config: &crate::configs::Config,
+    file_path: PathBuf,
+    metadata: &SessionMetadata,
+) {
     let span = tracing::span!(Level::DEBUG, "Exit script");
     let _enter = span.enter();
 
@@ -352,7 +1307,7 @@ fn run_file_exit_script(config: &'static crate::configs::Config, file_path: Path
     let full_file_path = file_path
         .canonicalize()
         .expect("All error conditions have been checked");
-    let cmd = create_platform_cmd(script_path, full_file_path);
+    let cmd = create_platform_cmd(script_path, full_file_path, metadata);
     if let Ok(output) = cmd {
         let msg = format!(
             "stdout: {}, stderr: {}",
@@ -363,37 +1318,391 @@ fn run_file_exit_script(config: &'static crate::configs::Config, file_path: Path
     }
 }
 
+/// Runs the config's [`Defaults::entry_script`][crate::configs::Defaults::entry_script],
+/// waiting until it exits or for at most
+/// [`Defaults::entry_script_timeout_ms`][crate::configs::Defaults::entry_script_timeout_ms].
+///
+/// The script is passed the `SERICOM_PORT` and `SERICOM_BAUD` environment variables.
+/// A script that fails or times out is logged and the session starts anyway.
+pub(crate) async fn run_entry_script(config: &crate::configs::Config, port_name: &str, baud: u32) {
+    let span = tracing::span!(Level::DEBUG, "Entry script");
+    let _enter = span.enter();
+
+    let Some(script_path) = config.defaults.entry_script.as_ref() else {
+        return;
+    };
+    let mut cmd = tokio::process::Command::from(platform_cmd(script_path));
+    cmd.env("SERICOM_PORT", port_name)
+        .env("SERICOM_BAUD", baud.to_string())
+        .kill_on_drop(true);
+    let timeout = std::time::Duration::from_millis(config.defaults.entry_script_timeout_ms);
+    match tokio::time::timeout(timeout, cmd.output()).await {
+        Ok(Ok(output)) => {
+            let msg = format!(
+                "status: {}, stdout: {}, stderr: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            tracing::debug!(msg);
+        }
+        Ok(Err(e)) => tracing::warn!("Failed to run the entry script: {e}"),
+        Err(_) => tracing::warn!(
+            "The entry script didn't exit within {}ms",
+            timeout.as_millis()
+        ),
+    }
+}
+
+/// Runs the [`notify_command`][crate::configs::Defaults::notify_command] for a
+/// `line` that matched [`notify_on`][crate::configs::Defaults::notify_on], without
+/// waiting for it to exit.
+pub(crate) fn spawn_notify_command(
+    script: &std::path::Path,
+    line: &str,
+) -> Result<tokio::process::Child, io::Error> {
+    use std::process::Stdio;
+
+    let mut cmd = tokio::process::Command::from(platform_cmd(script));
+    cmd.env("SERICOM_NOTIFY_LINE", line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    cmd.spawn()
+}
+
 fn create_platform_cmd(
     script: &std::path::Path,
     file_path: std::path::PathBuf,
+    metadata: &SessionMetadata,
 ) -> Result<std::process::Output, io::Error> {
+    use chrono::SecondsFormat;
+
+    let mut cmd = platform_cmd(script);
+    cmd.env("SERICOM_OUT_FILE", file_path)
+        .env("SERICOM_BAUD", metadata.baud.to_string())
+        .env(
+            "SERICOM_SESSION_START",
+            metadata.start.to_rfc3339_opts(SecondsFormat::Millis, false),
+        )
+        .env(
+            "SERICOM_SESSION_END",
+            metadata.end.to_rfc3339_opts(SecondsFormat::Millis, false),
+        );
+    if let Some(port) = &metadata.port {
+        cmd.env("SERICOM_PORT", port);
+    }
+    cmd.output()
+}
+
+/// Returns the command that runs `script`, through `powershell.exe` or `cmd.exe`
+/// on Windows.
+fn platform_cmd(script: &std::path::Path) -> std::process::Command {
     use std::process::Command;
 
     #[cfg(unix)]
     {
         Command::new(script)
-            .env("SERICOM_OUT_FILE", file_path)
-            .output()
     }
 
     #[cfg(windows)]
     {
-        let ext = script.extension().expect("Validated in initialization");
-        match ext
-            .to_ascii_lowercase()
-            .to_str()
-            .expect("Converted to ascii")
-        {
-            "ps1" => Command::new("powershell.exe")
-                .arg("-File")
-                .arg(script)
-                .env("SERICOM_OUT_FILE", file_path)
-                .output(),
-            _ => Command::new("cmd.exe")
-                .arg("/C")
-                .arg(script)
-                .env("SERICOM_OUT_FILE", file_path)
-                .output(),
+        // Scripts are validated to have one of the extensions of `is_executable`,
+        // anything other than a PowerShell script is run by `cmd.exe`
+        let is_powershell = script
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ps1"));
+        match is_powershell {
+            true => {
+                let mut cmd = Command::new("powershell.exe");
+                cmd.arg("-File").arg(script);
+                cmd
+            }
+            false => {
+                let mut cmd = Command::new("cmd.exe");
+                cmd.arg("/C").arg(script);
+                cmd
+            }
         }
     }
 }
+
+#[test]
+fn parse_port_settings() {
+    use serial2_tokio::{CharSize, FlowControl, Parity, StopBits};
+    assert_eq!(data_bits_parser("7"), Ok(CharSize::Bits7));
+    assert!(data_bits_parser("9").is_err());
+    assert_eq!(parity_parser("Even"), Ok(Parity::Even));
+    assert_eq!(parity_parser("n"), Ok(Parity::None));
+    assert!(parity_parser("mark").is_err());
+    assert_eq!(stop_bits_parser("2"), Ok(StopBits::Two));
+    assert!(stop_bits_parser("1.5").is_err());
+    assert_eq!(flow_control_parser("rts-cts"), Ok(FlowControl::RtsCts));
+    assert_eq!(flow_control_parser("software"), Ok(FlowControl::XonXoff));
+    assert!(flow_control_parser("dtr").is_err());
+    assert_eq!(device_reset_parser("ESP32"), Ok(DeviceReset::Esp32));
+    assert!(device_reset_parser("stm32").is_err());
+}
+
+#[test]
+fn parse_baud_rates() {
+    assert_eq!(valid_baud_rate("115200"), Ok(115200));
+    assert!(valid_baud_rate("250000").is_err());
+    assert_eq!(custom_baud_rate("250000"), Ok(250000));
+    assert_eq!(custom_baud_rate("1500000"), Ok(1500000));
+    assert!(custom_baud_rate("0").is_err());
+    assert!(custom_baud_rate("fast").is_err());
+}
+
+#[test]
+fn replay_delays() {
+    use std::time::Duration;
+    assert_eq!(replay_chunk_delay(960, 1.0), Duration::from_secs(1));
+    assert_eq!(replay_chunk_delay(960, 4.0), Duration::from_millis(250));
+    // Would overflow a Duration
+    assert_eq!(replay_chunk_delay(256, 1e-300), MAX_REPLAY_CHUNK_DELAY);
+    assert_eq!(replay_chunk_delay(256, 1e-6), MAX_REPLAY_CHUNK_DELAY);
+}
+
+#[test]
+fn settings_as_json() {
+    let settings = SettingsJson {
+        port: r"\\.\COM10",
+        baud_rate: 115200,
+        char_size: 8,
+        stop_bits: 1,
+        parity: "none",
+        flow_control: "none",
+        cts: true,
+        dsr: false,
+        ri: false,
+        cd: true,
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&serde_json::to_string_pretty(&settings).unwrap()).unwrap();
+    assert_eq!(json["port"], r"\\.\COM10");
+    assert_eq!(json["baud_rate"], 115200);
+    assert_eq!(json["char_size"], 8);
+    assert_eq!(json["cts"], true);
+    assert_eq!(output_format_parser("JSON"), Ok(OutputFormat::Json));
+    assert!(output_format_parser("yaml").is_err());
+}
+
+#[test]
+fn read_usb_metadata_from_sysfs() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let usb_device = root.join("usb1/1-1");
+    let tty_device = usb_device.join("1-1:1.0/ttyUSB0");
+    std::fs::create_dir_all(&tty_device).unwrap();
+    std::fs::write(usb_device.join("idVendor"), "0403\n").unwrap();
+    std::fs::write(usb_device.join("idProduct"), "6001\n").unwrap();
+    std::fs::write(usb_device.join("manufacturer"), "FTDI\n").unwrap();
+    std::fs::write(usb_device.join("product"), "FT232R USB UART\n").unwrap();
+
+    let metadata = PortMetadata::from_sysfs(&tty_device);
+    assert_eq!(metadata.vid_pid, Some((0x0403, 0x6001)));
+    assert_eq!(
+        metadata.description.as_deref(),
+        Some("FTDI FT232R USB UART")
+    );
+    // i.e. a built-in UART
+    assert_eq!(PortMetadata::from_sysfs(root), PortMetadata::default());
+}
+
+#[test]
+fn split_file_lines() {
+    let contents = b"hostname sw1\r\ninterface Gi0/1\n no shutdown";
+    assert_eq!(
+        file_lines(contents, false),
+        [
+            &b"hostname sw1\r\n"[..],
+            b"interface Gi0/1\n",
+            b" no shutdown"
+        ]
+    );
+    assert_eq!(
+        file_lines(contents, true),
+        [
+            &b"hostname sw1\r"[..],
+            b"interface Gi0/1\r",
+            b" no shutdown"
+        ]
+    );
+}
+
+#[test]
+fn highlight_changed_lines() {
+    let states = LineStates {
+        cts: true,
+        dsr: true,
+        ..Default::default()
+    };
+    let rows = states.rows(None);
+    assert_eq!(rows[0], ("Clear To Send line: true".to_string(), false));
+    assert!(rows.iter().all(|(_, changed)| !changed));
+
+    let previous = LineStates {
+        dsr: true,
+        cd: true,
+        ..Default::default()
+    };
+    let changed: Vec<bool> = states
+        .rows(Some(&previous))
+        .map(|(_, changed)| changed)
+        .to_vec();
+    assert_eq!(changed, [true, false, false, true]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn entry_script_env_and_timeout() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let script = root.join("entry.sh");
+    let out = root.join("env.txt");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\necho \"$SERICOM_PORT $SERICOM_BAUD\" > {}\n[ \"$SERICOM_BAUD\" = 0 ] && sleep 10\n",
+            out.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut config = crate::configs::Config::default();
+    config.defaults.entry_script = Some(script);
+    run_entry_script(&config, "/dev/ttyUSB0", 115200).await;
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        "/dev/ttyUSB0 115200\n"
+    );
+
+    // The session doesn't wait for a script that hangs
+    config.defaults.entry_script_timeout_ms = 100;
+    let started = std::time::Instant::now();
+    run_entry_script(&config, "/dev/ttyUSB0", 0).await;
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[cfg(unix)]
+#[test]
+fn exit_script_session_metadata() {
+    use chrono::TimeZone;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let script = root.join("exit.sh");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\necho \"$SERICOM_PORT $SERICOM_BAUD $SERICOM_SESSION_START\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let start = chrono::Local
+        .with_ymd_and_hms(2025, 6, 1, 14, 3, 44)
+        .unwrap();
+    let metadata = SessionMetadata {
+        port: Some("/dev/ttyUSB0".to_string()),
+        baud: 115200,
+        start,
+        end: start + chrono::Duration::minutes(5),
+    };
+    let output = create_platform_cmd(&script, root.join("out.txt"), &metadata).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "/dev/ttyUSB0 115200 {}\n",
+            start.to_rfc3339_opts(chrono::SecondsFormat::Millis, false)
+        )
+    );
+}
+
+#[tokio::test]
+async fn bridge_tcp_client() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (connection, mut device) = tokio::io::duplex(64);
+    let builder = SessionBuilder::new();
+    let events = builder.subscribe();
+    let session = builder.run_with(connection);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let bridge = tokio::spawn(async move {
+        let result = run_bridge(&session, events, listener).await;
+        session.shutdown().await;
+        result
+    });
+
+    let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    client.write_all(b"show version\r").await.unwrap();
+    let mut received = [0; 13];
+    device.read_exact(&mut received).await.unwrap();
+    assert_eq!(&received, b"show version\r");
+    device.write_all(b"Switch#").await.unwrap();
+    let mut received = [0; 7];
+    client.read_exact(&mut received).await.unwrap();
+    assert_eq!(&received, b"Switch#");
+
+    // The next client is accepted once the first one disconnects
+    drop(client);
+    let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    client.write_all(b"\r").await.unwrap();
+    let mut received = [0; 1];
+    device.read_exact(&mut received).await.unwrap();
+
+    // Closing the connection ends the bridge
+    drop(device);
+    bridge.await.unwrap().unwrap();
+    let mut rest = Vec::new();
+    client.read_to_end(&mut rest).await.unwrap();
+    assert!(rest.is_empty());
+}
+
+#[tokio::test]
+async fn idle_timeout_after_data_stops() {
+    use crate::serial_actor::SerialEvent;
+    use std::time::Duration;
+
+    let (event_tx, rx) = tokio::sync::broadcast::channel(16);
+    let idle = tokio::spawn(wait_for_idle(rx, Duration::from_millis(200)));
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        event_tx
+            .send(SerialEvent::Data(b"ok".to_vec().into()))
+            .unwrap();
+        // Other events don't count as activity
+        event_tx
+            .send(SerialEvent::Status("9600 8N1".into()))
+            .unwrap();
+    }
+    assert!(!idle.is_finished());
+    assert!(idle.await.unwrap());
+
+    let (event_tx, rx) = tokio::sync::broadcast::channel(16);
+    let idle = tokio::spawn(wait_for_idle(rx, Duration::from_secs(10)));
+    event_tx.send(SerialEvent::ConnectionClosed).unwrap();
+    assert!(!idle.await.unwrap());
+}
+
+#[test]
+fn absolute_file_path_skips_date_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let mut config = crate::configs::Config::default();
+    config.defaults.out_dir = root.join("out");
+    config.defaults.append_date_to_dir = true;
+
+    let path = resolve_file_path(&config, Some(root.join("abs/x.txt")), "/dev/ttyUSB0").unwrap();
+    assert_eq!(path, root.join("abs/x.txt"));
+    assert!(root.join("abs").is_dir());
+    assert!(!config.defaults.out_dir.exists());
+
+    let path = resolve_file_path(&config, None, "/dev/ttyUSB0").unwrap();
+    assert!(path.parent().unwrap().is_dir());
+}
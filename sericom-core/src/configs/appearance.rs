@@ -9,7 +9,19 @@ use std::borrow::Cow;
 /// ```toml
 /// [appearance]
 /// fg = "green"
-/// bg = "none"
+/// bg = "default"
+/// hl-fg = "black"
+/// hl-bg = "white"
+/// wrap = true
+/// # wrap-marker = "↩"
+/// timestamps = false
+/// throughput = false
+/// status-line = false
+/// # Shows all lines by default
+/// # display-filter = { include = "(?i)error", exclude = "debug" }
+/// # No ruler by default
+/// # ruler-column = 80
+/// max-fps = 30
 /// ```
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct Appearance {
@@ -17,6 +29,56 @@ pub struct Appearance {
     pub fg: SeriColor,
     #[serde(default = "default_bg")]
     pub bg: SeriColor,
+    /// The foreground color of selected text and search matches.
+    #[serde(default = "default_hl_fg")]
+    #[serde(rename = "hl-fg", alias = "hl_fg")]
+    pub hl_fg: SeriColor,
+    /// The background color of selected text and search matches.
+    #[serde(default = "default_hl_bg")]
+    #[serde(rename = "hl-bg", alias = "hl_bg")]
+    pub hl_bg: SeriColor,
+    /// Whether lines that are too long for the terminal continue on the next line.
+    /// When `false`, the characters past the last column are drawn over the last
+    /// column instead, like a terminal with auto-wrap disabled. Defaults to `true`.
+    #[serde(default = "default_wrap")]
+    pub wrap: bool,
+    /// A character drawn in the last column of lines that are too long for the
+    /// terminal and wrap onto the next line. When set, the last column is
+    /// reserved for the marker. No marker is drawn by default.
+    #[serde(default)]
+    #[serde(rename = "wrap-marker", alias = "wrap_marker")]
+    pub wrap_marker: Option<char>,
+    /// Whether to start the session showing the time each line was received
+    /// in a gutter on the left. Can be toggled during the session with
+    /// <kbd>Alt</kbd> + <kbd>t</kbd>.
+    #[serde(default)]
+    pub timestamps: bool,
+    /// Whether to start the session showing a graph of the bytes received per
+    /// second in the bottom-right corner. Can be toggled during the session with
+    /// <kbd>Alt</kbd> + <kbd>g</kbd>.
+    #[serde(default)]
+    pub throughput: bool,
+    /// Whether to reserve the last row of the terminal for a status line showing
+    /// whether the device is connected, its port and its settings.
+    #[serde(default)]
+    #[serde(rename = "status-line", alias = "status_line")]
+    pub status_line: bool,
+    /// Only displays the lines that match the filter, see [`LineFilter`][crate::configs::LineFilter].
+    /// All lines are still kept in the scrollback buffer and written to the files.
+    /// Can be changed during the session with <kbd>Alt</kbd> + <kbd>f</kbd>.
+    #[serde(default)]
+    #[serde(rename = "display-filter", alias = "display_filter")]
+    pub display_filter: crate::configs::LineFilter,
+    /// The column, starting at `1`, where a faint vertical ruler is drawn, i.e.
+    /// to spot lines longer than 80 columns. No ruler is drawn by default.
+    #[serde(default)]
+    #[serde(rename = "ruler-column", alias = "ruler_column")]
+    pub ruler_column: Option<std::num::NonZeroU16>,
+    /// The most times per second the screen is redrawn while data is being
+    /// received, the data received in between is drawn at once.
+    #[serde(default = "default_max_fps")]
+    #[serde(rename = "max-fps", alias = "max_fps")]
+    pub max_fps: std::num::NonZeroU16,
 }
 
 fn default_fg() -> SeriColor {
@@ -25,12 +87,34 @@ fn default_fg() -> SeriColor {
 fn default_bg() -> SeriColor {
     SeriColor::None
 }
+fn default_wrap() -> bool {
+    true
+}
+fn default_hl_fg() -> SeriColor {
+    SeriColor::Black
+}
+fn default_hl_bg() -> SeriColor {
+    SeriColor::White
+}
+fn default_max_fps() -> std::num::NonZeroU16 {
+    std::num::NonZeroU16::new(30).expect("Not zero")
+}
 
 impl Default for Appearance {
     fn default() -> Self {
         Self {
             fg: SeriColor::Green,
             bg: SeriColor::None,
+            hl_fg: SeriColor::Black,
+            hl_bg: SeriColor::White,
+            wrap: true,
+            wrap_marker: None,
+            timestamps: false,
+            throughput: false,
+            status_line: false,
+            display_filter: crate::configs::LineFilter::default(),
+            ruler_column: None,
+            max_fps: default_max_fps(),
         }
     }
 }
@@ -79,6 +163,9 @@ pub static VALID_SERICOLORS: &[&str] = &[
 
 /// A wrapper around [`crossterm::style::Color`] to allow for implementing serde's
 /// [`Deserialize`] beyond the default implementation from `#[derive(Deserialize)]`
+///
+/// Besides the names in [`VALID_SERICOLORS`], a color can be given as `#RRGGBB`
+/// or `rgb(r, g, b)` for [`SeriColor::Rgb`], and `ansi(N)` for [`SeriColor::Ansi`].
 #[derive(Debug, PartialEq, Clone, Eq)]
 pub enum SeriColor {
     Black,
@@ -98,6 +185,14 @@ pub enum SeriColor {
     Red,
     White,
     Yellow,
+    /// A 24-bit color, for terminals that support true color.
+    Rgb {
+        r: u8,
+        g: u8,
+        b: u8,
+    },
+    /// A color of the 256-color palette.
+    Ansi(u8),
 }
 
 impl SeriColor {
@@ -117,6 +212,8 @@ impl SeriColor {
     /// fn get_color() {
     ///     let color = SeriColor::parse_from_str("Dark-Green", NORMALIZER);
     ///     assert_eq!(color, Ok(SeriColor::DarkGreen));
+    ///     let color = SeriColor::parse_from_str("#1E90FF", NORMALIZER);
+    ///     assert_eq!(color, Ok(SeriColor::Rgb { r: 30, g: 144, b: 255 }));
     /// }
     /// ```
     pub fn parse_from_str<S, F>(input: S, normalizer: F) -> Result<Self, &'static [&'static str]>
@@ -145,8 +242,42 @@ impl SeriColor {
             "red" => Ok(SeriColor::Red),
             "white" => Ok(SeriColor::White),
             "yellow" => Ok(SeriColor::Yellow),
-            _ => Err(VALID_SERICOLORS),
+            other => Self::parse_numeric(other).ok_or(VALID_SERICOLORS),
+        }
+    }
+
+    /// Parses the `#rrggbb`, `rgb(r,g,b)` and `ansi(n)` forms of a normalized color.
+    fn parse_numeric(input: &str) -> Option<Self> {
+        fn arguments<'a>(input: &'a str, function: &str) -> Option<&'a str> {
+            input
+                .strip_prefix(function)?
+                .strip_prefix('(')?
+                .strip_suffix(')')
+        }
+
+        if let Some(hex) = input.strip_prefix('#') {
+            if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok();
+            return Some(SeriColor::Rgb {
+                r: channel(0)?,
+                g: channel(2)?,
+                b: channel(4)?,
+            });
+        }
+        if let Some(args) = arguments(input, "rgb") {
+            let channels = args
+                .split(',')
+                .map(|channel| channel.trim().parse::<u8>().ok())
+                .collect::<Option<Vec<u8>>>()?;
+            let [r, g, b] = channels[..] else {
+                return None;
+            };
+            return Some(SeriColor::Rgb { r, g, b });
         }
+        let value = arguments(input, "ansi")?.trim().parse::<u8>().ok()?;
+        Some(SeriColor::Ansi(value))
     }
 }
 
@@ -183,6 +314,65 @@ impl From<&SeriColor> for crossterm::style::Color {
             SeriColor::Red => crossterm::style::Color::Red,
             SeriColor::White => crossterm::style::Color::White,
             SeriColor::Yellow => crossterm::style::Color::Yellow,
+            &SeriColor::Rgb { r, g, b } => crossterm::style::Color::Rgb { r, g, b },
+            &SeriColor::Ansi(value) => crossterm::style::Color::AnsiValue(value),
         }
     }
 }
+
+#[test]
+fn parse_rgb_and_ansi_colors() {
+    let parse = |input: &str| SeriColor::parse_from_str(input, NORMALIZER);
+    assert_eq!(
+        parse("#1e90ff"),
+        Ok(SeriColor::Rgb {
+            r: 30,
+            g: 144,
+            b: 255
+        })
+    );
+    assert_eq!(
+        parse("#1E90FF"),
+        Ok(SeriColor::Rgb {
+            r: 30,
+            g: 144,
+            b: 255
+        })
+    );
+    assert_eq!(
+        parse("rgb(30, 144,255)"),
+        Ok(SeriColor::Rgb {
+            r: 30,
+            g: 144,
+            b: 255
+        })
+    );
+    assert_eq!(
+        parse("RGB( 0,0,0 )"),
+        Ok(SeriColor::Rgb { r: 0, g: 0, b: 0 })
+    );
+    assert_eq!(parse("ansi(208)"), Ok(SeriColor::Ansi(208)));
+    assert_eq!(parse("dark-green"), Ok(SeriColor::DarkGreen));
+    for invalid in [
+        "#1e90f",
+        "#1e90fg",
+        "#+1e90f",
+        "rgb(256,0,0)",
+        "rgb(1,2)",
+        "rgb(1,2,3,4)",
+        "rgb 1,2,3",
+        "ansi(256)",
+        "ansi()",
+        "purple",
+    ] {
+        assert_eq!(parse(invalid), Err(VALID_SERICOLORS), "{invalid}");
+    }
+    assert_eq!(
+        crossterm::style::Color::from(&SeriColor::Rgb { r: 1, g: 2, b: 3 }),
+        crossterm::style::Color::Rgb { r: 1, g: 2, b: 3 }
+    );
+    assert_eq!(
+        crossterm::style::Color::from(&SeriColor::Ansi(208)),
+        crossterm::style::Color::AnsiValue(208)
+    );
+}
@@ -0,0 +1,92 @@
+use crate::path_utils::ExpandPaths;
+use serde::{Deserialize, Deserializer};
+use std::path::PathBuf;
+
+/// Represents a single `[[capture]]` entry of the `config.toml` file.
+///
+/// Each capture writes the session's output to its own file, in addition to the
+/// file given with `-f`. A capture can filter which lines are written with
+/// `include` and `exclude` [regular expressions](https://docs.rs/regex/latest/regex/#syntax).
+///
+/// ```toml
+/// [[capture]]
+/// path = "{port}-errors.txt"
+/// include = "(?i)error|fail"
+/// exclude = "debug"
+/// ```
+///
+/// Relative paths are created within the `out-dir` and the file's name may use
+/// the placeholders of the `file-template`.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Capture {
+    #[serde(deserialize_with = "expand_path")]
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub filter: LineFilter,
+}
+
+/// Filters the lines written to a [`Capture`]'s file, or the lines displayed with
+/// [`Appearance::display_filter`][crate::configs::Appearance::display_filter].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct LineFilter {
+    /// Only lines matching this pattern are written, all lines if `None`.
+    #[serde(default)]
+    pub include: Option<Pattern>,
+    /// Lines matching this pattern are never written.
+    #[serde(default)]
+    pub exclude: Option<Pattern>,
+}
+
+impl LineFilter {
+    /// Returns whether `line` should be written.
+    pub fn matches(&self, line: &str) -> bool {
+        self.include.as_ref().is_none_or(|p| p.0.is_match(line))
+            && !self.exclude.as_ref().is_some_and(|p| p.0.is_match(line))
+    }
+
+    /// Returns whether the filter lets every line through.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+}
+
+/// A wrapper around [`regex::Regex`] that is deserialized from a string and
+/// compared by its pattern.
+#[derive(Debug, Clone)]
+pub struct Pattern(pub regex::Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        regex::Regex::new(&s)
+            .map(Pattern)
+            .map_err(|e| serde::de::Error::custom(format!("Invalid pattern '{s}', {e}")))
+    }
+}
+
+fn expand_path<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let p = PathBuf::deserialize(deserializer)?
+        .get_expanded_path()
+        .ok_or(Error::custom("Error expanding path."))?;
+    if p.file_name().is_none() {
+        return Err(Error::custom(format!(
+            "Invalid capture path '{}', Expected a path to a file",
+            p.display()
+        )));
+    }
+    Ok(p)
+}
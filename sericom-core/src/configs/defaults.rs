@@ -1,4 +1,4 @@
-use crate::path_utils::{ExpandPaths, is_executable};
+use crate::path_utils::{DEFAULT_FILE_TEMPLATE, EXECUTABLE_HINT, ExpandPaths, is_executable};
 use serde::{Deserialize, Deserializer};
 use std::path::PathBuf;
 
@@ -18,37 +18,508 @@ use std::path::PathBuf;
 /// debug-dir = "./"
 /// # No exit script by default
 /// # exit-script = "/path/to/script"
+/// # No entry script by default
+/// # entry-script = "/path/to/script"
+/// entry-script-timeout-ms = 30000
+/// file-template = "{port}-{timestamp}.txt"
+/// append-date-to-dir = false
+/// dir-date-format = "%Y-%m-%d"
+/// no-scrollback = false
+/// scrollback = 10000
+/// # Off by default
+/// # idle-gap-ms = 5000
+/// event-channel-capacity = 128
+/// command-channel-capacity = 100
+/// ui-channel-capacity = 100
+/// always-capture = false
+/// # Off by default
+/// # display-rate-limit = 64
+/// trim-trailing-ws = false
+/// reset-sequence = "\u001Bc"
+/// expand-tabs-in-file = false
+/// file-strip-ansi = false
+/// sanitize-paste = false
+/// on-reconnect = []
+/// reconnect-retries = 30
+/// reconnect-interval-ms = 1000
+/// mouse = "full"
+/// bell = "none"
+/// error-patterns = []
+/// notify-on = []
+/// # No notify command by default
+/// # notify-command = "/path/to/script"
+/// file-bom = false
+/// file-mode = "truncate"
+/// # Off by default
+/// # max-file-bytes = 104857600
+/// # max-files = 10
+/// timestamp-output = false
+/// search-ignore-case = true
+/// search-wrap = true
+/// # Off by default
+/// # paste-chunk-bytes = 256
+/// paste-chunk-delay-ms = 50
+/// # Off by default
+/// # paste-confirm-bytes = 4096
 /// ```
 ///
 /// [`current_dir`]: std::env::current_dir()
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct Defaults {
-    #[serde(rename = "out-dir")]
+    #[serde(rename = "out-dir", alias = "out_dir")]
     #[serde(default = "default_out_dir")]
     #[serde(deserialize_with = "validate_dir")]
     pub out_dir: PathBuf,
 
-    #[serde(rename = "debug-dir")]
+    #[serde(rename = "debug-dir", alias = "debug_dir")]
     #[serde(default = "default_out_dir")]
     #[serde(deserialize_with = "validate_dir")]
     pub debug_dir: PathBuf,
 
-    #[serde(rename = "exit-script")]
+    #[serde(rename = "exit-script", alias = "exit_script")]
     #[serde(default)]
     #[serde(deserialize_with = "is_script")]
     pub exit_script: Option<PathBuf>,
+
+    /// Path to a script that is run before the session starts, i.e. to power-cycle
+    /// the device. It is passed the `SERICOM_PORT` and `SERICOM_BAUD` environment variables.
+    #[serde(rename = "entry-script", alias = "entry_script")]
+    #[serde(default)]
+    #[serde(deserialize_with = "is_script")]
+    pub entry_script: Option<PathBuf>,
+
+    /// The longest time to wait for [`Self::entry_script`] to exit before the
+    /// session starts without it.
+    #[serde(rename = "entry-script-timeout-ms", alias = "entry_script_timeout_ms")]
+    #[serde(default = "default_entry_script_timeout_ms")]
+    pub entry_script_timeout_ms: u64,
+
+    /// The template used to name files when `-f` is given without a path.
+    /// See [`resolve_file_template`][crate::path_utils::resolve_file_template].
+    #[serde(rename = "file-template", alias = "file_template")]
+    #[serde(default = "default_file_template")]
+    #[serde(deserialize_with = "validate_file_template")]
+    pub file_template: String,
+
+    /// Whether files should be created within a subdirectory of `out_dir` named
+    /// after the current date, formatted with `dir_date_format`.
+    #[serde(rename = "append-date-to-dir", alias = "append_date_to_dir")]
+    #[serde(default)]
+    pub append_date_to_dir: bool,
+
+    /// A [`chrono` format string](https://docs.rs/chrono/latest/chrono/format/strftime/index.html)
+    /// used to name the subdirectory when `append_date_to_dir` is enabled.
+    #[serde(rename = "dir-date-format", alias = "dir_date_format")]
+    #[serde(default = "default_dir_date_format")]
+    #[serde(deserialize_with = "validate_date_format")]
+    pub dir_date_format: String,
+
+    /// Only keep the lines visible on the screen in memory and disable scrolling.
+    #[serde(rename = "no-scrollback", alias = "no_scrollback")]
+    #[serde(default)]
+    pub no_scrollback: bool,
+
+    /// The maximum number of lines kept in the session's history, `0` keeps every line.
+    #[serde(rename = "scrollback")]
+    #[serde(default = "default_scrollback")]
+    pub scrollback: usize,
+
+    /// Writes a `--- idle 12s ---` line to the output file when no data has been
+    /// received for longer than this many milliseconds, `0` is the same as not setting it.
+    #[serde(rename = "idle-gap-ms", alias = "idle_gap_ms")]
+    #[serde(default)]
+    pub idle_gap_ms: Option<u64>,
+
+    /// The capacity of the channel that broadcasts the data received from the
+    /// device to the tasks. Tasks that fall behind by more than this many
+    /// messages skip the oldest messages.
+    #[serde(rename = "event-channel-capacity", alias = "event_channel_capacity")]
+    #[serde(default = "default_event_channel_capacity")]
+    #[serde(deserialize_with = "validate_channel_capacity")]
+    pub event_channel_capacity: usize,
+
+    /// The capacity of the channel that sends commands (i.e. user input) to the device.
+    #[serde(
+        rename = "command-channel-capacity",
+        alias = "command_channel_capacity"
+    )]
+    #[serde(default = "default_channel_capacity")]
+    #[serde(deserialize_with = "validate_channel_capacity")]
+    pub command_channel_capacity: usize,
+
+    /// The capacity of the channel that sends UI commands (i.e. scrolling) to the screen.
+    #[serde(rename = "ui-channel-capacity", alias = "ui_channel_capacity")]
+    #[serde(default = "default_channel_capacity")]
+    #[serde(deserialize_with = "validate_channel_capacity")]
+    pub ui_channel_capacity: usize,
+
+    /// Writes every session to a file, as if `-f` was given without a path,
+    /// naming the file with `file_template`.
+    #[serde(rename = "always-capture", alias = "always_capture")]
+    #[serde(default)]
+    pub always_capture: bool,
+
+    /// Limits how many KB/s of the data received from the device are displayed,
+    /// to keep the screen responsive when a device sends large amounts of data.
+    /// Everything is still written to the output file. `0` disables the limit.
+    #[serde(rename = "display-rate-limit", alias = "display_rate_limit")]
+    #[serde(default)]
+    pub display_rate_limit: Option<u64>,
+
+    /// Removes the spaces and tabs at the end of each line written to the output file
+    /// when it is plain text, see [`Defaults::file_strip_ansi`]. A raw capture is
+    /// written as it was received.
+    #[serde(rename = "trim-trailing-ws", alias = "trim_trailing_ws")]
+    #[serde(default)]
+    pub trim_trailing_ws: bool,
+
+    /// The sequence sent to the device when resetting the terminal with
+    /// <kbd>Alt</kbd> + <kbd>r</kbd>, defaults to `ESC c` (Reset to Initial State).
+    #[serde(rename = "reset-sequence", alias = "reset_sequence")]
+    #[serde(default = "default_reset_sequence")]
+    pub reset_sequence: String,
+
+    /// Expands the tabs written to the output file into spaces, like they are
+    /// displayed on the screen. Tabs are written as-is by default.
+    #[serde(rename = "expand-tabs-in-file", alias = "expand_tabs_in_file")]
+    #[serde(default)]
+    pub expand_tabs_in_file: bool,
+
+    /// Removes the escape sequences (i.e. colors and cursor movement) and control
+    /// characters, other than newlines, carriage returns and tabs, from the data
+    /// written to the output file so that it is plain text. The screen isn't affected.
+    #[serde(rename = "file-strip-ansi", alias = "file_strip_ansi")]
+    #[serde(default)]
+    pub file_strip_ansi: bool,
+
+    /// Removes the control characters, other than newlines and tabs, from text that
+    /// is pasted before sending it to the device, i.e. escape sequences.
+    #[serde(rename = "sanitize-paste", alias = "sanitize_paste")]
+    #[serde(default)]
+    pub sanitize_paste: bool,
+
+    /// Commands sent to the device, each followed by a carriage return, after
+    /// sericom reconnects to it, i.e. to restore `terminal length 0`. They are
+    /// not sent when first connecting.
+    #[serde(rename = "on-reconnect", alias = "on_reconnect")]
+    #[serde(default)]
+    pub on_reconnect: Vec<String>,
+
+    /// The number of attempts to reopen the port with `--reconnect` before giving
+    /// up, `0` keeps trying until the session is closed.
+    #[serde(rename = "reconnect-retries", alias = "reconnect_retries")]
+    #[serde(default = "default_reconnect_retries")]
+    pub reconnect_retries: u32,
+
+    /// The time to wait before the first attempt to reopen the port with `--reconnect`,
+    /// doubled after each failed attempt up to 30 seconds.
+    #[serde(rename = "reconnect-interval-ms", alias = "reconnect_interval_ms")]
+    #[serde(default = "default_reconnect_interval_ms")]
+    pub reconnect_interval_ms: u64,
+
+    /// Which mouse actions sericom handles, see [`MouseMode`].
+    #[serde(default)]
+    pub mouse: MouseMode,
+
+    /// What happens when the device sends the bell character (`BEL`, `0x07`),
+    /// see [`BellMode`].
+    #[serde(default)]
+    pub bell: BellMode,
+
+    /// [Regular expressions](https://docs.rs/regex/latest/regex/#syntax) matched
+    /// against each line received from the device. With `--output-on-error-only`,
+    /// a matching line causes the session's output to be written.
+    #[serde(rename = "error-patterns", alias = "error_patterns")]
+    #[serde(default)]
+    pub error_patterns: Vec<crate::configs::Pattern>,
+
+    /// [Regular expressions](https://docs.rs/regex/latest/regex/#syntax) matched
+    /// against each line received from the device, without its escape sequences.
+    /// A matching line makes the terminal beep and runs the [`notify_command`][Self::notify_command].
+    #[serde(rename = "notify-on", alias = "notify_on")]
+    #[serde(default)]
+    pub notify_on: Vec<crate::configs::Pattern>,
+
+    /// Path to a script that is run when a line matches [`notify_on`][Self::notify_on],
+    /// i.e. to send a desktop notification. It is passed the line in the
+    /// `SERICOM_NOTIFY_LINE` environment variable.
+    #[serde(rename = "notify-command", alias = "notify_command")]
+    #[serde(default)]
+    #[serde(deserialize_with = "is_script")]
+    pub notify_command: Option<PathBuf>,
+
+    /// Starts the files with a UTF-8 byte order mark, for tools (i.e. on Windows)
+    /// that otherwise misdetect the encoding of the file.
+    #[serde(rename = "file-bom", alias = "file_bom")]
+    #[serde(default)]
+    pub file_bom: bool,
+
+    /// Whether an existing file is overwritten or appended to, see [`FileMode`].
+    #[serde(rename = "file-mode", alias = "file_mode")]
+    #[serde(default)]
+    pub file_mode: FileMode,
+
+    /// Once a file grows past this many bytes, it is closed and the output
+    /// continues in a new file named after it, i.e. `capture.txt.1`, `capture.txt.2`.
+    #[serde(rename = "max-file-bytes", alias = "max_file_bytes")]
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_max_file_bytes")]
+    pub max_file_bytes: Option<u64>,
+
+    /// The most files a file is split into with [`Self::max_file_bytes`], including
+    /// the first one. Once there are more, the oldest of the numbered files is deleted.
+    #[serde(rename = "max-files", alias = "max_files")]
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_max_files")]
+    pub max_files: Option<u32>,
+
+    /// Prefixes each line written to the files with the time it was received.
+    #[serde(rename = "timestamp-output", alias = "timestamp_output")]
+    #[serde(default)]
+    pub timestamp_output: bool,
+
+    /// Whether searching the history ignores the case of the text.
+    #[serde(rename = "search-ignore-case", alias = "search_ignore_case")]
+    #[serde(default = "default_true")]
+    pub search_ignore_case: bool,
+
+    /// Whether searching the history continues from the other end of the
+    /// history once there are no more matches.
+    #[serde(rename = "search-wrap", alias = "search_wrap")]
+    #[serde(default = "default_true")]
+    pub search_wrap: bool,
+
+    /// Splits pasted text into chunks of at most this many bytes that are sent
+    /// [`paste_chunk_delay_ms`][Self::paste_chunk_delay_ms] apart, for devices
+    /// that drop input when a large paste arrives at once.
+    #[serde(rename = "paste-chunk-bytes", alias = "paste_chunk_bytes")]
+    #[serde(default)]
+    #[serde(deserialize_with = "validate_paste_bytes")]
+    pub paste_chunk_bytes: Option<usize>,
+
+    /// The delay between the chunks of a paste, see [`paste_chunk_bytes`][Self::paste_chunk_bytes].
+    #[serde(rename = "paste-chunk-delay-ms", alias = "paste_chunk_delay_ms")]
+    #[serde(default = "default_paste_chunk_delay_ms")]
+    pub paste_chunk_delay_ms: u64,
+
+    /// Asks for confirmation before sending pastes larger than this many bytes.
+    #[serde(rename = "paste-confirm-bytes", alias = "paste_confirm_bytes")]
+    #[serde(default)]
+    pub paste_confirm_bytes: Option<usize>,
+}
+
+/// Which mouse actions sericom handles, see [`Defaults::mouse`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MouseMode {
+    /// Scrolling and selecting text to copy it.
+    #[default]
+    Full,
+    /// Only scrolling, text can't be selected.
+    #[serde(alias = "scroll_only")]
+    ScrollOnly,
+    /// The mouse isn't captured, leaving it to the terminal, i.e. to use the
+    /// terminal's own text selection.
+    Off,
+}
+
+/// What happens when the device sends the bell character, see [`Defaults::bell`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BellMode {
+    /// The bell is ignored.
+    #[default]
+    None,
+    /// The bell is passed on to the terminal, which beeps or flashes
+    /// depending on its own settings.
+    Audible,
+    /// The screen's colors are briefly inverted.
+    Visual,
+}
+
+/// What happens when the file written to already exists, see [`Defaults::file_mode`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileMode {
+    /// The file is overwritten.
+    #[default]
+    Truncate,
+    /// The output is added to the end of the file, after a line marking
+    /// where the session was resumed.
+    Append,
 }
 
+/// The smallest capacity allowed for the channels configured in [`Defaults`].
+pub const MIN_CHANNEL_CAPACITY: usize = 8;
+/// The largest capacity allowed for the channels configured in [`Defaults`].
+pub const MAX_CHANNEL_CAPACITY: usize = 1 << 16;
+
 impl Default for Defaults {
     fn default() -> Self {
         Self {
             out_dir: default_out_dir(),
             debug_dir: default_out_dir(),
             exit_script: None,
+            entry_script: None,
+            entry_script_timeout_ms: default_entry_script_timeout_ms(),
+            file_template: default_file_template(),
+            append_date_to_dir: false,
+            dir_date_format: default_dir_date_format(),
+            no_scrollback: false,
+            scrollback: default_scrollback(),
+            idle_gap_ms: None,
+            event_channel_capacity: default_event_channel_capacity(),
+            command_channel_capacity: default_channel_capacity(),
+            ui_channel_capacity: default_channel_capacity(),
+            always_capture: false,
+            display_rate_limit: None,
+            trim_trailing_ws: false,
+            reset_sequence: default_reset_sequence(),
+            expand_tabs_in_file: false,
+            file_strip_ansi: false,
+            sanitize_paste: false,
+            on_reconnect: Vec::new(),
+            reconnect_retries: default_reconnect_retries(),
+            reconnect_interval_ms: default_reconnect_interval_ms(),
+            mouse: MouseMode::Full,
+            bell: BellMode::None,
+            error_patterns: Vec::new(),
+            notify_on: Vec::new(),
+            notify_command: None,
+            file_bom: false,
+            file_mode: FileMode::Truncate,
+            max_file_bytes: None,
+            max_files: None,
+            timestamp_output: false,
+            search_ignore_case: true,
+            search_wrap: true,
+            paste_chunk_bytes: None,
+            paste_chunk_delay_ms: default_paste_chunk_delay_ms(),
+            paste_confirm_bytes: None,
         }
     }
 }
 
+fn default_file_template() -> String {
+    DEFAULT_FILE_TEMPLATE.to_string()
+}
+
+impl Defaults {
+    /// Returns an iterator over [`Self::on_reconnect`] with each command terminated
+    /// by a carriage return, ready to be written to the serial connection.
+    pub fn on_reconnect_lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.on_reconnect.iter().map(|cmd| format!("{cmd}\r"))
+    }
+}
+
+fn default_reset_sequence() -> String {
+    "\u{1b}c".to_string()
+}
+
+const fn default_event_channel_capacity() -> usize {
+    128
+}
+
+const fn default_channel_capacity() -> usize {
+    100
+}
+
+fn validate_channel_capacity<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let capacity = usize::deserialize(deserializer)?;
+    if !(MIN_CHANNEL_CAPACITY..=MAX_CHANNEL_CAPACITY).contains(&capacity) {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid channel capacity '{capacity}', Expected a value from {MIN_CHANNEL_CAPACITY} to {MAX_CHANNEL_CAPACITY}"
+        )));
+    }
+    Ok(capacity)
+}
+
+const fn default_reconnect_retries() -> u32 {
+    30
+}
+
+const fn default_reconnect_interval_ms() -> u64 {
+    1000
+}
+
+const fn default_entry_script_timeout_ms() -> u64 {
+    30_000
+}
+
+const fn default_paste_chunk_delay_ms() -> u64 {
+    50
+}
+
+fn validate_paste_bytes<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let bytes = Option::<usize>::deserialize(deserializer)?;
+    if bytes == Some(0) {
+        return Err(serde::de::Error::custom(
+            "Invalid paste-chunk-bytes '0', Expected at least 1 byte",
+        ));
+    }
+    Ok(bytes)
+}
+
+fn validate_max_file_bytes<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let bytes = Option::<u64>::deserialize(deserializer)?;
+    if bytes == Some(0) {
+        return Err(serde::de::Error::custom(
+            "Invalid max-file-bytes '0', Expected at least 1 byte",
+        ));
+    }
+    Ok(bytes)
+}
+
+fn validate_max_files<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let files = Option::<u32>::deserialize(deserializer)?;
+    if let Some(files @ 0..2) = files {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid max-files '{files}', Expected at least 2 files"
+        )));
+    }
+    Ok(files)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_scrollback() -> usize {
+    crate::screen_buffer::MAX_SCROLLBACK
+}
+
+fn default_dir_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+fn validate_date_format<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    crate::path_utils::validate_date_format(&s).map_err(serde::de::Error::custom)
+}
+
+fn validate_file_template<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    crate::path_utils::validate_file_template(&s).map_err(serde::de::Error::custom)
+}
+
 fn default_out_dir() -> PathBuf {
     use std::env::current_dir;
     current_dir().unwrap_or(PathBuf::from("./"))
@@ -87,10 +558,28 @@ where
     }
 
     if !is_executable(&p) {
-        return Err(serde::de::Error::custom(
-            "Invalid file type, Make sure the file is executable",
-        ));
+        return Err(serde::de::Error::custom(format!(
+            "Invalid file type, {EXECUTABLE_HINT}"
+        )));
     }
 
     Ok(Some(p))
 }
+
+#[cfg(windows)]
+#[test]
+fn windows_exit_script() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let parse = |name: &str| {
+        let script = root.join(name);
+        std::fs::write(&script, "@echo off\r\n").unwrap();
+        let toml = format!("exit-script = '{}'", script.display());
+        toml::from_str::<Defaults>(&toml).map(|defaults| defaults.exit_script)
+    };
+    assert_eq!(parse("format.bat").unwrap(), Some(root.join("format.bat")));
+    assert!(parse("format.PS1").is_ok());
+    // Scripts without an extension can't be run and are rejected up front
+    let err = parse("format").unwrap_err();
+    assert!(err.message().contains(".ps1"));
+}
@@ -26,6 +26,14 @@ pub enum ConfigError {
         "Config already initialized.\nPlease report the bug to {}", "https://github.com/tkatter/sericom".bold()
     )]
     AlreadyInitialized,
+    #[error("Profile '{}' is not defined in the config file", .0.as_str().bold())]
+    #[diagnostic(help("Profiles are defined as `[profiles.<NAME>]` tables"))]
+    UnknownProfile(String),
+    #[error("Could not find the config file '{}'", .0.display().to_string().bold())]
+    #[diagnostic(help(
+        "The config file is set with `--config` or the `SERICOM_CONFIG` environment variable"
+    ))]
+    ConfigNotFound(std::path::PathBuf),
 }
 
 /// A wrapper around [`toml::de::Error`] to print custom error messages with [`miette`].
@@ -0,0 +1,567 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+
+/// Represents the `[keybindings]` table of the `config.toml` file.
+///
+/// The `[keybindings]` table changes what some of sericom's keys do.
+///
+/// ```toml
+/// [keybindings]
+/// ctrl-l = "clear-screen"
+/// home = "\u001B[H"
+/// end = "\u001B[F"
+/// page-keys = "scroll"
+/// quit = "ctrl+q"
+/// clear = "ctrl+l"
+/// break = "alt+b"
+/// scroll-top = "f1"
+/// scroll-bottom = "f2"
+/// history-prev = "alt+up"
+/// history-next = "alt+down"
+/// help = "f12"
+/// search = "alt+/"
+/// search-next = "alt+n"
+/// search-prev = "alt+shift+n"
+/// filter = "alt+f"
+/// color = "alt+p"
+/// toggle-timestamps = "alt+t"
+/// toggle-graph = "alt+g"
+/// toggle-control-chars = "alt+c"
+/// toggle-hex-view = "alt+x"
+/// reset = "alt+r"
+/// dump-scrollback = "alt+s"
+/// # Unbound by default
+/// # copy = "alt+y"
+/// # toggle-dtr = "alt+d"
+/// # toggle-rts = "alt+w"
+/// ```
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Keybindings {
+    /// What the [`Keybindings::clear`] key clears, see [`ClearAction`].
+    #[serde(default)]
+    #[serde(rename = "ctrl-l", alias = "ctrl_l")]
+    pub ctrl_l: ClearAction,
+    /// The sequence sent to the device for <kbd>Home</kbd>, defaults to `ESC[H`.
+    /// Devices differ, others expect `ESC[1~` or `ESC OH`.
+    #[serde(default = "default_home")]
+    pub home: String,
+    /// The sequence sent to the device for <kbd>End</kbd>, defaults to `ESC[F`.
+    /// Devices differ, others expect `ESC[4~` or `ESC OF`.
+    #[serde(default = "default_end")]
+    pub end: String,
+    /// What <kbd>PageUp</kbd> and <kbd>PageDown</kbd> do, see [`PageKeys`].
+    #[serde(default)]
+    #[serde(rename = "page-keys", alias = "page_keys")]
+    pub page_keys: PageKeys,
+    /// Ends the session, defaults to <kbd>Ctrl</kbd> + <kbd>q</kbd>.
+    #[serde(default = "default_quit")]
+    pub quit: KeySpec,
+    /// Clears the screen, defaults to <kbd>Ctrl</kbd> + <kbd>l</kbd>.
+    #[serde(default = "default_clear")]
+    pub clear: KeySpec,
+    /// Sends the break signal, defaults to <kbd>Alt</kbd> + <kbd>b</kbd>.
+    #[serde(default = "default_break")]
+    #[serde(rename = "break")]
+    pub send_break: KeySpec,
+    /// Scrolls to the top of the session's history, defaults to <kbd>F1</kbd>.
+    #[serde(default = "default_scroll_top")]
+    #[serde(rename = "scroll-top", alias = "scroll_top")]
+    pub scroll_top: KeySpec,
+    /// Scrolls to the bottom of the session's history, defaults to <kbd>F2</kbd>.
+    #[serde(default = "default_scroll_bottom")]
+    #[serde(rename = "scroll-bottom", alias = "scroll_bottom")]
+    pub scroll_bottom: KeySpec,
+    /// Copies the selected text, unbound by default as the text is copied
+    /// when releasing the mouse button.
+    #[serde(default)]
+    pub copy: Option<KeySpec>,
+    /// Re-sends the previous line typed with `--local-history`, defaults to
+    /// <kbd>Alt</kbd> + <kbd>Up</kbd>.
+    #[serde(default = "default_history_prev")]
+    #[serde(rename = "history-prev", alias = "history_prev")]
+    pub history_prev: KeySpec,
+    /// Re-sends the next line typed with `--local-history`, defaults to
+    /// <kbd>Alt</kbd> + <kbd>Down</kbd>.
+    #[serde(default = "default_history_next")]
+    #[serde(rename = "history-next", alias = "history_next")]
+    pub history_next: KeySpec,
+    /// Shows/hides a list of the keys over the bottom of the screen, defaults
+    /// to <kbd>F12</kbd>.
+    #[serde(default = "default_help")]
+    pub help: KeySpec,
+    /// Toggles the Data Terminal Ready line, unbound by default.
+    #[serde(default)]
+    #[serde(rename = "toggle-dtr", alias = "toggle_dtr")]
+    pub toggle_dtr: Option<KeySpec>,
+    /// Toggles the Request To Send line, unbound by default.
+    #[serde(default)]
+    #[serde(rename = "toggle-rts", alias = "toggle_rts")]
+    pub toggle_rts: Option<KeySpec>,
+    /// Opens the prompt to search the session's history, defaults to
+    /// <kbd>Alt</kbd> + <kbd>/</kbd>.
+    #[serde(default = "default_search")]
+    pub search: KeySpec,
+    /// Finds the next (older) match of the search, defaults to <kbd>Alt</kbd> + <kbd>n</kbd>.
+    #[serde(default = "default_search_next")]
+    #[serde(rename = "search-next", alias = "search_next")]
+    pub search_next: KeySpec,
+    /// Finds the previous (newer) match of the search, defaults to
+    /// <kbd>Alt</kbd> + <kbd>Shift</kbd> + <kbd>n</kbd>.
+    #[serde(default = "default_search_prev")]
+    #[serde(rename = "search-prev", alias = "search_prev")]
+    pub search_prev: KeySpec,
+    /// Opens the prompt to only display the lines matching a pattern, defaults
+    /// to <kbd>Alt</kbd> + <kbd>f</kbd>.
+    #[serde(default = "default_filter")]
+    #[serde(rename = "filter")]
+    pub display_filter: KeySpec,
+    /// Opens the prompt to change the text color, defaults to <kbd>Alt</kbd> + <kbd>p</kbd>.
+    #[serde(default = "default_color")]
+    pub color: KeySpec,
+    /// Shows/hides the time each line was received, defaults to <kbd>Alt</kbd> + <kbd>t</kbd>.
+    #[serde(default = "default_toggle_timestamps")]
+    #[serde(rename = "toggle-timestamps", alias = "toggle_timestamps")]
+    pub toggle_timestamps: KeySpec,
+    /// Shows/hides the graph of the bytes received per second, defaults to
+    /// <kbd>Alt</kbd> + <kbd>g</kbd>.
+    #[serde(default = "default_toggle_graph")]
+    #[serde(rename = "toggle-graph", alias = "toggle_graph")]
+    pub toggle_graph: KeySpec,
+    /// Shows/hides the control characters received in caret notation, defaults
+    /// to <kbd>Alt</kbd> + <kbd>c</kbd>.
+    #[serde(default = "default_toggle_control_chars")]
+    #[serde(rename = "toggle-control-chars", alias = "toggle_control_chars")]
+    pub toggle_control_chars: KeySpec,
+    /// Switches between the session's lines and a hexdump of the bytes received,
+    /// defaults to <kbd>Alt</kbd> + <kbd>x</kbd>.
+    #[serde(default = "default_toggle_hex_view")]
+    #[serde(rename = "toggle-hex-view", alias = "toggle_hex_view")]
+    pub toggle_hex_view: KeySpec,
+    /// Resets the device's terminal and the screen, see
+    /// [`Defaults::reset_sequence`][super::Defaults::reset_sequence], defaults to
+    /// <kbd>Alt</kbd> + <kbd>r</kbd>.
+    #[serde(default = "default_reset")]
+    pub reset: KeySpec,
+    /// Opens the prompt to save the session's history to a file, defaults to
+    /// <kbd>Alt</kbd> + <kbd>s</kbd>.
+    #[serde(default = "default_dump_scrollback")]
+    #[serde(rename = "dump-scrollback", alias = "dump_scrollback")]
+    pub dump_scrollback: KeySpec,
+}
+
+fn default_home() -> String {
+    "\u{1b}[H".to_string()
+}
+fn default_end() -> String {
+    "\u{1b}[F".to_string()
+}
+
+fn default_quit() -> KeySpec {
+    KeySpec::new(KeyCode::Char('q'), KeyModifiers::CONTROL)
+}
+fn default_clear() -> KeySpec {
+    KeySpec::new(KeyCode::Char('l'), KeyModifiers::CONTROL)
+}
+fn default_break() -> KeySpec {
+    KeySpec::new(KeyCode::Char('b'), KeyModifiers::ALT)
+}
+fn default_scroll_top() -> KeySpec {
+    KeySpec::new(KeyCode::F(1), KeyModifiers::NONE)
+}
+fn default_scroll_bottom() -> KeySpec {
+    KeySpec::new(KeyCode::F(2), KeyModifiers::NONE)
+}
+fn default_history_prev() -> KeySpec {
+    KeySpec::new(KeyCode::Up, KeyModifiers::ALT)
+}
+fn default_history_next() -> KeySpec {
+    KeySpec::new(KeyCode::Down, KeyModifiers::ALT)
+}
+fn default_help() -> KeySpec {
+    KeySpec::new(KeyCode::F(12), KeyModifiers::NONE)
+}
+fn default_search() -> KeySpec {
+    KeySpec::new(KeyCode::Char('/'), KeyModifiers::ALT)
+}
+fn default_search_next() -> KeySpec {
+    KeySpec::new(KeyCode::Char('n'), KeyModifiers::ALT)
+}
+fn default_search_prev() -> KeySpec {
+    KeySpec::new(
+        KeyCode::Char('N'),
+        KeyModifiers::ALT.union(KeyModifiers::SHIFT),
+    )
+}
+fn default_filter() -> KeySpec {
+    KeySpec::new(KeyCode::Char('f'), KeyModifiers::ALT)
+}
+fn default_color() -> KeySpec {
+    KeySpec::new(KeyCode::Char('p'), KeyModifiers::ALT)
+}
+fn default_toggle_timestamps() -> KeySpec {
+    KeySpec::new(KeyCode::Char('t'), KeyModifiers::ALT)
+}
+fn default_toggle_graph() -> KeySpec {
+    KeySpec::new(KeyCode::Char('g'), KeyModifiers::ALT)
+}
+fn default_toggle_control_chars() -> KeySpec {
+    KeySpec::new(KeyCode::Char('c'), KeyModifiers::ALT)
+}
+fn default_toggle_hex_view() -> KeySpec {
+    KeySpec::new(KeyCode::Char('x'), KeyModifiers::ALT)
+}
+fn default_reset() -> KeySpec {
+    KeySpec::new(KeyCode::Char('r'), KeyModifiers::ALT)
+}
+fn default_dump_scrollback() -> KeySpec {
+    KeySpec::new(KeyCode::Char('s'), KeyModifiers::ALT)
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            ctrl_l: ClearAction::default(),
+            home: default_home(),
+            end: default_end(),
+            page_keys: PageKeys::default(),
+            quit: default_quit(),
+            clear: default_clear(),
+            send_break: default_break(),
+            scroll_top: default_scroll_top(),
+            scroll_bottom: default_scroll_bottom(),
+            copy: None,
+            history_prev: default_history_prev(),
+            history_next: default_history_next(),
+            help: default_help(),
+            toggle_dtr: None,
+            toggle_rts: None,
+            search: default_search(),
+            search_next: default_search_next(),
+            search_prev: default_search_prev(),
+            display_filter: default_filter(),
+            color: default_color(),
+            toggle_timestamps: default_toggle_timestamps(),
+            toggle_graph: default_toggle_graph(),
+            toggle_control_chars: default_toggle_control_chars(),
+            toggle_hex_view: default_toggle_hex_view(),
+            reset: default_reset(),
+            dump_scrollback: default_dump_scrollback(),
+        }
+    }
+}
+
+impl Keybindings {
+    /// Returns the [`KeyAction`] bound to each key.
+    pub fn actions(&self) -> HashMap<KeySpec, KeyAction> {
+        self.bindings().into_iter().collect()
+    }
+
+    /// Returns the keys that are bound and their [`KeyAction`], in the order
+    /// they are listed in [`KeyAction`].
+    fn bindings(&self) -> Vec<(KeySpec, KeyAction)> {
+        [
+            (Some(self.quit), KeyAction::Quit),
+            (Some(self.clear), KeyAction::Clear),
+            (Some(self.send_break), KeyAction::Break),
+            (Some(self.scroll_top), KeyAction::ScrollTop),
+            (Some(self.scroll_bottom), KeyAction::ScrollBottom),
+            (self.copy, KeyAction::Copy),
+            (Some(self.history_prev), KeyAction::HistoryPrev),
+            (Some(self.history_next), KeyAction::HistoryNext),
+            (Some(self.help), KeyAction::Help),
+            (self.toggle_dtr, KeyAction::ToggleDtr),
+            (self.toggle_rts, KeyAction::ToggleRts),
+            (Some(self.search), KeyAction::Search),
+            (Some(self.search_next), KeyAction::SearchNext),
+            (Some(self.search_prev), KeyAction::SearchPrev),
+            (Some(self.display_filter), KeyAction::DisplayFilter),
+            (Some(self.color), KeyAction::Color),
+            (Some(self.toggle_timestamps), KeyAction::ToggleTimestamps),
+            (Some(self.toggle_graph), KeyAction::ToggleGraph),
+            (
+                Some(self.toggle_control_chars),
+                KeyAction::ToggleControlChars,
+            ),
+            (Some(self.toggle_hex_view), KeyAction::ToggleHexView),
+            (Some(self.reset), KeyAction::Reset),
+            (Some(self.dump_scrollback), KeyAction::DumpScrollback),
+        ]
+        .into_iter()
+        .filter_map(|(key, action)| key.map(|key| (key, action)))
+        .collect()
+    }
+
+    /// Returns the keys and what they do, i.e. `"Ctrl+q quit"`, as listed with
+    /// [`Keybindings::help`].
+    pub fn help_entries(&self) -> Vec<String> {
+        let mut entries = vec![
+            format!("{} quit", self.quit),
+            format!("{} clear", self.clear),
+            format!("{} break", self.send_break),
+            format!("{} top", self.scroll_top),
+            format!("{} bottom", self.scroll_bottom),
+        ];
+        if let Some(copy) = self.copy {
+            entries.push(format!("{copy} copy"));
+        }
+        entries.extend([
+            format!("{} search", self.search),
+            format!("{} filter", self.display_filter),
+            format!("{} timestamps", self.toggle_timestamps),
+            format!("{} color", self.color),
+            format!("{} graph", self.toggle_graph),
+            format!("{} control chars", self.toggle_control_chars),
+            format!("{} hex", self.toggle_hex_view),
+            format!("{} save", self.dump_scrollback),
+            format!("{} reset", self.reset),
+        ]);
+        if let Some(toggle_dtr) = self.toggle_dtr {
+            entries.push(format!("{toggle_dtr} DTR"));
+        }
+        if let Some(toggle_rts) = self.toggle_rts {
+            entries.push(format!("{toggle_rts} RTS"));
+        }
+        entries.push(format!("{} close help", self.help));
+        entries
+    }
+}
+
+/// The actions that can be bound to a key in [`Keybindings`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    /// See [`Keybindings::quit`].
+    Quit,
+    /// See [`Keybindings::clear`].
+    Clear,
+    /// See [`Keybindings::send_break`].
+    Break,
+    /// See [`Keybindings::scroll_top`].
+    ScrollTop,
+    /// See [`Keybindings::scroll_bottom`].
+    ScrollBottom,
+    /// See [`Keybindings::copy`].
+    Copy,
+    /// See [`Keybindings::history_prev`].
+    HistoryPrev,
+    /// See [`Keybindings::history_next`].
+    HistoryNext,
+    /// See [`Keybindings::help`].
+    Help,
+    /// See [`Keybindings::toggle_dtr`].
+    ToggleDtr,
+    /// See [`Keybindings::toggle_rts`].
+    ToggleRts,
+    /// See [`Keybindings::search`].
+    Search,
+    /// See [`Keybindings::search_next`].
+    SearchNext,
+    /// See [`Keybindings::search_prev`].
+    SearchPrev,
+    /// See [`Keybindings::display_filter`].
+    DisplayFilter,
+    /// See [`Keybindings::color`].
+    Color,
+    /// See [`Keybindings::toggle_timestamps`].
+    ToggleTimestamps,
+    /// See [`Keybindings::toggle_graph`].
+    ToggleGraph,
+    /// See [`Keybindings::toggle_control_chars`].
+    ToggleControlChars,
+    /// See [`Keybindings::toggle_hex_view`].
+    ToggleHexView,
+    /// See [`Keybindings::reset`].
+    Reset,
+    /// See [`Keybindings::dump_scrollback`].
+    DumpScrollback,
+}
+
+impl KeyAction {
+    /// Returns the name of the action in the `[keybindings]` table.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::Clear => "clear",
+            Self::Break => "break",
+            Self::ScrollTop => "scroll-top",
+            Self::ScrollBottom => "scroll-bottom",
+            Self::Copy => "copy",
+            Self::HistoryPrev => "history-prev",
+            Self::HistoryNext => "history-next",
+            Self::Help => "help",
+            Self::ToggleDtr => "toggle-dtr",
+            Self::ToggleRts => "toggle-rts",
+            Self::Search => "search",
+            Self::SearchNext => "search-next",
+            Self::SearchPrev => "search-prev",
+            Self::DisplayFilter => "filter",
+            Self::Color => "color",
+            Self::ToggleTimestamps => "toggle-timestamps",
+            Self::ToggleGraph => "toggle-graph",
+            Self::ToggleControlChars => "toggle-control-chars",
+            Self::ToggleHexView => "toggle-hex-view",
+            Self::Reset => "reset",
+            Self::DumpScrollback => "dump-scrollback",
+        }
+    }
+}
+
+/// Deserializes the `[keybindings]` table, ensuring that no two actions are
+/// bound to the same key.
+pub(super) fn unique_keys<'de, D>(deserializer: D) -> Result<Keybindings, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let keybindings = Keybindings::deserialize(deserializer)?;
+    let mut seen: HashMap<KeySpec, KeyAction> = HashMap::new();
+    for (key, action) in keybindings.bindings() {
+        if let Some(other) = seen.insert(key, action) {
+            return Err(Error::custom(format!(
+                "Keybindings '{}' and '{}' are both bound to '{key}'",
+                other.name(),
+                action.name()
+            )));
+        }
+    }
+    Ok(keybindings)
+}
+
+/// A key and the modifiers held with it, i.e. `"ctrl+q"`, `"alt+shift+n"` or `"f1"`.
+///
+/// Modifiers (`ctrl`, `alt` and `shift`) are separated from the key with `+` or `-`.
+/// Keys are single characters, `f1` to `f12`, or one of `esc`, `enter`, `tab`,
+/// `backspace`, `delete`, `insert`, `home`, `end`, `pageup`, `pagedown`, `up`,
+/// `down`, `left` and `right`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeySpec {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeySpec {
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    /// Parses a key spec, returns `None` if `s` isn't a valid [`KeySpec`].
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut rest = s.trim();
+        let mut modifiers = KeyModifiers::NONE;
+        // A single character is the key itself, i.e. `-` or `+`
+        while rest.chars().count() > 1 {
+            let Some((modifier, key)) = rest.split_once(['+', '-']) else {
+                break;
+            };
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = key;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            // Without Shift, crossterm reports letters in lowercase, i.e. `Char('q')` for Ctrl+Q
+            (Some(c), None) => KeyCode::Char(c.to_ascii_lowercase()),
+            _ => match rest.to_lowercase().as_str() {
+                "esc" | "escape" => KeyCode::Esc,
+                "enter" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                key => {
+                    let f: u8 = key.strip_prefix('f')?.parse().ok()?;
+                    if !(1..=12).contains(&f) {
+                        return None;
+                    }
+                    KeyCode::F(f)
+                }
+            },
+        };
+        Some(Self { code, modifiers })
+    }
+}
+
+/// Writes the key the way it is shown to the user, i.e. `Ctrl+q` or `F12`.
+impl std::fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl"),
+            (KeyModifiers::ALT, "Alt"),
+            (KeyModifiers::SHIFT, "Shift"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::SHIFT) => {
+                write!(f, "{}", c.to_ascii_lowercase())
+            }
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+impl From<&KeyEvent> for KeySpec {
+    fn from(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeySpec {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        KeySpec::parse(&s).ok_or(serde::de::Error::custom(format!(
+            "Invalid key '{s}', Expected a key with optional modifiers, i.e. 'ctrl+q', 'alt+b' or 'f1'"
+        )))
+    }
+}
+
+/// What is cleared by a keybinding, i.e. [`Keybindings::ctrl_l`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClearAction {
+    /// Clears the screen, the lines on the screen are kept in the session's
+    /// history and can be scrolled back to.
+    #[default]
+    #[serde(alias = "clear_screen")]
+    ClearScreen,
+    /// Clears the screen and the session's history.
+    #[serde(alias = "clear_buffer")]
+    ClearBuffer,
+}
+
+/// What <kbd>PageUp</kbd> and <kbd>PageDown</kbd> do, see [`Keybindings::page_keys`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PageKeys {
+    /// Scrolls the session's history by a screen.
+    #[default]
+    Scroll,
+    /// Sends `ESC[5~`/`ESC[6~` to the device, i.e. for full-screen applications
+    /// running on the device.
+    Device,
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+
+/// Represents a single entry within the `[macros]` table of the `config.toml` file.
+///
+/// A macro is a named list of commands that are sent to the device, one after
+/// another, each followed by a carriage return (`\r`). A macro can optionally be
+/// bound to a function key (`F1` - `F12`) with `key`.
+///
+/// ```toml
+/// [macros.audit]
+/// key = "F3"
+/// commands = ["show version", "show inventory"]
+/// ```
+///
+/// Binding a macro to `F1` or `F2` will override their default behavior of
+/// scrolling to the top/bottom of the session's history.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Macro {
+    #[serde(default)]
+    #[serde(deserialize_with = "function_key")]
+    pub key: Option<u8>,
+    pub commands: Vec<String>,
+    /// The number of milliseconds to wait between commands for devices that
+    /// can't keep up, defaults to `0`.
+    #[serde(default)]
+    #[serde(rename = "delay-ms", alias = "delay_ms")]
+    pub delay_ms: u64,
+}
+
+impl Macro {
+    /// Returns an iterator over [`Self::commands`] with each command terminated
+    /// by a carriage return, ready to be written to the serial connection.
+    pub fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.commands.iter().map(|cmd| format!("{cmd}\r"))
+    }
+
+    /// Returns the time to wait between [`Self::commands`].
+    pub fn delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.delay_ms)
+    }
+}
+
+/// Returns a map of function key codes (i.e. `3` for `F3`) to the [`Macro`]
+/// that is bound to it.
+pub fn function_key_macros(macros: &HashMap<String, Macro>) -> HashMap<u8, &Macro> {
+    macros
+        .values()
+        .filter_map(|m| m.key.map(|key| (key, m)))
+        .collect()
+}
+
+fn function_key<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let s = String::deserialize(deserializer)?;
+    parse_function_key(&s)
+        .map(Some)
+        .ok_or(Error::custom(format!(
+            "Invalid key '{s}', Expected a function key from 'F1' to 'F12'"
+        )))
+}
+
+fn parse_function_key(s: &str) -> Option<u8> {
+    let code: u8 = s.trim().strip_prefix(['f', 'F'])?.parse().ok()?;
+    (1..=12).contains(&code).then_some(code)
+}
+
+/// Deserializes the `[macros]` table, ensuring that no two macros are bound
+/// to the same function key.
+pub(super) fn unique_keys<'de, D>(deserializer: D) -> Result<HashMap<String, Macro>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let macros = HashMap::<String, Macro>::deserialize(deserializer)?;
+    let mut seen: HashMap<u8, &str> = HashMap::new();
+    for (name, m) in &macros {
+        if let Some(key) = m.key
+            && let Some(other) = seen.insert(key, name)
+        {
+            return Err(Error::custom(format!(
+                "Macros '{other}' and '{name}' are both bound to 'F{key}'"
+            )));
+        }
+    }
+    Ok(macros)
+}
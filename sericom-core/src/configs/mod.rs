@@ -3,17 +3,25 @@
 //! respectively serde's [`toml`] crate.
 
 mod appearance;
+mod capture;
 mod defaults;
 pub mod errors;
+mod keybindings;
+mod macros;
+mod profiles;
 pub use appearance::*;
+pub use capture::*;
 pub use defaults::*;
+pub use keybindings::*;
+pub use macros::*;
+pub use profiles::*;
 
 use crate::{
     configs::errors::{ConfigError, TomlError},
     create_recursive,
 };
 use serde::Deserialize;
-use std::{io::Read, ops::Range, path::PathBuf, sync::OnceLock};
+use std::{collections::HashMap, io::Read, ops::Range, path::PathBuf, sync::OnceLock};
 
 /// Global value of the user's config.
 ///
@@ -25,17 +33,48 @@ pub static CONFIG: OnceLock<Config> = OnceLock::new();
 
 /// Represents the entire `config.toml` configuration file.
 ///
-/// See [`Appearance`] and [`Defaults`]
+/// See [`Appearance`], [`Defaults`], [`Keybindings`], [`Macro`], [`Profile`] and [`Capture`]
 #[derive(Default, Debug, Deserialize, PartialEq)]
 pub struct Config {
     #[serde(default)]
     pub appearance: Appearance,
     #[serde(default)]
     pub defaults: Defaults,
+    #[serde(default)]
+    #[serde(deserialize_with = "keybindings::unique_keys")]
+    pub keybindings: Keybindings,
+    #[serde(default)]
+    #[serde(deserialize_with = "macros::unique_keys")]
+    pub macros: HashMap<String, Macro>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+    /// Additional files that the session is written to, see [`Capture`].
+    #[serde(default)]
+    #[serde(rename = "capture")]
+    pub captures: Vec<Capture>,
+    /// The name of the profile selected with [`ConfigOverride::profile`].
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
 impl Config {
-    fn apply_overrides(&mut self, overrides: ConfigOverride) {
+    /// Returns the [`Profile`] that was selected for this session, if any.
+    pub fn active_profile(&self) -> Option<&Profile> {
+        self.profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+    }
+
+    /// Returns whether the session should be written to a file even when `--file`
+    /// isn't given. [`Profile::always_capture`] takes precedence over
+    /// [`Defaults::always_capture`].
+    pub fn always_capture(&self) -> bool {
+        self.active_profile()
+            .and_then(|profile| profile.always_capture)
+            .unwrap_or(self.defaults.always_capture)
+    }
+
+    fn apply_overrides(&mut self, overrides: ConfigOverride) -> Result<(), ConfigError> {
         if let Some(color) = overrides.color {
             self.appearance.fg = color;
         }
@@ -45,6 +84,28 @@ impl Config {
         if let Some(script) = overrides.exit_script {
             self.defaults.exit_script = Some(script);
         }
+        if overrides.append_date_to_dir {
+            self.defaults.append_date_to_dir = true;
+        }
+        if overrides.no_scrollback {
+            self.defaults.no_scrollback = true;
+        }
+        if overrides.timestamp_output {
+            self.defaults.timestamp_output = true;
+        }
+        if overrides.append_file {
+            self.defaults.file_mode = FileMode::Append;
+        }
+        if let Some(scrollback) = overrides.scrollback {
+            self.defaults.scrollback = scrollback;
+        }
+        if let Some(profile) = overrides.profile {
+            if !self.profiles.contains_key(&profile) {
+                return Err(ConfigError::UnknownProfile(profile));
+            }
+            self.profile = Some(profile);
+        }
+        Ok(())
     }
 }
 
@@ -56,12 +117,25 @@ impl Config {
 /// for every field, the global `static CONFIG` will be initialized with the user's
 /// values and fill in the unspecified fields with their default values.
 ///
+/// The config file is looked for in the following order:
+/// 1. [`ConfigOverride::config_file`] (`--config`)
+/// 2. The path in the [`CONFIG_ENV_VAR`] (`SERICOM_CONFIG`) environment variable
+/// 3. `~/.config/sericom/config.toml`
+///
 /// Takes [`ConfigOverride`] to set any overriding values before initialization.
 ///
 /// Returns a [`ConfigError::AlreadyInitialized`] error if called after it has
-/// already been called ([`CONFIG`] has already been set).
-pub fn initialize_config(overrides: ConfigOverride) -> miette::Result<(), ConfigError> {
-    let mut config: Config = if let Ok(config_file) = get_config_file() {
+/// already been called ([`CONFIG`] has already been set). Returns a
+/// [`ConfigError::ConfigNotFound`] error if the config file was given explicitly
+/// (with `--config` or `SERICOM_CONFIG`) and does not exist.
+pub fn initialize_config(mut overrides: ConfigOverride) -> miette::Result<(), ConfigError> {
+    let config_file = match explicit_config_file(overrides.config_file.take()) {
+        Some(path) if path.is_file() => Ok(path),
+        Some(path) => return Err(ConfigError::ConfigNotFound(path)),
+        None => get_config_file(),
+    };
+
+    let mut config: Config = if let Ok(config_file) = config_file {
         let mut file = std::fs::File::open(config_file).expect("File should exist");
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
@@ -76,7 +150,7 @@ pub fn initialize_config(overrides: ConfigOverride) -> miette::Result<(), Config
         Config::default()
     };
 
-    config.apply_overrides(overrides);
+    config.apply_overrides(overrides)?;
 
     CONFIG
         .set(config)
@@ -89,24 +163,68 @@ pub fn initialize_config(overrides: ConfigOverride) -> miette::Result<(), Config
 ///
 /// See [`Config`].
 ///
-/// ## Panics
-/// Will panic if [`CONFIG`] as not been initialized before calling with [`initialize_config()`].
+/// Returns [`Config::default()`] if [`CONFIG`] hasn't been initialized with
+/// [`initialize_config()`] yet, i.e. when the library is used without sericom's CLI
+/// or in tests. The config can still be initialized afterwards. See [`try_get_config()`]
+/// to tell whether it has been initialized.
 pub fn get_config() -> &'static Config {
-    CONFIG.get().expect("Config not initialized")
+    static DEFAULT_CONFIG: OnceLock<Config> = OnceLock::new();
+    try_get_config().unwrap_or_else(|| DEFAULT_CONFIG.get_or_init(Config::default))
 }
 
+/// Returns a reference to the global [`CONFIG`] if it has been initialized
+/// with [`initialize_config()`], otherwise returns `None`.
+pub fn try_get_config() -> Option<&'static Config> {
+    CONFIG.get()
+}
+
+/// The environment variable that can be set to the path of a config file,
+/// to be used instead of `~/.config/sericom/config.toml`.
+pub const CONFIG_ENV_VAR: &str = "SERICOM_CONFIG";
+
 #[derive(Debug)]
 /// Available configuration options that can be overridden
 pub struct ConfigOverride {
+    /// The path to the config file to use, takes precedence over [`CONFIG_ENV_VAR`]
+    pub config_file: Option<PathBuf>,
     /// Overrides [`Appearance::fg`]
     pub color: Option<SeriColor>,
     /// Overrides [`Defaults::out_dir`]
     pub out_dir: Option<PathBuf>,
     /// Overrides [`Defaults::exit_script`]
     pub exit_script: Option<PathBuf>,
+    /// Selects a [`Profile`] from [`Config::profiles`]
+    pub profile: Option<String>,
+    /// Enables [`Defaults::append_date_to_dir`]
+    pub append_date_to_dir: bool,
+    /// Enables [`Defaults::no_scrollback`]
+    pub no_scrollback: bool,
+    /// Overrides [`Defaults::scrollback`]
+    pub scrollback: Option<usize>,
+    /// Enables [`Defaults::timestamp_output`]
+    pub timestamp_output: bool,
+    /// Sets [`Defaults::file_mode`] to [`FileMode::Append`]
+    pub append_file: bool,
 }
 
-fn get_conf_dir() -> std::path::PathBuf {
+/// Returns the config file given with `--config` (`config_file`) or [`CONFIG_ENV_VAR`].
+fn explicit_config_file(config_file: Option<PathBuf>) -> Option<PathBuf> {
+    config_file
+        .or_else(|| std::env::var_os(CONFIG_ENV_VAR).map(PathBuf::from))
+        .filter(|path| !path.as_os_str().is_empty())
+}
+
+/// Returns the path of the config file that [`initialize_config()`] reads,
+/// following the same order of precedence. The file may not exist.
+///
+/// `config_file` is the path given with `--config`, if any.
+pub fn config_file_path(config_file: Option<PathBuf>) -> PathBuf {
+    explicit_config_file(config_file).unwrap_or_else(|| conf_dir_path().join("config.toml"))
+}
+
+/// Returns the path of sericom's config directory, `~/.config/sericom`, without
+/// creating it. See [`get_conf_dir()`].
+pub fn conf_dir_path() -> std::path::PathBuf {
     let mut user_home_dir = std::env::home_dir().expect("Failed to get home directory");
 
     if cfg!(windows) {
@@ -114,8 +232,13 @@ fn get_conf_dir() -> std::path::PathBuf {
     } else {
         user_home_dir.push(".config/sericom");
     }
+    user_home_dir
+}
 
-    let user_conf_dir = user_home_dir;
+/// Returns sericom's config directory, `~/.config/sericom`, creating it if it
+/// doesn't exist.
+pub fn get_conf_dir() -> std::path::PathBuf {
+    let user_conf_dir = conf_dir_path();
     create_recursive!(user_conf_dir.as_path());
 
     user_conf_dir
@@ -153,19 +276,373 @@ fn parse_test_config() -> miette::Result<()> {
         appearance: Appearance {
             fg: SeriColor::DarkGrey,
             bg: SeriColor::Red,
+            hl_fg: SeriColor::Black,
+            hl_bg: SeriColor::White,
+            wrap: true,
+            wrap_marker: None,
+            timestamps: false,
+            throughput: false,
+            status_line: false,
+            display_filter: LineFilter::default(),
+            ruler_column: None,
+            max_fps: Appearance::default().max_fps,
         },
         defaults: Defaults {
             out_dir: PathBuf::from("/home/thomas/.config"),
             exit_script: Some(PathBuf::from("/home/thomas/.local/bin/format-cisco")),
             debug_dir: PathBuf::from("/home/thomas/Code/Work/sericom/sericom-core"),
             // file_exit_script: None,
+            ..Defaults::default()
         },
+        keybindings: Keybindings::default(),
+        macros: HashMap::new(),
+        profiles: HashMap::new(),
+        captures: Vec::new(),
+        profile: None,
     };
 
     assert_eq!(file, parsed_conf);
     Ok(())
 }
 
+#[test]
+fn parse_test_keybindings() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config =
+        toml::from_str("[keybindings]\nctrl_l = \"clear_buffer\"").into_diagnostic()?;
+    assert_eq!(file.keybindings.ctrl_l, ClearAction::ClearBuffer);
+
+    let file: Config =
+        toml::from_str("[keybindings]\nctrl-l = \"clear-screen\"").into_diagnostic()?;
+    assert_eq!(file.keybindings.ctrl_l, ClearAction::ClearScreen);
+    assert_eq!(
+        Config::default().keybindings.ctrl_l,
+        ClearAction::ClearScreen
+    );
+    assert!(toml::from_str::<Config>("[keybindings]\nctrl-l = \"nothing\"").is_err());
+    assert_eq!(Config::default().keybindings.home, "\u{1b}[H");
+
+    let file: Config = toml::from_str(
+        r#"
+            [keybindings]
+            home = "\u001BOH"
+            end = "\u001B[4~"
+            "#,
+    )
+    .into_diagnostic()?;
+    assert_eq!(file.keybindings.home, "\u{1b}OH");
+    assert_eq!(file.keybindings.end, "\u{1b}[4~");
+    assert_eq!(file.keybindings.page_keys, PageKeys::Scroll);
+
+    let file: Config = toml::from_str("[keybindings]\npage-keys = \"device\"").into_diagnostic()?;
+    assert_eq!(file.keybindings.page_keys, PageKeys::Device);
+    Ok(())
+}
+
+#[test]
+fn parse_test_key_actions() -> miette::Result<()> {
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use miette::IntoDiagnostic;
+
+    let file: Config = toml::from_str(
+        r#"
+            [keybindings]
+            quit = "ctrl+x"
+            break = "Alt-Shift-B"
+            scroll-top = "f11"
+            copy = "alt+y"
+        "#,
+    )
+    .into_diagnostic()?;
+    let actions = file.keybindings.actions();
+    let ctrl_x = KeySpec::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
+    assert_eq!(actions.get(&ctrl_x), Some(&KeyAction::Quit));
+    let alt_shift_b = KeySpec::new(KeyCode::Char('B'), KeyModifiers::ALT | KeyModifiers::SHIFT);
+    assert_eq!(actions.get(&alt_shift_b), Some(&KeyAction::Break));
+    assert_eq!(
+        actions.get(&KeySpec::new(KeyCode::F(11), KeyModifiers::NONE)),
+        Some(&KeyAction::ScrollTop)
+    );
+    assert_eq!(file.keybindings.copy, KeySpec::parse("alt+y"));
+    // No longer bound once quit is moved
+    let ctrl_q = KeySpec::new(KeyCode::Char('q'), KeyModifiers::CONTROL);
+    assert!(!actions.contains_key(&ctrl_q));
+    assert_eq!(
+        Config::default().keybindings.actions().get(&ctrl_q),
+        Some(&KeyAction::Quit)
+    );
+
+    assert_eq!(
+        KeySpec::parse("alt+-"),
+        Some(KeySpec::new(KeyCode::Char('-'), KeyModifiers::ALT))
+    );
+    assert!(KeySpec::parse("hyper+q").is_none());
+    assert!(KeySpec::parse("f13").is_none());
+    assert!(toml::from_str::<Config>("[keybindings]\nquit = \"ctrl+\"").is_err());
+    // crossterm reports letters without Shift in lowercase
+    assert_eq!(KeySpec::parse("ctrl+Q"), Some(ctrl_q));
+    assert_eq!(
+        KeySpec::parse("Alt+B"),
+        Some(KeySpec::new(KeyCode::Char('b'), KeyModifiers::ALT))
+    );
+
+    let dup = toml::from_str::<Config>("[keybindings]\nhelp = \"ctrl+q\"");
+    assert!(dup.is_err_and(|e| e.message().contains("'quit' and 'help'")));
+    let moved = toml::from_str::<Config>("[keybindings]\nquit = \"f1\"\nscroll-top = \"f3\"");
+    assert!(moved.is_ok());
+
+    assert_eq!(alt_shift_b.to_string(), "Alt+Shift+b");
+    assert_eq!(KeySpec::parse("alt+up").unwrap().to_string(), "Alt+Up");
+    let help = Config::default().keybindings.help_entries();
+    assert_eq!(help.first().map(String::as_str), Some("Ctrl+q quit"));
+    assert_eq!(help.last().map(String::as_str), Some("F12 close help"));
+
+    let file: Config =
+        toml::from_str("[keybindings]\ntoggle-hex-view = \"f5\"").into_diagnostic()?;
+    let actions = file.keybindings.actions();
+    assert_eq!(
+        actions.get(&KeySpec::new(KeyCode::F(5), KeyModifiers::NONE)),
+        Some(&KeyAction::ToggleHexView)
+    );
+    assert!(!actions.contains_key(&KeySpec::new(KeyCode::Char('x'), KeyModifiers::ALT)));
+    assert!(
+        file.keybindings
+            .help_entries()
+            .contains(&"F5 hex".to_string())
+    );
+    // Alt+s saves the history unless it is bound to something else
+    let dup = toml::from_str::<Config>("[keybindings]\ntoggle-rts = \"alt+s\"");
+    assert!(dup.is_err_and(|e| e.message().contains("'toggle-rts' and 'dump-scrollback'")));
+    let moved = toml::from_str::<Config>(
+        "[keybindings]\ntoggle-rts = \"alt+s\"\ndump-scrollback = \"alt+w\"",
+    );
+    assert!(moved.is_ok());
+    let search_prev = KeySpec::parse("alt+shift+n").unwrap();
+    assert_eq!(
+        Config::default().keybindings.actions().get(&search_prev),
+        Some(&KeyAction::SearchPrev)
+    );
+    Ok(())
+}
+
+#[test]
+fn parse_test_macros() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config = toml::from_str(
+        r#"
+            [macros.audit]
+            key = "F3"
+            commands = ["show version", "show inventory"]
+
+            [macros.unbound]
+            delay-ms = 250
+            commands = ["terminal length 0"]
+            "#,
+    )
+    .into_diagnostic()?;
+    assert_eq!(file.macros["audit"].delay(), std::time::Duration::ZERO);
+    assert_eq!(file.macros["unbound"].delay_ms, 250);
+
+    let fkeys = function_key_macros(&file.macros);
+    assert_eq!(fkeys.len(), 1);
+    assert_eq!(
+        fkeys[&3].lines().collect::<Vec<_>>(),
+        vec!["show version\r", "show inventory\r"]
+    );
+
+    let dup = toml::from_str::<Config>(
+        r#"
+            [macros.one]
+            key = "f5"
+            commands = []
+            [macros.two]
+            key = "F5"
+            commands = []
+            "#,
+    );
+    assert!(dup.is_err());
+    assert!(toml::from_str::<Config>("[macros.bad]\nkey = \"F13\"\ncommands = []").is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_test_profiles() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let mut file: Config = toml::from_str(
+        r#"
+            [profiles.lab.login]
+            enabled = true
+            username = "admin"
+            password = "hunter2"
+            "#,
+    )
+    .into_diagnostic()?;
+
+    assert!(file.active_profile().is_none());
+    file.profile = Some("lab".to_string());
+    let login = file.active_profile().unwrap().login.as_ref().unwrap();
+    assert_eq!(login.username_prompt, "Username:");
+    assert_eq!(login.password_prompt, "Password:");
+    assert!(!format!("{login:?}").contains("hunter2"));
+    assert!(!file.always_capture());
+    Ok(())
+}
+
+#[test]
+fn profile_always_capture() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let mut file: Config = toml::from_str(
+        r#"
+            [defaults]
+            always-capture = true
+
+            [profiles.lab]
+            always-capture = false
+            "#,
+    )
+    .into_diagnostic()?;
+
+    assert!(file.always_capture());
+    file.profile = Some("lab".to_string());
+    assert!(!file.always_capture());
+    Ok(())
+}
+
+#[test]
+fn parse_test_captures() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config = toml::from_str(
+        r#"
+            [[capture]]
+            path = "full.txt"
+
+            [[capture]]
+            path = "{port}-errors.txt"
+            include = "(?i)error"
+            exclude = "debug"
+            "#,
+    )
+    .into_diagnostic()?;
+
+    assert_eq!(file.captures.len(), 2);
+    assert!(file.captures[0].filter.is_empty());
+    let filter = &file.captures[1].filter;
+    assert!(filter.matches("%LINK-3-ERROR: Gi0/1"));
+    assert!(!filter.matches("debug: error"));
+    assert!(!filter.matches("Gi0/1 up"));
+
+    let invalid = toml::from_str::<Config>("[[capture]]\npath = \"a.txt\"\ninclude = \"(\"");
+    assert!(invalid.is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_test_channel_capacities() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config = toml::from_str(
+        r#"
+            [defaults]
+            event-channel-capacity = 4096
+            ui_channel_capacity = 16
+            "#,
+    )
+    .into_diagnostic()?;
+
+    assert_eq!(file.defaults.event_channel_capacity, 4096);
+    assert_eq!(file.defaults.command_channel_capacity, 100);
+    assert_eq!(file.defaults.ui_channel_capacity, 16);
+
+    let too_small = toml::from_str::<Config>("[defaults]\ncommand-channel-capacity = 1");
+    assert!(too_small.is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_test_max_files() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config =
+        toml::from_str("[defaults]\nmax-file-bytes = 1024\nmax_files = 5").into_diagnostic()?;
+    assert_eq!(file.defaults.max_file_bytes, Some(1024));
+    assert_eq!(file.defaults.max_files, Some(5));
+    assert_eq!(Config::default().defaults.max_files, None);
+    assert!(toml::from_str::<Config>("[defaults]\nmax-files = 1").is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_test_on_reconnect() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config =
+        toml::from_str("[defaults]\non_reconnect = [\"terminal length 0\", \"enable\"]")
+            .into_diagnostic()?;
+    assert_eq!(
+        file.defaults.on_reconnect_lines().collect::<Vec<_>>(),
+        vec!["terminal length 0\r", "enable\r"]
+    );
+    assert!(Config::default().defaults.on_reconnect.is_empty());
+    Ok(())
+}
+
+#[test]
+fn parse_test_mouse() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config = toml::from_str("[defaults]\nmouse = \"scroll-only\"").into_diagnostic()?;
+    assert_eq!(file.defaults.mouse, MouseMode::ScrollOnly);
+    let file: Config = toml::from_str("[defaults]\nmouse = \"off\"").into_diagnostic()?;
+    assert_eq!(file.defaults.mouse, MouseMode::Off);
+    assert_eq!(Config::default().defaults.mouse, MouseMode::Full);
+    assert!(toml::from_str::<Config>("[defaults]\nmouse = \"some\"").is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_test_ruler_column() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config = toml::from_str("[appearance]\nruler-column = 80").into_diagnostic()?;
+    assert_eq!(file.appearance.ruler_column.map(|c| c.get()), Some(80));
+    assert!(toml::from_str::<Config>("[appearance]\nruler-column = 0").is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_test_highlight_colors() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config = toml::from_str("[appearance]\nfg = \"cyan\"").into_diagnostic()?;
+    assert_eq!(file.appearance.hl_fg, SeriColor::Black);
+    assert_eq!(file.appearance.hl_bg, SeriColor::White);
+    let file: Config = toml::from_str("[appearance]\nhl-fg = \"yellow\"\nhl_bg = \"dark-blue\"")
+        .into_diagnostic()?;
+    assert_eq!(file.appearance.hl_fg, SeriColor::Yellow);
+    assert_eq!(file.appearance.hl_bg, SeriColor::DarkBlue);
+    Ok(())
+}
+
+#[test]
+fn parse_test_paste_chunks() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config =
+        toml::from_str("[defaults]\npaste-chunk-bytes = 256\npaste_confirm_bytes = 4096")
+            .into_diagnostic()?;
+    assert_eq!(file.defaults.paste_chunk_bytes, Some(256));
+    assert_eq!(file.defaults.paste_chunk_delay_ms, 50);
+    assert_eq!(file.defaults.paste_confirm_bytes, Some(4096));
+    assert!(toml::from_str::<Config>("[defaults]\npaste-chunk-bytes = 0").is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_test_scrollback() -> miette::Result<()> {
+    use miette::IntoDiagnostic;
+    let file: Config = toml::from_str("[defaults]\nscrollback = 0").into_diagnostic()?;
+    assert_eq!(file.defaults.scrollback, 0);
+    let file: Config = toml::from_str("[defaults]").into_diagnostic()?;
+    assert_eq!(
+        file.defaults.scrollback,
+        crate::screen_buffer::MAX_SCROLLBACK
+    );
+    Ok(())
+}
+
 #[test]
 fn check_conf_dir_is_dir() {
     let dir = get_conf_dir();
@@ -212,3 +689,23 @@ fn get_expanded_path() {
 //     // assert_eq!(config, &Config::default())
 //     Ok(())
 // }
+
+#[test]
+fn shipped_defaults_parse() {
+    // The shipped file documents the values used when a key isn't set
+    let shipped: Config =
+        toml::from_str(include_str!("../../../configuration/defaults.toml")).unwrap();
+    let default: Config = toml::from_str("").unwrap();
+    assert_eq!(shipped.appearance, default.appearance);
+    assert_eq!(shipped.keybindings, default.keybindings);
+    assert_eq!(
+        shipped.defaults.entry_script_timeout_ms,
+        default.defaults.entry_script_timeout_ms
+    );
+    assert_eq!(shipped.defaults.file_mode, default.defaults.file_mode);
+
+    let snake_case: Config =
+        toml::from_str("[defaults]\nidle_gap_ms = 5000\nreset_sequence = \"\\u0003\"").unwrap();
+    assert_eq!(snake_case.defaults.idle_gap_ms, Some(5000));
+    assert_eq!(snake_case.defaults.reset_sequence, "\u{3}");
+}
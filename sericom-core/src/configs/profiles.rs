@@ -0,0 +1,70 @@
+use serde::Deserialize;
+
+/// Represents a single entry within the `[profiles]` table of the `config.toml` file.
+///
+/// A profile groups settings that are specific to a device (or a set of devices)
+/// and is selected at runtime with `sericom --profile <NAME> [PORT]`.
+///
+/// ```toml
+/// [profiles.lab-switch]
+/// always-capture = true
+///
+/// [profiles.lab-switch.login]
+/// enabled = true
+/// username = "admin"
+/// password = "hunter2"
+/// ```
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Profile {
+    #[serde(default)]
+    pub login: Option<Login>,
+    /// Overrides [`Defaults::always_capture`][crate::configs::Defaults::always_capture]
+    /// for sessions using this profile.
+    #[serde(rename = "always-capture", alias = "always_capture")]
+    #[serde(default)]
+    pub always_capture: Option<bool>,
+}
+
+/// Represents the `[profiles.<name>.login]` table of the `config.toml` file.
+///
+/// When enabled, sericom watches the data received from the device for the
+/// `username_prompt` and `password_prompt` and responds with the `username`
+/// and `password` respectively.
+///
+/// Because the credentials are stored in plain text, logging in automatically
+/// must be explicitly opted into with `enabled = true`. The credentials are only
+/// written to the serial connection; they are never written to the output file
+/// or to the debug/tracing output.
+#[derive(Deserialize, PartialEq)]
+pub struct Login {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub username: Option<String>,
+    pub password: String,
+    #[serde(rename = "username-prompt")]
+    #[serde(default = "default_username_prompt")]
+    pub username_prompt: String,
+    #[serde(rename = "password-prompt")]
+    #[serde(default = "default_password_prompt")]
+    pub password_prompt: String,
+}
+
+fn default_username_prompt() -> String {
+    "Username:".to_string()
+}
+fn default_password_prompt() -> String {
+    "Password:".to_string()
+}
+
+impl std::fmt::Debug for Login {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Login")
+            .field("enabled", &self.enabled)
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("username_prompt", &self.username_prompt)
+            .field("password_prompt", &self.password_prompt)
+            .finish()
+    }
+}
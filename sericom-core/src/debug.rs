@@ -1,9 +1,57 @@
-//! As of now, there is only one function, [`run_debug_output`], which is meant
-//! to debug the data being received over the serial connection. In future
+//! As of now, there are only two functions, [`run_debug_output`] and [`run_trace_stderr`],
+//! which are meant to debug the data being received over the serial connection. In future
 //! updates, this module is intended to be used for running tracing events with
 //! the [`tracing`](https://docs.rs/tracing/latest/tracing/) crate.
 
-use crate::serial_actor::SerialEvent;
+use crate::serial_actor::{OutputSink, SerialEvent, run_output_sink};
+use std::io::Write;
+
+/// The number of bytes printed for each batch of data received.
+const PREVIEW_BYTES: usize = 20;
+
+/// Formats the time, the number of bytes and the first [`PREVIEW_BYTES`] of `data` i.e.
+/// "\[04:41:27.550\] RX 9 bytes: \[0D, 0A, 53, 77, 69, 74, 63, 68, 23\]".
+fn summarize_rx(data: &[u8]) -> String {
+    format!(
+        "[{}] RX {} bytes: {:02X?}{}",
+        chrono::Utc::now().format("%H:%M:%S%.3f"),
+        data.len(),
+        &data[..std::cmp::min(PREVIEW_BYTES, data.len())],
+        if data.len() > PREVIEW_BYTES {
+            "..."
+        } else {
+            ""
+        },
+    )
+}
+
+/// Prints a summary of each batch of data received from the device to stderr,
+/// for `--trace-stderr`. Unlike [`run_debug_output`], the data is not batched and
+/// only the first bytes are printed, i.e.
+///
+/// "\[04:41:27.550\] RX 9 bytes: \[0D, 0A, 53, 77, 69, 74, 63, 68, 23\]"
+///
+/// Meant to be used with stderr redirected, i.e. `2> trace.txt` or `2>&1 | ...`
+/// from a script, as printing to the terminal would draw over the session.
+pub async fn run_trace_stderr(mut rx: tokio::sync::broadcast::Receiver<SerialEvent>) {
+    loop {
+        match rx.recv().await {
+            Ok(SerialEvent::Data(data)) => eprintln!("{}", summarize_rx(&data)),
+            Ok(SerialEvent::Error(e)) => eprintln!("[ERROR] {e}"),
+            Ok(SerialEvent::ConnectionClosed) => {
+                eprintln!("[CLOSED] Connection closed.");
+                break;
+            }
+            Ok(SerialEvent::Reconnecting) => eprintln!("[RECONNECTING] Connection lost."),
+            Ok(SerialEvent::Reconnected) => eprintln!("[RECONNECTED] Connection reopened."),
+            Ok(SerialEvent::Status(status)) => eprintln!("[STATUS] {status}"),
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                eprintln!("[LAGGED] Skipped {skipped} messages");
+            }
+            Err(_) => break,
+        }
+    }
+}
 
 /// This function is used for debugging the data that is sent from a device.
 /// It will create a file "debug.txt" and print the data received from the device
@@ -15,97 +63,55 @@ use crate::serial_actor::SerialEvent;
 /// "\[04:41:27.550\] RX 9 bytes: \[0D, 0A, 53, 77, 69, 74, 63, 68\]... UTF8: ^M Switch#"
 ///
 /// Each line will only print a maximum of 8 bytes, after 8 it will simply write "...".
-pub async fn run_debug_output(mut rx: tokio::sync::broadcast::Receiver<SerialEvent>) {
-    use std::io::{BufWriter, Write};
-    use std::path::Path;
+pub async fn run_debug_output(rx: tokio::sync::broadcast::Receiver<SerialEvent>) {
+    run_output_sink(rx, DebugSink { writer: None }).await;
+}
 
-    let (write_tx, write_rx) = std::sync::mpsc::channel::<Vec<u8>>();
-    let write_handle = tokio::task::spawn_blocking(move || {
-        let path = Path::new("./debug.txt");
-        let file = match std::fs::File::create(path) {
-            Ok(f) => f,
-            Err(e) => {
-                eprintln!("Failed to create file: {e}");
-                return;
-            }
-        };
-        let mut writer = BufWriter::with_capacity(48 * 1024, file);
-        let mut last_flush = std::time::Instant::now();
+/// The [`OutputSink`] of [`run_debug_output`], writing to "debug.txt".
+struct DebugSink {
+    writer: Option<std::io::BufWriter<std::fs::File>>,
+}
 
-        writeln!(writer, "Session started at: {}", chrono::Utc::now()).ok();
-        while let Ok(data) = write_rx.recv() {
-            // let control_bytes_for_hex: Vec<u8> = data[..std::cmp::min(20, data.len())]
-            //     .iter()
-            //     .filter(|b| b.is_ascii_control())
-            //     .cloned()
-            //     .collect();
-            // Only prints the bytes of ASCII escape characters
-            // writeln!(
-            //     writer,
-            //     "RX {} bytes: {:02X?}{} UTF8: {}",
-            //     data.len(),
-            //     control_bytes_for_hex,
-            //     if data.len() > 20 { "..." } else { "" },
-            //     String::from_utf8_lossy(&data)
-            // )
-            // .ok();
-            // Prints bytes of all characters
-            writeln!(
-                writer,
-                "[{}] RX {} bytes: {:02X?}{} UTF8: {}",
-                chrono::Utc::now().format("%H:%M:%S%.3f"),
-                data.len(),
-                &data[..std::cmp::min(20, data.len())],
-                if data.len() > 10 { "..." } else { "" },
-                String::from_utf8_lossy(&data)
-            )
-            .ok();
+impl DebugSink {
+    fn writer(&mut self) -> &mut std::io::BufWriter<std::fs::File> {
+        self.writer.as_mut().expect("Created in on_open")
+    }
+}
 
-            let now = std::time::Instant::now();
-            if now.duration_since(last_flush) > std::time::Duration::from_millis(100)
-                || writer.buffer().len() > 32 * 1024
-            {
-                let _ = writer.flush();
-                last_flush = now;
-            }
-        }
-        let _ = writer.flush();
-    });
+impl OutputSink for DebugSink {
+    fn on_open(&mut self) -> std::io::Result<()> {
+        let file = std::fs::File::create("./debug.txt")
+            .map_err(|e| std::io::Error::new(e.kind(), format!("Failed to create file: {e}")))?;
+        let mut writer = std::io::BufWriter::with_capacity(48 * 1024, file);
+        writeln!(writer, "Session started at: {}", chrono::Utc::now())?;
+        self.writer = Some(writer);
+        Ok(())
+    }
 
-    let data_streamer = tokio::spawn(async move {
-        let mut write_buf = Vec::with_capacity(4096);
-        let mut batch_timer = tokio::time::interval(tokio::time::Duration::from_millis(200));
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        // Prints bytes of all characters
+        writeln!(
+            self.writer(),
+            "{} UTF8: {}",
+            summarize_rx(data),
+            String::from_utf8_lossy(data)
+        )
+    }
 
-        loop {
-            tokio::select! {
-                event = rx.recv() => {
-                    match event {
-                        Ok(SerialEvent::Data(data)) => {
-                            write_buf.extend_from_slice(&data);
-                            if write_buf.len() >= 4096 && write_tx.send(std::mem::take(&mut write_buf)).is_err() {
-                                    break;
-                            }
-                        }
-                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
-                            eprintln!("File writer lagged, skipped {skipped} messages");
-                            continue; // Don't break on lag
-                        }
-                        _ => break,
-                    }
-                }
-                _ = batch_timer.tick() => {
-                    if !write_buf.is_empty() && write_tx.send(std::mem::take(&mut write_buf)).is_err() {
-                            break;
-                    }
-                }
-            }
-        }
-        if !write_buf.is_empty() {
-            let _ = write_tx.send(std::mem::take(&mut write_buf));
-        }
-        drop(write_tx);
-    });
+    fn on_error(&mut self, error: &str) -> std::io::Result<()> {
+        writeln!(self.writer(), "[ERROR] {error}")
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer().flush()
+    }
+}
 
-    let _ = data_streamer.await;
-    let _ = write_handle.await;
+#[test]
+fn summarize_received_data() {
+    let summary = summarize_rx(b"\r\nSwitch#");
+    assert!(summary.ends_with("] RX 9 bytes: [0D, 0A, 53, 77, 69, 74, 63, 68, 23]"));
+    let summary = summarize_rx(&[b'a'; 21]);
+    assert!(summary.starts_with('[') && summary.ends_with(", 61]..."));
+    assert!(summary.contains("RX 21 bytes"));
 }
@@ -8,6 +8,9 @@
 //! create an [issue](https://github.com/tkatter/sericom) so I can become aware and work
 //! towards making `sericom-core` a generalized/compatible library that is better suited
 //! for use among other crates.
+//!
+//! The [`session`] module is the starting point for running a session from other
+//! Rust programs, without `sericom`'s terminal UI.
 
 pub mod cli;
 pub mod configs;
@@ -15,3 +18,4 @@ pub mod debug;
 pub mod path_utils;
 pub mod screen_buffer;
 pub mod serial_actor;
+pub mod session;
@@ -1,4 +1,6 @@
 mod macros;
+mod template;
 mod validators;
 pub use macros::*;
+pub use template::*;
 pub use validators::*;
@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+
+/// The default template used for naming output files when no file name is given.
+///
+/// Produces file names like `ttyUSB0-09251554.txt`.
+pub const DEFAULT_FILE_TEMPLATE: &str = "{port}-{timestamp}.txt";
+
+/// The placeholders that can be used within a file template.
+pub static FILE_TEMPLATE_PLACEHOLDERS: &[&str] =
+    &["{port}", "{profile}", "{timestamp}", "{date}", "{time}"];
+
+/// Resolves a file `template` into a file name.
+///
+/// The following placeholders are replaced:
+/// - `{port}`: The name of the port, i.e. `ttyUSB0` or `COM4`
+/// - `{profile}`: The name of the active profile, or nothing if there isn't one
+/// - `{timestamp}`: The current time formatted as `MMDDHHMM`
+/// - `{date}`: The current date formatted as `YYYY-MM-DD`
+/// - `{time}`: The current time formatted as `HHMMSS`
+///
+/// Placeholders that resolve to nothing leave behind any `-` or `_` separators
+/// at the start or end of the file's stem, these are trimmed, i.e.
+/// `{port}_{profile}.log` becomes `ttyUSB0.log` without a profile.
+///
+/// # Errors
+/// Errors if the port's name can't be determined or if the resolved file name is empty.
+///
+/// # Example
+/// ```
+/// use sericom_core::path_utils::resolve_file_template;
+/// use std::path::PathBuf;
+///
+/// fn get_fname() -> miette::Result<()> {
+///     let fname = resolve_file_template("{port}_{profile}.log", "COM4", Some("lab"))?;
+///     assert_eq!(fname, PathBuf::from("COM4_lab.log"));
+///     Ok(())
+/// }
+/// get_fname().unwrap();
+/// ```
+pub fn resolve_file_template<S>(
+    template: &str,
+    port: S,
+    profile: Option<&str>,
+) -> miette::Result<PathBuf>
+where
+    S: Into<PathBuf>,
+{
+    let port = super::get_compat_port_path(port)?;
+    let now = chrono::Utc::now();
+    let resolved = template
+        .replace("{port}", &port.display().to_string())
+        .replace("{profile}", profile.unwrap_or_default())
+        .replace("{timestamp}", &now.format("%m%d%H%M").to_string())
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H%M%S").to_string());
+
+    let (stem, ext) = match resolved.rsplit_once('.') {
+        Some((stem, ext)) => (stem, Some(ext)),
+        None => (resolved.as_str(), None),
+    };
+    let stem = stem.trim().trim_matches(['-', '_']);
+    if stem.is_empty() {
+        return Err(miette::miette!(
+            help = format!(
+                "Make sure the `file-template` includes one of: {}",
+                FILE_TEMPLATE_PLACEHOLDERS.join(", ")
+            ),
+            "The file template '{template}' resolved to an empty file name"
+        ));
+    }
+
+    Ok(match ext {
+        Some(ext) => PathBuf::from(format!("{stem}.{ext}")),
+        None => PathBuf::from(stem),
+    })
+}
+
+/// Validates a file template
+///
+/// Checks that the template is not empty and doesn't contain any path separators.
+///
+/// # Errors
+/// Errors if `template` is blank or contains a '/' or '\\'.
+pub fn validate_file_template(template: &str) -> Result<String, String> {
+    if template.trim().is_empty() {
+        return Err("The file template must not be empty".to_string());
+    }
+    if template.contains(['/', '\\']) {
+        return Err(format!(
+            "Invalid file template '{template}'\nThe template is for the file's name and can't contain path separators"
+        ));
+    }
+    Ok(template.to_string())
+}
+
+#[cfg(unix)]
+#[test]
+fn resolve_templates() -> miette::Result<()> {
+    let fname = resolve_file_template(DEFAULT_FILE_TEMPLATE, "/dev/ttyUSB0", None)?;
+    assert_eq!(
+        fname,
+        PathBuf::from(format!(
+            "ttyUSB0-{}.txt",
+            chrono::Utc::now().format("%m%d%H%M")
+        ))
+    );
+
+    let fname = resolve_file_template("{port}_{profile}.log", "/dev/ttyUSB0", None)?;
+    assert_eq!(fname, PathBuf::from("ttyUSB0.log"));
+
+    let fname = resolve_file_template("{unknown}-{port}", "/dev/ttyUSB0", None)?;
+    assert_eq!(fname, PathBuf::from("{unknown}-ttyUSB0"));
+
+    assert!(resolve_file_template("{profile}.log", "/dev/ttyUSB0", None).is_err());
+    assert!(validate_file_template("logs/{port}.txt").is_err());
+    Ok(())
+}
@@ -55,7 +55,7 @@ pub fn is_script(input: &str) -> Result<Option<PathBuf>, String> {
 
     if !is_executable(&p) {
         return Err(format!(
-            "Invalid file type '{}'\nMake sure the file is executable",
+            "Invalid file type '{}'\n{EXECUTABLE_HINT}",
             p.display()
         ));
     }
@@ -63,6 +63,34 @@ pub fn is_script(input: &str) -> Result<Option<PathBuf>, String> {
     Ok(Some(p))
 }
 
+/// Validates a [`chrono` format string](https://docs.rs/chrono/latest/chrono/format/strftime/index.html)
+///
+/// Used in `sericom`s `clap` cli interface to validate user input and
+/// propogate errors before running the program.
+///
+/// # Errors
+/// Errors if the format string is empty or contains an invalid specifier.
+pub fn validate_date_format(input: &str) -> Result<String, String> {
+    use chrono::format::{Item, StrftimeItems};
+
+    if input.trim().is_empty() {
+        return Err("The date format must not be empty".to_string());
+    }
+    if StrftimeItems::new(input).any(|item| matches!(item, Item::Error)) {
+        return Err(format!(
+            "Invalid date format '{input}'\nSee https://docs.rs/chrono/latest/chrono/format/strftime/index.html"
+        ));
+    }
+    Ok(input.to_string())
+}
+
+/// Explains what [`is_executable`] requires of a script, for the errors about it.
+pub(crate) const EXECUTABLE_HINT: &str = if cfg!(windows) {
+    "Make sure the file is a .exe, .bat, .cmd, .com or .ps1 file"
+} else {
+    "Make sure the file is executable"
+};
+
 pub(crate) fn is_executable(path: &std::path::Path) -> bool {
     #[cfg(unix)]
     {
@@ -77,14 +105,34 @@ pub(crate) fn is_executable(path: &std::path::Path) -> bool {
 
     #[cfg(windows)]
     {
-        match path.extension() {
-            Some(ext) => matches!(
-                ext.to_ascii_lowercase()
-                    .to_str()
-                    .expect("Converted to ascii"),
-                "exe" | "bat" | "cmd" | "com" | "ps1"
-            ),
-            None => false,
-        }
+        has_script_extension(path)
+    }
+}
+
+/// Whether `path` ends in one of the extensions that Windows can run, ignoring
+/// case. A missing or non-UTF-8 extension is never one of them.
+#[cfg(any(windows, test))]
+fn has_script_extension(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ["exe", "bat", "cmd", "com", "ps1"]
+                .iter()
+                .any(|script_ext| ext.eq_ignore_ascii_case(script_ext))
+        })
+}
+
+#[test]
+fn windows_script_extensions() {
+    use std::path::Path;
+    assert!(has_script_extension(Path::new(r"C:\scripts\format.PS1")));
+    assert!(has_script_extension(Path::new("format.bat")));
+    assert!(!has_script_extension(Path::new("format")));
+    assert!(!has_script_extension(Path::new("format.sh")));
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let non_utf8 = std::ffi::OsStr::from_bytes(b"format.\xff");
+        assert!(!has_script_extension(Path::new(non_utf8)));
     }
 }
@@ -1,6 +1,4 @@
-use crossterm::style::Color;
-
-use crate::configs::get_config;
+use crossterm::style::{Attributes, Color};
 
 /// `Cell` represents a cell within the terminal's window/frame.
 ///
@@ -9,21 +7,36 @@ use crate::configs::get_config;
 #[derive(Clone, Debug)]
 pub struct Cell {
     pub(super) character: char,
-    pub(super) fg_color: Color,
-    pub(super) bg_color: Color,
+    /// The cell's foreground color, `None` uses the session's foreground color.
+    pub(super) fg_color: Option<Color>,
+    /// The cell's background color, `None` uses the session's background color.
+    pub(super) bg_color: Option<Color>,
     pub(super) is_selected: bool,
+    pub(super) attrs: Attributes,
 }
 
 impl Default for Cell {
-    /// The default for [`Cell`] is the fg color from [`Appearance.fg`][`crate::configs::Appearance`],
-    /// the bg color from [`Appearance.bg`][`crate::configs::Appearance`], `' '` for the character, and is not selected.
+    /// The default for [`Cell`] uses the session's fg and bg colors (initially
+    /// [`Appearance.fg`][`crate::configs::Appearance`] and [`Appearance.bg`][`crate::configs::Appearance`]),
+    /// `' '` for the character, no attributes and is not selected.
     fn default() -> Self {
-        let config = get_config();
         Self {
             character: ' ',
-            fg_color: Color::from(&config.appearance.fg),
-            bg_color: Color::from(&config.appearance.bg),
+            fg_color: None,
+            bg_color: None,
             is_selected: false,
+            attrs: Attributes::none(),
         }
     }
 }
+
+impl Cell {
+    /// Returns whether the cell is a space without attributes or colors,
+    /// the same as a [`Cell::default()`] that hasn't been written to.
+    pub(super) fn is_blank(&self) -> bool {
+        self.character == ' '
+            && self.attrs.is_empty()
+            && self.fg_color.is_none()
+            && self.bg_color.is_none()
+    }
+}
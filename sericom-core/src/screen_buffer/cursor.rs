@@ -3,7 +3,7 @@ use std::fmt::Display;
 use super::{Line, ScreenBuffer};
 
 /// Represent's the cursor's position within the [`ScreenBuffer`].
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Position {
     /// The column within [`ScreenBuffer`]'s scrollback buffer.
     /// This translates to the [`Cell`][`super::Cell`] within a line (`Vec`).
@@ -56,12 +56,14 @@ pub trait Cursor {
     fn move_cursor_down(&mut self, lines: u16);
     fn move_cursor_right(&mut self, cells: u16);
     fn set_cursor_col(&mut self, col: u16);
+    fn set_cursor_row(&mut self, row: u16);
 }
 
 impl Cursor for ScreenBuffer {
-    /// Sets the cursor position.
+    /// Sets the cursor position. The column is clamped to the last column.
     fn set_cursor_pos<P: Into<Position>>(&mut self, position: P) {
         self.cursor_pos = position.into();
+        self.cursor_pos.x = self.cursor_pos.x.min(self.width.saturating_sub(1));
     }
 
     /// Moves the cursor left by `cells`.
@@ -82,13 +84,49 @@ impl Cursor for ScreenBuffer {
         }
     }
 
-    /// Moves the cursor right by `cells`.
+    /// Moves the cursor right by `cells`, stopping at the last column.
     fn move_cursor_right(&mut self, cells: u16) {
-        self.cursor_pos.x = self.cursor_pos.x.saturating_add(cells);
+        self.set_cursor_col(self.cursor_pos.x.saturating_add(cells));
     }
 
-    /// Sets the column of the cursor
+    /// Sets the column of the cursor, clamped to the last column.
     fn set_cursor_col(&mut self, col: u16) {
-        self.cursor_pos.x = col;
+        self.cursor_pos.x = col.min(self.width.saturating_sub(1));
+    }
+
+    /// Sets the row of the cursor within the screen, where `0` is the top
+    /// of the screen. Clamped to the last row of the screen.
+    fn set_cursor_row(&mut self, row: u16) {
+        let top = self.lines.len().saturating_sub(self.height as usize);
+        let row = row.min(self.height.saturating_sub(1));
+        self.cursor_pos.y = top + row as usize;
+        while self.cursor_pos.y >= self.lines.len() {
+            self.lines.push_back(Line::new(self.width as usize));
+        }
+    }
+}
+
+impl ScreenBuffer {
+    /// Saves the cursor's position (`ESC[s`), see [`ScreenBuffer::restore_cursor()`].
+    pub(super) fn save_cursor(&mut self) {
+        self.saved_cursor = Some(self.cursor_pos);
+    }
+
+    /// Moves the cursor back to the position saved with [`ScreenBuffer::save_cursor()`]
+    /// (`ESC[u`), or to the top left of the screen when none was saved.
+    pub(super) fn restore_cursor(&mut self) {
+        let top = self.lines.len().saturating_sub(self.height as usize);
+        self.set_cursor_pos(self.saved_cursor.unwrap_or(Position::from((0, top))));
+        // The lines may have been cleared since
+        while self.cursor_pos.y >= self.lines.len() {
+            self.lines.push_back(Line::new(self.width as usize));
+        }
+    }
+
+    /// Moves the saved cursor along with its line after the oldest line was dropped.
+    pub(super) fn shift_saved_cursor(&mut self) {
+        if let Some(saved) = self.saved_cursor.as_mut() {
+            saved.y = saved.y.saturating_sub(1);
+        }
     }
 }
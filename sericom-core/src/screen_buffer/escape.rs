@@ -1,7 +1,7 @@
 use crossterm::style::Attributes;
 use tracing::debug;
 
-use super::{Cursor, Line, ScreenBuffer};
+use super::{Cursor, ScreenBuffer};
 use crate::screen_buffer::UIAction;
 
 /// `EscapeState` holds stateful information about the incoming
@@ -28,6 +28,9 @@ pub(super) enum EscapePart {
     Numbers(Vec<char>),
     /// The `Separator` represents the `;` used in ascii escape sequences.
     Separator,
+    /// The `Private` represents the `?`, `>`, `<` or `=` that starts a private
+    /// sequence, i.e. the DEC private modes like `ESC[?25l`.
+    Private(char),
     /// The `Action` represents the (typically) last letter of an escape
     /// sequence that determines what action is to be taken i.e. `ESC[2J`.
     Action(char),
@@ -83,6 +86,49 @@ impl EscapeSequence {
         }
     }
 
+    /// Returns the text of the sequence that has been received so far,
+    /// i.e. `['1', '2'], Separator, ['3']` -> `"12;3"`.
+    pub(super) fn partial_text(&self) -> String {
+        let mut text = String::new();
+        for part in self.sequence.iter().chain(std::iter::once(&self.part)) {
+            match part {
+                EscapePart::Empty => {}
+                EscapePart::Numbers(nums) => text.extend(nums),
+                EscapePart::Separator => text.push(';'),
+                EscapePart::Private(marker) => text.push(*marker),
+                EscapePart::Action(action) => text.push(*action),
+            }
+        }
+        text
+    }
+
+    /// Returns the numeric parameters of the sequence, i.e. `ESC[1;;7m` -> `[1, 0, 7]`.
+    ///
+    /// Empty parameters default to `0`.
+    fn params(&self) -> Vec<u16> {
+        let mut params = vec![0];
+        for part in &self.sequence {
+            match part {
+                EscapePart::Numbers(nums) => {
+                    if let Some(last) = params.last_mut() {
+                        *last = nums.iter().collect::<String>().parse().unwrap_or(u16::MAX);
+                    }
+                }
+                EscapePart::Separator => params.push(0),
+                _ => {}
+            }
+        }
+        params
+    }
+
+    /// Appends a [`EscapePart::Private`] to [`Self::sequence`].
+    pub(super) fn push_private(&mut self, marker: char) {
+        if self.part != EscapePart::Empty {
+            self.push_part();
+        }
+        self.sequence.push(EscapePart::Private(marker));
+    }
+
     /// Pushes the action to the escape sequence, signaling the end
     /// and results in carrying out the action for the escape sequence
     /// and then resetting its values.
@@ -101,28 +147,28 @@ impl ScreenBuffer {
         let _enter = span.enter();
         match &self.escape_sequence.sequence[..] {
             [
-                EscapePart::Numbers(x),
-                EscapePart::Separator,
-                EscapePart::Numbers(y),
-                EscapePart::Separator,
-                EscapePart::Numbers(z),
-                EscapePart::Action('m'),
-            ] => {
-                debug!("Got: 'ESC[{:?};{:?};{:?}m'", x, y, z);
-                let x: u16 = x.iter().collect::<String>().parse().unwrap();
-                let y: u16 = y.iter().collect::<String>().parse().unwrap();
-                let z: u16 = z.iter().collect::<String>().parse().unwrap();
-                if x == 0 {
-                    self.display_attributes = Attributes::none();
-                }
-                if y == 1 {
-                    self.display_attributes
-                        .set(crossterm::style::Attribute::Bold);
-                }
-                if z == 7 {
-                    self.display_attributes
-                        .set(crossterm::style::Attribute::Reverse);
-                }
+                EscapePart::Private('?'),
+                EscapePart::Numbers(nums),
+                EscapePart::Action(action @ ('h' | 'l')),
+            ] if nums.iter().eq(['2', '5'].iter()) => {
+                debug!("Got: 'ESC[?25{}'", action);
+                // Show (`h`) or hide (`l`) the cursor
+                self.cursor_visible = *action == 'h';
+                self.needs_render = true;
+                self.escape_state = EscapeState::Normal;
+            }
+            // Other private sequences, i.e. `ESC[?1049h` or `ESC[>4;1m`, aren't handled
+            [EscapePart::Private(_), ..] => {
+                debug!(
+                    "Unhandled ESC: 'ESC[{}'",
+                    self.escape_sequence.partial_text()
+                );
+                self.escape_state = EscapeState::Normal;
+            }
+            [.., EscapePart::Action('m')] => {
+                let params = self.escape_sequence.params();
+                debug!("Got: 'ESC[{:?}m'", params);
+                self.set_graphic_rendition(&params);
                 self.escape_state = EscapeState::Normal;
             }
             [
@@ -135,17 +181,26 @@ impl ScreenBuffer {
                 match action {
                     // Move cursor to (line_num, col_num)
                     'H' | 'f' => {
-                        // Can unwrap because it is guaranteed elsewhere that
-                        // `EscapePart::Numbers(Vec<Char>)` only holds ascii digits (0-9).
-                        let mut line_num: u16 =
-                            line_nums.iter().collect::<String>().parse().unwrap();
-                        let col_num: u16 = col_nums.iter().collect::<String>().parse().unwrap();
-                        if line_num <= 1 {
-                            line_num = (self.lines.len() as u16).saturating_sub(self.height);
-                        } else {
-                            line_num += (self.lines.len() as u16).saturating_sub(self.height);
-                        }
-                        self.set_cursor_pos((col_num.saturating_sub(1), line_num));
+                        // Numbers too large for a `u16` are saturated, the cursor is
+                        // clamped to the screen anyway
+                        let line_num: u16 = line_nums
+                            .iter()
+                            .collect::<String>()
+                            .parse()
+                            .unwrap_or(u16::MAX);
+                        let col_num: u16 = col_nums
+                            .iter()
+                            .collect::<String>()
+                            .parse()
+                            .unwrap_or(u16::MAX);
+                        self.set_cursor_row(line_num.saturating_sub(1));
+                        self.set_cursor_col(col_num.saturating_sub(1));
+                    }
+                    // Set the scroll region to rows (line_num, col_num)
+                    'r' => {
+                        let top: u16 = line_nums.iter().collect::<String>().parse().unwrap_or(0);
+                        let bottom: u16 = col_nums.iter().collect::<String>().parse().unwrap_or(0);
+                        self.set_scroll_region(top, bottom);
                     }
                     _ => {}
                 }
@@ -160,10 +215,18 @@ impl ScreenBuffer {
                 match action {
                     // Move cursor to (same, col_num)
                     'H' | 'f' => {
-                        // Can unwrap because it is guaranteed elsewhere that
-                        // `EscapePart::Numbers(Vec<Char>)` only holds ascii digits (0-9).
-                        let col_num: u16 = col_nums.iter().collect::<String>().parse().unwrap();
-                        self.cursor_pos.x = col_num.saturating_sub(1);
+                        // Numbers too large for a `u16` are saturated
+                        let col_num: u16 = col_nums
+                            .iter()
+                            .collect::<String>()
+                            .parse()
+                            .unwrap_or(u16::MAX);
+                        self.set_cursor_col(col_num.saturating_sub(1));
+                    }
+                    // Set the scroll region to rows (top of the screen, col_num)
+                    'r' => {
+                        let bottom: u16 = col_nums.iter().collect::<String>().parse().unwrap_or(0);
+                        self.set_scroll_region(0, bottom);
                     }
                     _ => {}
                 }
@@ -171,9 +234,8 @@ impl ScreenBuffer {
             }
             [EscapePart::Numbers(nums), EscapePart::Action(action)] => {
                 debug!("Got: 'ESC[{:?}{}'", nums, action);
-                // Can unwrap because it is guaranteed elsewhere that
-                // `EscapePart::Numbers(Vec<Char>)` only holds ascii digits (0-9).
-                let num: u16 = nums.iter().collect::<String>().parse().unwrap();
+                // Numbers too large for a `u16` are saturated
+                let num: u16 = nums.iter().collect::<String>().parse().unwrap_or(u16::MAX);
                 match (num, action) {
                     // Move cursor up # of lines
                     (num, 'A') => self.move_cursor_up(num),
@@ -185,15 +247,30 @@ impl ScreenBuffer {
                     (num, 'D') => self.move_cursor_left(num),
                     // Moves cursor to beginning of line, # lines down
                     (num, 'E') => {
-                        self.set_cursor_pos((0, (self.cursor_pos.y as u16) + num));
-                        while self.cursor_pos.y > self.lines.len() {
-                            self.lines.push_back(Line::new(self.width as usize));
-                        }
+                        self.move_cursor_down(num);
+                        self.set_cursor_col(0);
                     }
                     // Moves cursor to beginning of line, # lines up
-                    (num, 'F') => self.set_cursor_pos((0, (self.cursor_pos.y as u16) - num)),
+                    (num, 'F') => {
+                        self.move_cursor_up(num);
+                        self.set_cursor_col(0);
+                    }
                     // Moves cursor to column #
                     (num, 'G') => self.set_cursor_col(num),
+                    // Moves cursor to line # of the screen
+                    (num, 'd') => self.set_cursor_row(num.saturating_sub(1)),
+                    // Moves cursor down # of lines
+                    (num, 'e') => self.move_cursor_down(num),
+                    // Repeats the last character # times, 0 meaning once
+                    (num, 'b') => self.repeat_last_char(num.max(1)),
+                    // Sets the scroll region to rows (#, bottom of the screen)
+                    (num, 'r') => self.set_scroll_region(num, 0),
+                    // Inserts # blank lines at the cursor
+                    (num, 'L') => self.insert_lines(num),
+                    // Deletes # lines from the cursor
+                    (num, 'M') => self.delete_lines(num),
+                    // Deletes # characters from the cursor
+                    (num, 'P') => self.delete_chars(num),
                     // Erase from cursor until end of screen
                     (0, 'J') => self.clear_from_cursor_to_eos(),
                     // Erase from cursor to beginning of screen
@@ -214,8 +291,9 @@ impl ScreenBuffer {
                 debug!("Got: 'ESC[{}'", action);
                 match action {
                     // Set cursor position to 0, 0 of screen
-                    'H' => {
-                        self.set_cursor_pos((0, self.lines.len().saturating_sub(self.view_start)));
+                    'H' | 'f' => {
+                        self.set_cursor_row(0);
+                        self.set_cursor_col(0);
                     }
                     // Erase from cursor until end of screen
                     'J' => self.clear_from_cursor_to_eos(),
@@ -223,9 +301,16 @@ impl ScreenBuffer {
                     'K' => self.clear_from_cursor_to_eol(),
                     'C' => self.move_cursor_right(1),
                     'D' => self.move_cursor_left(1),
-                    'm' => {
-                        self.display_attributes = Attributes::none();
-                    }
+                    'd' => self.set_cursor_row(0),
+                    'e' => self.move_cursor_down(1),
+                    'b' => self.repeat_last_char(1),
+                    // Scrolls the whole screen again
+                    'r' => self.set_scroll_region(0, 0),
+                    'L' => self.insert_lines(1),
+                    'M' => self.delete_lines(1),
+                    'P' => self.delete_chars(1),
+                    's' => self.save_cursor(),
+                    'u' => self.restore_cursor(),
                     action if action.is_alphabetic() => {}
                     _ => {}
                 }
@@ -237,4 +322,291 @@ impl ScreenBuffer {
             }
         }
     }
+
+    /// Applies the parameters of a 'Select Graphic Rendition' (`ESC[...m`) sequence
+    /// to the attributes and colors of the characters that are received afterwards,
+    /// including those on the following lines.
+    ///
+    /// Handles the 8/16 colors, the 256 colors (`38;5;n`) and the RGB colors
+    /// (`38;2;r;g;b`), as well as their `48` counterparts for the background.
+    fn set_graphic_rendition(&mut self, params: &[u16]) {
+        use crossterm::style::{Attribute, Color};
+
+        let mut params = params.iter();
+        while let Some(&param) = params.next() {
+            match param {
+                0 => {
+                    self.display_attributes = Attributes::none();
+                    self.display_colors = (None, None);
+                }
+                1 => self.display_attributes.set(Attribute::Bold),
+                2 => self.display_attributes.set(Attribute::Dim),
+                3 => self.display_attributes.set(Attribute::Italic),
+                4 => self.display_attributes.set(Attribute::Underlined),
+                5 => self.display_attributes.set(Attribute::SlowBlink),
+                7 => self.display_attributes.set(Attribute::Reverse),
+                8 => self.display_attributes.set(Attribute::Hidden),
+                9 => self.display_attributes.set(Attribute::CrossedOut),
+                22 => {
+                    self.display_attributes.unset(Attribute::Bold);
+                    self.display_attributes.unset(Attribute::Dim);
+                }
+                23 => self.display_attributes.unset(Attribute::Italic),
+                24 => self.display_attributes.unset(Attribute::Underlined),
+                25 => self.display_attributes.unset(Attribute::SlowBlink),
+                27 => self.display_attributes.unset(Attribute::Reverse),
+                28 => self.display_attributes.unset(Attribute::Hidden),
+                29 => self.display_attributes.unset(Attribute::CrossedOut),
+                30..=37 => self.display_colors.0 = Some(Color::AnsiValue((param - 30) as u8)),
+                40..=47 => self.display_colors.1 = Some(Color::AnsiValue((param - 40) as u8)),
+                90..=97 => self.display_colors.0 = Some(Color::AnsiValue((param - 82) as u8)),
+                100..=107 => self.display_colors.1 = Some(Color::AnsiValue((param - 92) as u8)),
+                39 => self.display_colors.0 = None,
+                49 => self.display_colors.1 = None,
+                // `38;5;n` / `38;2;r;g;b`
+                38 | 48 => {
+                    let color = match params.next() {
+                        Some(5) => params.next().map(|&n| Color::AnsiValue(n as u8)),
+                        Some(2) => match (params.next(), params.next(), params.next()) {
+                            (Some(&r), Some(&g), Some(&b)) => Some(Color::Rgb {
+                                r: r as u8,
+                                g: g as u8,
+                                b: b as u8,
+                            }),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if color.is_some() {
+                        if param == 38 {
+                            self.display_colors.0 = color;
+                        } else {
+                            self.display_colors.1 = color;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[test]
+fn line_position_absolute() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"1\r\n2\r\n3\r\n4\r\n5\r\n6");
+    // The screen shows lines 3-6
+    buffer.add_data(b"\x1b[2dX");
+    assert_eq!(buffer.line_text(3), "4X");
+
+    buffer.add_data(b"\x1b[dY");
+    assert_eq!(buffer.line_text(2), "3 Y");
+
+    // Clamped to the bottom of the screen
+    buffer.add_data(b"\x1b[99dZ");
+    assert_eq!(buffer.line_text(5), "6  Z");
+    assert_eq!(buffer.lines.len(), 6);
+
+    buffer.add_data(b"\r\x1b[2d\x1b[eW");
+    assert_eq!(buffer.line_text(4), "W");
+}
+
+#[test]
+fn clear_screen_on_new_buffer() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"\x1b[2J\x1b[HA");
+    assert_eq!(buffer.view_start, buffer.lines.len() - 4);
+    assert_eq!(buffer.line_text(buffer.view_start), "A");
+
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"\x1b[H\x1b[1JB");
+    assert_eq!(buffer.cursor_pos, (1, 0u16).into());
+
+    // The screen has fewer lines than its height
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"\x1b[3;5HC\x1b[2FD");
+    assert_eq!(buffer.line_text(2), "    C");
+    assert_eq!(buffer.line_text(0), "D");
+    buffer.add_data(b"\x1b[9FE");
+    assert_eq!(buffer.line_text(0), "E");
+}
+
+#[test]
+fn colors_carry_across_lines() {
+    use crossterm::style::Color;
+
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(4, 4);
+    // The first line is filled exactly to the width before wrapping
+    buffer.add_data(b"\x1b[31;44mabcdef\r\ngh\x1b[39mi\x1b[0mj");
+    let red = (Some(Color::AnsiValue(1)), Some(Color::AnsiValue(4)));
+    for (line, col) in [(0, 0), (0, 3), (1, 0), (1, 1), (2, 0), (2, 1)] {
+        let cell = &buffer.lines[line][col];
+        assert_eq!((cell.fg_color, cell.bg_color), red, "cell ({col}, {line})");
+    }
+    let cell = &buffer.lines[2][2];
+    assert_eq!(
+        (cell.fg_color, cell.bg_color),
+        (None, Some(Color::AnsiValue(4)))
+    );
+    let cell = &buffer.lines[2][3];
+    assert_eq!((cell.fg_color, cell.bg_color), (None, None));
+
+    buffer.add_data(b"\x1b[38;5;208m\r\nk\x1b[48;2;1;2;3ml");
+    assert_eq!(buffer.lines[4][0].fg_color, Some(Color::AnsiValue(208)));
+    assert_eq!(
+        buffer.lines[4][1].bg_color,
+        Some(Color::Rgb { r: 1, g: 2, b: 3 })
+    );
+}
+
+#[test]
+fn cursor_forward_and_back() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"abcdef\x1b[4D");
+    assert_eq!(buffer.cursor_pos, (2, 0u16).into());
+    // `C` moves the cursor right and `D` moves it left
+    buffer.add_data(b"\x1b[C");
+    assert_eq!(buffer.cursor_pos, (3, 0u16).into());
+    buffer.add_data(b"\x1b[2CX\x1b[DY");
+    assert_eq!(buffer.line_text(0), "abcdeY");
+}
+
+#[test]
+fn scroll_region_new_lines() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 4);
+    // The last row is a status line outside of the region
+    buffer.add_data(b"\x1b[1;3r\x1b[4;1Hstatus\x1b[1;1Ha\r\nb\r\nc\r\nd\r\ne");
+    assert_eq!(buffer.scroll_region, Some((0, 2)));
+    assert_eq!(buffer.lines.len(), 4);
+    let screen: Vec<String> = (0..4).map(|idx| buffer.line_text(idx)).collect();
+    assert_eq!(screen, ["c", "d", "e", "status"]);
+    assert_eq!(buffer.cursor_pos, (1, 2u16).into());
+
+    // A region that doesn't start at the top keeps the rows above it
+    buffer.add_data(b"\x1b[2;3r");
+    assert_eq!(buffer.cursor_pos, (0, 0u16).into());
+    buffer.add_data(b"\x1b[3;1Hf\r\ng");
+    let screen: Vec<String> = (0..4).map(|idx| buffer.line_text(idx)).collect();
+    assert_eq!(screen, ["c", "f", "g", "status"]);
+
+    // Resetting the region scrolls the whole screen into the history again
+    buffer.add_data(b"\x1b[r\x1b[4;1H\r\nh");
+    assert_eq!(buffer.scroll_region, None);
+    assert_eq!(buffer.lines.len(), 5);
+    assert_eq!(buffer.line_text(4), "h");
+
+    // Invalid and whole-screen regions don't set a region
+    buffer.add_data(b"\x1b[3;3r\x1b[1;4r\x1b[;9r");
+    assert_eq!(buffer.scroll_region, None);
+    buffer.add_data(b"\x1b[2r");
+    assert_eq!(buffer.scroll_region, Some((1, 3)));
+}
+
+#[test]
+fn insert_and_delete_lines_and_chars() {
+    super::init_test_config();
+    let screen = |buffer: &ScreenBuffer| -> Vec<String> {
+        let top = buffer.lines.len().saturating_sub(buffer.height as usize);
+        (top..buffer.lines.len())
+            .map(|idx| buffer.line_text(idx))
+            .collect()
+    };
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"a\r\nb\r\nc\r\nd\x1b[2;3H\x1b[2L");
+    assert_eq!(screen(&buffer), ["a", "", "", "b"]);
+    assert_eq!(buffer.cursor_pos, (0, 1u16).into());
+    assert_eq!(buffer.lines.len(), 4);
+
+    buffer.add_data(b"\x1b[M");
+    assert_eq!(screen(&buffer), ["a", "", "b", ""]);
+    buffer.add_data(b"\x1b[9M");
+    assert_eq!(screen(&buffer), ["a", "", "", ""]);
+    assert_eq!(buffer.lines.len(), 4);
+
+    // Only the lines within the scroll region move
+    buffer.add_data(b"\x1b[1;1Hw\r\nx\r\ny\r\nstatus\x1b[1;3r\x1b[2;1H\x1b[L");
+    assert_eq!(screen(&buffer), ["w", "", "x", "status"]);
+    buffer.add_data(b"\x1b[2M");
+    assert_eq!(screen(&buffer), ["w", "", "", "status"]);
+    // Outside of the region, the lines are left as they are
+    buffer.add_data(b"\x1b[4;1H\x1b[L");
+    assert_eq!(screen(&buffer), ["w", "", "", "status"]);
+
+    buffer.add_data(b"\x1b[4;2H\x1b[2P");
+    assert_eq!(screen(&buffer)[3], "stus");
+    buffer.add_data(b"\x1b[P");
+    assert_eq!(screen(&buffer)[3], "sus");
+    buffer.add_data(b"\x1b[99P");
+    assert_eq!(screen(&buffer)[3], "s");
+}
+
+#[test]
+fn save_and_restore_cursor() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"prompt> \x1b[s\x1b[4;1Hmenu\x1b[ux");
+    assert_eq!(buffer.line_text(0), "prompt> x");
+    assert_eq!(buffer.line_text(3), "menu");
+
+    // The saved position moves with its line when the oldest lines are dropped
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.max_scrollback = 3;
+    buffer.add_data(b"a\r\nb\r\nc\x1b[s\r\nd\r\ne\x1b[u!");
+    assert_eq!(buffer.lines.len(), 3);
+    assert_eq!(buffer.line_text(0), "c!");
+
+    // Restoring without a saved position goes to the top left of the screen,
+    // not of the scrollback
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.add_data(b"ab\r\ncd\r\nef\r\ngh\x1b[uX");
+    assert_eq!(buffer.line_text(0), "ab");
+    assert_eq!(buffer.line_text(2), "Xf");
+    assert_eq!(buffer.line_text(3), "gh");
+}
+
+#[test]
+fn show_and_hide_cursor() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.add_data(b"a\x1b[?25lb");
+    assert!(!buffer.cursor_visible);
+    assert_eq!(buffer.line_text(0), "ab");
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    assert!(!String::from_utf8_lossy(&frame).contains("\x1b[?25h"));
+
+    buffer.add_data(b"\x1b[?25h");
+    assert!(buffer.cursor_visible);
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    assert!(String::from_utf8_lossy(&frame).ends_with("\x1b[?25h"));
+
+    // Other private sequences are dropped rather than written as text
+    buffer.add_data(b"\x1b[?1049hc\x1b[>4;1md");
+    assert_eq!(buffer.line_text(0), "abcd");
+    assert!(buffer.lines[0][3].attrs.is_empty());
+}
+
+#[test]
+fn oversized_numbers_saturate() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"a\r\nb\x1b[99999A");
+    assert_eq!(buffer.cursor_pos.y, 0);
+    buffer.add_data(b"\x1b[1;99999HX");
+    assert_eq!(buffer.line_text(0), "a        X");
+    buffer.add_data(b"\x1b[99999;1HY\x1b[;99999HZ");
+    assert_eq!(buffer.line_text(3), "Y        Z");
+    buffer.add_data(b"\x1b[99999999999D");
+    assert_eq!(buffer.cursor_pos.x, 0);
+
+    // `E` moves to the start of a line further down
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"abc\x1b[2EV");
+    assert_eq!(buffer.line_text(2), "V");
 }
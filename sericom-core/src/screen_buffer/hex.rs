@@ -0,0 +1,112 @@
+use super::ScreenBuffer;
+use std::collections::VecDeque;
+
+/// The number of bytes shown on each row of the hex view.
+const BYTES_PER_ROW: usize = 16;
+/// The most bytes kept for the hex view, older bytes are dropped a row at a time.
+const MAX_HEX_BYTES: usize = 64 * 1024;
+
+/// The raw bytes received from the device, shown as a hexdump with
+/// [`UICommand::ToggleHexView`][super::UICommand::ToggleHexView].
+#[derive(Debug, Default)]
+pub(super) struct HexDump {
+    bytes: VecDeque<u8>,
+    /// The offset of the first byte in `bytes` since the start of the session.
+    offset: u64,
+}
+
+impl HexDump {
+    /// Appends `data`, dropping the oldest rows once more than [`MAX_HEX_BYTES`] are kept.
+    pub(super) fn push(&mut self, data: &[u8]) {
+        self.bytes.extend(data);
+        if self.bytes.len() > MAX_HEX_BYTES {
+            // Whole rows are dropped so that the offsets stay aligned
+            let excess = self.bytes.len() - MAX_HEX_BYTES;
+            let drop = excess.div_ceil(BYTES_PER_ROW) * BYTES_PER_ROW;
+            self.bytes.drain(..drop);
+            self.offset += drop as u64;
+        }
+    }
+
+    /// Returns the number of rows, the last one may be partially filled.
+    pub(super) fn rows(&self) -> usize {
+        self.bytes.len().div_ceil(BYTES_PER_ROW)
+    }
+
+    /// Formats row `idx` like `hexdump -C`, i.e. the offset, the bytes in two
+    /// groups of 8 and the printable ASCII characters between `|`s.
+    pub(super) fn row(&self, idx: usize) -> String {
+        let start = idx * BYTES_PER_ROW;
+        let bytes: Vec<u8> = self
+            .bytes
+            .range(start.min(self.bytes.len())..(start + BYTES_PER_ROW).min(self.bytes.len()))
+            .copied()
+            .collect();
+        let mut row = format!("{:08x} ", self.offset + start as u64);
+        for col in 0..BYTES_PER_ROW {
+            if col % 8 == 0 {
+                row.push(' ');
+            }
+            match bytes.get(col) {
+                Some(byte) => row.push_str(&format!("{byte:02x} ")),
+                None => row.push_str("   "),
+            }
+        }
+        row.push('|');
+        row.extend(bytes.iter().map(|&byte| match byte {
+            0x20..=0x7e => byte as char,
+            _ => '.',
+        }));
+        row.push('|');
+        row
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.offset += self.bytes.len() as u64;
+        self.bytes.clear();
+    }
+}
+
+impl ScreenBuffer {
+    /// Switches between showing the lines and a hexdump of the bytes received,
+    /// the lines keep being updated while the hexdump is shown.
+    pub(crate) fn toggle_hex_view(&mut self) {
+        self.hex_view = !self.hex_view;
+        self.needs_render = true;
+    }
+
+    /// Returns the rows of the hexdump on the screen, the newest ones at the bottom.
+    pub(super) fn displayed_hex_rows(&self) -> Vec<String> {
+        let rows = self.hex_dump.rows();
+        let first = rows.saturating_sub(self.height as usize);
+        (first..rows).map(|idx| self.hex_dump.row(idx)).collect()
+    }
+}
+
+#[test]
+fn hex_view_rows() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(80, 2);
+    buffer.add_data(b"hello\r\n\x1b[1mworld");
+    buffer.add_data(b"!\x00\xff");
+    assert_eq!(
+        buffer.displayed_hex_rows(),
+        [
+            "00000000  68 65 6c 6c 6f 0d 0a 1b  5b 31 6d 77 6f 72 6c 64 |hello...[1mworld|",
+            "00000010  21 00 ff                                         |!..|",
+        ]
+    );
+    // Synthetic text from flushing isn't part of the bytes received
+    buffer.add_data(b"\x1b[1");
+    buffer.flush();
+    assert_eq!(buffer.hex_dump.rows(), 2);
+    assert_eq!(buffer.line_text(1), "world!\0\u{fffd}[1");
+
+    buffer.hex_dump.push(&[b'a'; MAX_HEX_BYTES]);
+    assert_eq!(buffer.hex_dump.offset, 32);
+    assert!(buffer.hex_dump.row(0).starts_with("00000020 "));
+
+    assert!(!buffer.hex_view);
+    buffer.toggle_hex_view();
+    assert!(buffer.hex_view);
+}
@@ -3,25 +3,70 @@ use std::ops::{Index, IndexMut};
 
 /// Line is a wrapper around [`Vec<Cell>`] and represents a line within the [`ScreenBuffer`][`super::ScreenBuffer`].
 #[derive(Clone, Debug)]
-pub struct Line(Vec<Cell>);
+pub struct Line {
+    cells: Vec<Cell>,
+    /// Whether the line was soft-wrapped, meaning it continues on the next line
+    /// rather than having ended with a new line.
+    wrapped: bool,
+    /// When the line was created, shown in the timestamp gutter.
+    timestamp: chrono::DateTime<chrono::Local>,
+    /// Whether the line matches the screen's display filter, `None` until it is
+    /// checked and again once the line is written to.
+    filter_match: Option<bool>,
+}
 
 impl Line {
     /// Create a new line with the length/size of `width`.
     ///
     /// Filled with [`Cell::default()`].
     pub fn new(width: usize) -> Self {
-        Self(vec![Cell::default(); width])
+        Self {
+            cells: vec![Cell::default(); width],
+            wrapped: false,
+            timestamp: chrono::Local::now(),
+            filter_match: None,
+        }
+    }
+
+    /// Creates a line of `width` cells from `cells`, padded with [`Cell::default()`]
+    /// or truncated as needed.
+    pub(super) fn from_cells(
+        mut cells: Vec<Cell>,
+        width: usize,
+        wrapped: bool,
+        timestamp: chrono::DateTime<chrono::Local>,
+    ) -> Self {
+        cells.resize(width, Cell::default());
+        Self {
+            cells,
+            wrapped,
+            timestamp,
+            filter_match: None,
+        }
+    }
+
+    /// Consumes the line, returning its [`Cell`]s.
+    pub(super) fn into_cells(self) -> Vec<Cell> {
+        self.cells
     }
 
     /// Iterates over all the [`Cell`]s within the line and sets them to [`Cell::default()`].
+    ///
+    /// The line's timestamp is set to now, as it is blank until new characters are written.
     pub fn reset(&mut self) {
-        self.0.iter_mut().for_each(|cell| *cell = Cell::default());
+        self.cells
+            .iter_mut()
+            .for_each(|cell| *cell = Cell::default());
+        self.wrapped = false;
+        self.timestamp = chrono::Local::now();
+        self.filter_match = None;
     }
 
     /// Iterates over the [`Cell`]s to index `idx` within [`Self`]
     /// and sets them to [`Cell::default()`].
     pub fn reset_to(&mut self, idx: usize) {
-        self.0[..idx]
+        self.filter_match = None;
+        self.cells[..idx]
             .iter_mut()
             .for_each(|cell| *cell = Cell::default());
     }
@@ -29,36 +74,78 @@ impl Line {
     /// Iterates over the [`Cell`]s from index `idx` within [`Self`]
     /// to the end of [`Self`] and sets them to [`Cell::default()`].
     pub fn reset_from(&mut self, idx: usize) {
-        self.0
+        self.filter_match = None;
+        self.cells
             .iter_mut()
             .skip(idx)
             .for_each(|cell| *cell = Cell::default());
+        self.wrapped = false;
+    }
+
+    /// Removes `count` [`Cell`]s from index `idx`, shifting the rest of the line
+    /// to the left and filling its end with [`Cell::default()`].
+    pub fn delete_cells(&mut self, idx: usize, count: usize) {
+        let idx = idx.min(self.cells.len());
+        let count = count.min(self.cells.len() - idx);
+        self.cells.drain(idx..idx + count);
+        self.cells.resize(self.cells.len() + count, Cell::default());
+        self.filter_match = None;
     }
 
     /// Sets the character in [`Cell`] at [`Self`]\[`idx`\] to `ch`.
     pub fn set_char(&mut self, idx: usize, ch: char) {
-        self.0[idx].character = ch;
+        self.cells[idx].character = ch;
+        self.filter_match = None;
     }
 
     /// Util function to return the length of [`Self`].
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> usize {
-        self.0.len()
+        self.cells.len()
     }
 
     /// Iterates over the [`Cell`]s and resets their selected state.
     pub fn clear_selection(&mut self) {
-        self.0.iter_mut().for_each(|cell| cell.is_selected = false);
+        self.cells
+            .iter_mut()
+            .for_each(|cell| cell.is_selected = false);
+    }
+
+    /// Returns whether the line was soft-wrapped onto the next line.
+    pub const fn is_wrapped(&self) -> bool {
+        self.wrapped
+    }
+
+    /// Marks the line as soft-wrapped onto the next line.
+    pub fn set_wrapped(&mut self, wrapped: bool) {
+        self.wrapped = wrapped;
+    }
+
+    /// Returns the time at which the line was created.
+    pub const fn timestamp(&self) -> &chrono::DateTime<chrono::Local> {
+        &self.timestamp
     }
 
     /// Returns a reference to [`Cell`] at `idx`.
     pub fn get_cell(&self, idx: usize) -> Option<&Cell> {
-        self.0.get(idx)
+        self.cells.get(idx)
     }
 
     /// Returns a mutable reference to [`Cell`] at `idx`.
     pub fn get_mut_cell(&mut self, idx: usize) -> Option<&mut Cell> {
-        self.0.get_mut(idx)
+        self.filter_match = None;
+        self.cells.get_mut(idx)
+    }
+
+    /// Returns whether the line matched the display filter when it was last checked,
+    /// `None` if it was written to since.
+    pub(super) const fn filter_match(&self) -> Option<bool> {
+        self.filter_match
+    }
+
+    /// Caches whether the line matches the display filter, see [`Self::filter_match()`].
+    pub(super) fn set_filter_match(&mut self, filter_match: Option<bool>) {
+        self.filter_match = filter_match;
     }
 }
 
@@ -67,7 +154,7 @@ impl IntoIterator for Line {
     type IntoIter = std::vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.cells.into_iter()
     }
 }
 
@@ -76,7 +163,7 @@ impl<'a> IntoIterator for &'a Line {
     type IntoIter = std::slice::Iter<'a, Cell>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.iter()
+        self.cells.iter()
     }
 }
 
@@ -85,19 +172,21 @@ impl<'a> IntoIterator for &'a mut Line {
     type IntoIter = std::slice::IterMut<'a, Cell>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.iter_mut()
+        self.filter_match = None;
+        self.cells.iter_mut()
     }
 }
 
 impl Index<usize> for Line {
     type Output = Cell;
     fn index(&self, index: usize) -> &Self::Output {
-        &self.0[index]
+        &self.cells[index]
     }
 }
 
 impl IndexMut<usize> for Line {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.0[index]
+        self.filter_match = None;
+        &mut self.cells[index]
     }
 }
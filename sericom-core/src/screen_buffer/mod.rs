@@ -13,18 +13,21 @@
 //! locations within the screen for things like copying to a clipboard.
 //!
 //! The screen buffer solves these issues by storing each line received from the
-//! connection in a [`VecDeque`]. It is important to note that
-//! currently, the **capacity of the [`VecDeque`] is hardcoded with a value of 10,000
-//! lines with [`MAX_SCROLLBACK`]**.
+//! connection in a [`VecDeque`]. It is important to note that the **capacity of
+//! the [`VecDeque`] defaults to 10,000 lines with [`MAX_SCROLLBACK`]**, see
+//! [`Defaults::scrollback`][crate::configs::Defaults::scrollback].
 
 mod cell;
 mod cursor;
 mod escape;
+mod hex;
 mod line;
 mod render;
+pub(crate) use render::TAB_WIDTH;
+mod search;
 mod ui_command;
 pub use cell::*;
-use crossterm::style::Attributes;
+use crossterm::style::{Attributes, Color};
 pub use cursor::*;
 use escape::{EscapeSequence, EscapeState};
 pub use line::*;
@@ -32,9 +35,37 @@ pub use ui_command::*;
 
 use std::collections::VecDeque;
 
-/// The maximum number of lines stored in memory in [`ScreenBuffer`].
+/// The default maximum number of lines stored in memory in [`ScreenBuffer`],
+/// see [`Defaults::scrollback`][crate::configs::Defaults::scrollback].
 pub const MAX_SCROLLBACK: usize = 10000;
 
+/// The smallest `(width, height)` returned by [`terminal_size()`].
+pub const MIN_TERMINAL_SIZE: (u16, u16) = (20, 3);
+
+/// How long the screen is inverted for a visual bell.
+const VISUAL_BELL_DURATION: tokio::time::Duration = tokio::time::Duration::from_millis(100);
+
+/// Returns the size of the terminal, clamped to at least [`MIN_TERMINAL_SIZE`].
+///
+/// Falls back to `80x24` if the size can't be read. Some terminals (i.e. some CI
+/// or pty environments) report a size of `0x0`, which would leave no room to draw.
+pub fn terminal_size() -> (u16, u16) {
+    let (width, height) = crossterm::terminal::size().unwrap_or((80, 24));
+    (
+        width.max(MIN_TERMINAL_SIZE.0),
+        height.max(MIN_TERMINAL_SIZE.1),
+    )
+}
+
+/// Returns the number of lines a [`ScreenBuffer`] of `height` keeps for a
+/// scrollback of `lines`, where `0` is unbounded.
+fn max_scrollback(lines: usize, height: u16) -> usize {
+    match lines {
+        0 => usize::MAX,
+        lines => lines.max(height as usize),
+    }
+}
+
 /// The `ScreenBuffer` holds rendering state for the entire terminal's window/frame.
 ///
 /// It mainly serves to allow for user-interactions that require a history and location
@@ -51,12 +82,27 @@ pub struct ScreenBuffer {
     /// Current view into the buffer.
     /// Denotes which line is at the top of the screen.
     view_start: usize,
+    /// Whether the view scrolls to the bottom when new data is received. Cleared
+    /// by scrolling up and set again once scrolled back to the bottom, like a pager.
+    follow: bool,
     /// Position of the cursor within the `ScreenBuffer`.
     cursor_pos: Position,
+    /// The rows of the screen, from `0` and inclusive, that scroll when a new line
+    /// is started on the bottom row, set with `ESC[<top>;<bottom>r` (DECSTBM).
+    /// `None` scrolls the whole screen into the history.
+    scroll_region: Option<(u16, u16)>,
+    /// The cursor's position saved with `ESC[s`, restored with `ESC[u`.
+    saved_cursor: Option<Position>,
+    /// Whether the cursor is drawn, hidden by the device with `ESC[?25l` and
+    /// shown again with `ESC[?25h`.
+    cursor_visible: bool,
     /// Start of text selection. Used for highlighting and copying to clipboard.
     selection_start: Option<(u16, usize)>,
     /// End of text selection. Used for highlighting and copying to clipboard.
     selection_end: Option<(u16, usize)>,
+    /// Whether a selection is being dragged with the mouse, scrolling keeps the
+    /// selection while dragging so that it can extend past the screen.
+    dragging: bool,
     /// Configuration for the maximum amount of lines to keep in memory.
     max_scrollback: usize,
     /// Represents the current state for handling ansii escape sequences
@@ -65,38 +111,446 @@ pub struct ScreenBuffer {
     /// As ascii escape sequences are recieved, they are built in the
     /// [`EscapeSequence`] to evaluate upon a completed escape sequence.
     escape_sequence: EscapeSequence,
+    /// The first bytes of a UTF-8 character that is split between calls to
+    /// [`ScreenBuffer::add_data()`].
+    utf8_pending: Vec<u8>,
     /// Represents the time since [`ScreenBuffer::render()`] was last called.
     last_render: Option<tokio::time::Instant>,
+    /// The time between two frames, from [`Appearance::max_fps`][crate::configs::Appearance::max_fps].
+    render_interval: tokio::time::Duration,
+    /// The attributes (bold, reverse, etc.) applied to incoming characters,
+    /// set by `ESC[...m` sequences.
     display_attributes: Attributes,
+    /// The foreground and background colors applied to incoming characters, set
+    /// by `ESC[...m` sequences. `None` uses the session's colors.
+    display_colors: (Option<Color>, Option<Color>),
     /// Indicates that [`ScreenBuffer`] has new data and needs to render.
     needs_render: bool,
+    /// Whether lines that scroll off of the screen are kept in memory.
+    /// See [`ScreenBuffer::disable_scrollback()`].
+    scrollback_enabled: bool,
+    /// A short-lived message drawn over the last line of the screen.
+    /// Cleared when new data is received.
+    notice: Option<String>,
+    /// State of the UI that is shared with the task processing user input.
+    ui_state: std::sync::Arc<UIState>,
+    /// The character drawn in the last column of soft-wrapped lines.
+    /// See [`Appearance::wrap_marker`][crate::configs::Appearance::wrap_marker].
+    wrap_marker: Option<char>,
+    /// Whether lines that are too long for the screen are soft-wrapped onto the
+    /// next line. See [`Appearance::wrap`][crate::configs::Appearance::wrap].
+    wrap_lines: bool,
+    /// Whether the time each line was received is shown in a gutter
+    /// to the left of the line. See [`UICommand::ToggleTimestamps`].
+    show_timestamps: bool,
+    /// A hash of the last frame written to the terminal, used to skip
+    /// rendering frames that haven't changed.
+    last_frame_hash: Option<u64>,
+    /// The commands of each row of the last frame written to the terminal, used
+    /// to only redraw the rows that changed.
+    last_rows: Vec<Vec<u8>>,
+    /// The size of the last frame, used to allocate the next frame.
+    frame_capacity: usize,
+    /// The colors used for cells that don't have colors of their own. Starts as
+    /// the config's [`Appearance`][crate::configs::Appearance] and can be changed
+    /// during the session.
+    current_appearance: SessionAppearance,
+    /// A line of user input drawn over the last line of the screen,
+    /// see [`UICommand::ShowPrompt`].
+    prompt: Option<String>,
+    /// The last character written to the screen, repeated by `ESC[nb`.
+    last_char: Option<char>,
+    /// A graph of the recent throughput drawn in the bottom-right corner of
+    /// the screen, see [`UICommand::ToggleThroughput`].
+    sparkline: Option<String>,
+    /// The state of the connection drawn on the terminal's last row, which is
+    /// reserved for it once set. See [`ScreenBuffer::set_status()`].
+    status: Option<String>,
+    /// Whether the list of keys is drawn over the bottom of the screen,
+    /// see [`UICommand::ToggleHelp`].
+    show_help: bool,
+    /// What the bell character does, see [`Defaults::bell`][crate::configs::Defaults::bell].
+    bell: crate::configs::BellMode,
+    /// Whether a bell was received since the last render, with [`BellMode::Audible`][crate::configs::BellMode].
+    pending_bell: bool,
+    /// When the screen stops being inverted after a bell, with [`BellMode::Visual`][crate::configs::BellMode].
+    flash_until: Option<tokio::time::Instant>,
+    /// Whether control characters are shown in caret notation (i.e. `^C`) instead
+    /// of being acted on, see [`UICommand::ToggleControlChars`].
+    show_control_chars: bool,
+    /// Only the lines that match the filter are displayed, all lines are
+    /// displayed when `None`. See [`UICommand::SetDisplayFilter`].
+    display_filter: Option<crate::configs::LineFilter>,
+    /// The column, starting at `1`, where a vertical ruler is drawn.
+    /// See [`Appearance::ruler_column`][crate::configs::Appearance::ruler_column].
+    ruler_column: Option<u16>,
+    /// The text searched for in the history, see [`UICommand::Search`].
+    search: Option<search::Search>,
+    /// The bytes received, shown as a hexdump instead of the lines while
+    /// `hex_view` is set. See [`UICommand::ToggleHexView`].
+    hex_dump: hex::HexDump,
+    hex_view: bool,
+}
+
+/// The colors of a session, see [`ScreenBuffer::current_appearance`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SessionAppearance {
+    fg: crossterm::style::Color,
+    bg: crossterm::style::Color,
+    hl_fg: crossterm::style::Color,
+    hl_bg: crossterm::style::Color,
+}
+
+impl From<&crate::configs::Appearance> for SessionAppearance {
+    fn from(appearance: &crate::configs::Appearance) -> Self {
+        Self {
+            fg: (&appearance.fg).into(),
+            bg: (&appearance.bg).into(),
+            hl_fg: (&appearance.hl_fg).into(),
+            hl_bg: (&appearance.hl_bg).into(),
+        }
+    }
 }
 
 impl ScreenBuffer {
     /// Constructs a new `ScreenBuffer`.
     ///
-    /// Takes the `width` and `height` of the terminal.
+    /// Takes the `width` and `height` of the terminal, see [`terminal_size()`].
+    /// A `width` or `height` of `0` is treated as `1`.
     pub fn new(width: u16, height: u16) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let config = crate::configs::get_config();
+        let appearance = &config.appearance;
         let mut buffer = Self {
             width,
             height,
             lines: VecDeque::new(),
             view_start: 0,
+            follow: true,
             cursor_pos: Position::home(),
+            scroll_region: None,
+            saved_cursor: None,
+            cursor_visible: true,
             selection_start: None,
             selection_end: None,
-            max_scrollback: MAX_SCROLLBACK,
+            dragging: false,
+            max_scrollback: max_scrollback(config.defaults.scrollback, height),
             last_render: None,
+            render_interval: tokio::time::Duration::from_secs(1) / appearance.max_fps.get() as u32,
             needs_render: false,
             escape_state: EscapeState::Normal,
             escape_sequence: EscapeSequence::new(),
+            utf8_pending: Vec::new(),
             display_attributes: Attributes::none(),
+            display_colors: (None, None),
+            scrollback_enabled: true,
+            notice: None,
+            ui_state: std::sync::Arc::default(),
+            wrap_marker: appearance.wrap_marker,
+            wrap_lines: appearance.wrap,
+            show_timestamps: appearance.timestamps,
+            last_frame_hash: None,
+            last_rows: Vec::new(),
+            frame_capacity: 0,
+            current_appearance: appearance.into(),
+            prompt: None,
+            last_char: None,
+            sparkline: None,
+            status: None,
+            show_help: false,
+            bell: config.defaults.bell,
+            pending_bell: false,
+            flash_until: None,
+            show_control_chars: false,
+            display_filter: Some(&appearance.display_filter)
+                .filter(|filter| !filter.is_empty())
+                .cloned(),
+            ruler_column: appearance.ruler_column.map(std::num::NonZeroU16::get),
+            search: None,
+            hex_dump: hex::HexDump::default(),
+            hex_view: false,
         };
         // Start with an empty line
         buffer.lines.push_back(Line::new(width as usize));
         buffer
     }
 
+    /// Shares `ui_state` with the [`ScreenBuffer`] so that it keeps it up-to-date.
+    pub fn with_ui_state(mut self, ui_state: std::sync::Arc<UIState>) -> Self {
+        self.ui_state = ui_state;
+        self
+    }
+
+    /// Sets the number of lines kept in memory, `0` for no limit, instead of
+    /// [`Defaults::scrollback`][crate::configs::Defaults::scrollback].
+    pub fn with_scrollback(mut self, lines: usize) -> Self {
+        self.max_scrollback = max_scrollback(lines, self.height);
+        self
+    }
+
+    /// Disables the scrollback buffer, only keeping the lines that are visible
+    /// on the screen in memory. Lines are dropped as soon as they scroll off of
+    /// the screen and scrolling is disabled.
+    ///
+    /// Meant to minimize memory usage for when only the live output is needed.
+    pub fn disable_scrollback(&mut self) {
+        self.max_scrollback = self.height as usize;
+        self.scrollback_enabled = false;
+        while self.lines.len() > self.max_scrollback {
+            self.lines.pop_front();
+            self.cursor_pos.y = self.cursor_pos.y.saturating_sub(1);
+            self.shift_saved_cursor();
+        }
+        self.view_start = 0;
+    }
+
+    /// Resizes the buffer to the terminal's new size, re-wrapping the lines that were
+    /// soft-wrapped to the new width. Stays scrolled to the bottom if it already was.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        // The status line isn't part of the screen
+        let height = height.saturating_sub(self.status.is_some() as u16);
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        let at_bottom = self.at_bottom();
+        self.clear_selection();
+        // The rows of the region and the saved cursor may no longer be on the screen
+        self.scroll_region = None;
+        self.saved_cursor = None;
+        if width != self.width {
+            let old_wrap_width = self.wrap_width() as usize;
+            self.width = width;
+            self.reflow(old_wrap_width);
+        }
+        self.height = height;
+        if !self.scrollback_enabled {
+            self.max_scrollback = height as usize;
+        }
+        // Never drop lines that are still visible on the screen
+        self.max_scrollback = self.max_scrollback.max(height as usize);
+        while self.lines.len() > self.max_scrollback {
+            self.lines.pop_front();
+            self.cursor_pos.y = self.cursor_pos.y.saturating_sub(1);
+        }
+        let max_view_start = self.lines.len().saturating_sub(height as usize);
+        self.view_start = if at_bottom || !self.scrollback_enabled {
+            max_view_start
+        } else {
+            self.view_start.min(max_view_start)
+        };
+        self.invalidate();
+    }
+
+    /// Joins the lines that were soft-wrapped at `old_wrap_width` and splits them
+    /// again at the current [`ScreenBuffer::wrap_width()`], keeping the cursor on the
+    /// same character.
+    fn reflow(&mut self, old_wrap_width: usize) {
+        let width = self.width as usize;
+        let cursor = (self.cursor_pos.x as usize, self.cursor_pos.y);
+        let mut lines = VecDeque::with_capacity(self.lines.len());
+        // The cells of the line being joined, the time its first part was received
+        // and the cursor's offset within it
+        let mut joined: Vec<Cell> = Vec::new();
+        let mut timestamp = None;
+        let mut cursor_offset = None;
+
+        for (y, line) in std::mem::take(&mut self.lines).into_iter().enumerate() {
+            let wrapped = line.is_wrapped();
+            timestamp.get_or_insert(*line.timestamp());
+            if y == cursor.1 {
+                cursor_offset = Some(joined.len() + cursor.0);
+            }
+            let mut cells = line.into_cells();
+            if wrapped {
+                cells.truncate(old_wrap_width);
+            } else {
+                let min_len = if y == cursor.1 { cursor.0 } else { 0 };
+                let len = cells
+                    .iter()
+                    .rposition(|cell| !cell.is_blank())
+                    .map_or(0, |idx| idx + 1);
+                cells.truncate(len.max(min_len));
+            }
+            joined.append(&mut cells);
+            if !wrapped {
+                self.push_rewrapped(
+                    &mut lines,
+                    &mut joined,
+                    timestamp.take(),
+                    cursor_offset.take(),
+                );
+            }
+        }
+        // The parts of a soft-wrapped last line haven't been pushed yet
+        if timestamp.is_some() {
+            self.push_rewrapped(&mut lines, &mut joined, timestamp, cursor_offset);
+        }
+        if lines.is_empty() {
+            lines.push_back(Line::new(width));
+        }
+        self.cursor_pos.y = self.cursor_pos.y.min(lines.len() - 1);
+        self.lines = lines;
+    }
+
+    /// Splits the `joined` cells of a line into `lines` at [`ScreenBuffer::wrap_width()`],
+    /// moving the cursor to `cursor_offset` within them.
+    fn push_rewrapped(
+        &mut self,
+        lines: &mut VecDeque<Line>,
+        joined: &mut Vec<Cell>,
+        timestamp: Option<chrono::DateTime<chrono::Local>>,
+        cursor_offset: Option<usize>,
+    ) {
+        let wrap_width = self.wrap_width() as usize;
+        let timestamp = timestamp.unwrap_or_else(chrono::Local::now);
+        let mut parts = joined.len().div_ceil(wrap_width).max(1);
+        if let Some(offset) = cursor_offset {
+            self.cursor_pos = Position {
+                x: (offset % wrap_width) as u16,
+                y: lines.len() + offset / wrap_width,
+            };
+            parts = parts.max(offset / wrap_width + 1);
+        }
+        let mut cells = std::mem::take(joined).into_iter();
+        for part in 0..parts {
+            let part_cells = cells.by_ref().take(wrap_width).collect();
+            let wrapped = part + 1 < parts;
+            lines.push_back(Line::from_cells(
+                part_cells,
+                self.width as usize,
+                wrapped,
+                timestamp,
+            ));
+        }
+    }
+
+    /// Shows `msg` over the last line of the screen until new data is received.
+    pub(crate) fn set_notice<S: Into<String>>(&mut self, msg: S) {
+        self.notice = Some(msg.into());
+        self.needs_render = true;
+    }
+
+    /// Sets the graph drawn in the bottom-right corner of the screen, hidden
+    /// while a notice or prompt is shown.
+    pub(crate) fn set_sparkline(&mut self, sparkline: Option<String>) {
+        if self.sparkline != sparkline {
+            self.sparkline = sparkline;
+            self.needs_render = true;
+        }
+    }
+
+    /// Draws `status` on the terminal's last row, i.e. `Connected | /dev/ttyUSB0 9600 8N1`.
+    /// The first status reserves the row, the screen's height is reduced by one.
+    pub(crate) fn set_status(&mut self, status: String) {
+        if self.status.is_none() {
+            let (width, height) = (self.width, self.height);
+            self.status = Some(status);
+            self.resize(width, height);
+        } else if self.status.as_ref() != Some(&status) {
+            self.status = Some(status);
+            self.needs_render = true;
+        }
+    }
+
+    /// Acts on the bell character according to [`ScreenBuffer::bell`].
+    fn ring_bell(&mut self) {
+        use crate::configs::BellMode;
+        match self.bell {
+            BellMode::None => return,
+            BellMode::Audible => self.pending_bell = true,
+            BellMode::Visual => {
+                self.flash_until = Some(tokio::time::Instant::now() + VISUAL_BELL_DURATION);
+            }
+        }
+        self.needs_render = true;
+    }
+
+    /// Makes the terminal beep on the next render whatever the [`ScreenBuffer::bell`]
+    /// is, i.e. when a line matches [`notify_on`][crate::configs::Defaults::notify_on].
+    pub(crate) fn alert(&mut self) {
+        self.pending_bell = true;
+        self.needs_render = true;
+    }
+
+    /// Returns when the screen should stop being inverted after a visual bell,
+    /// see [`ScreenBuffer::end_flash()`].
+    pub(crate) fn flash_deadline(&self) -> Option<tokio::time::Instant> {
+        self.flash_until
+    }
+
+    /// Stops inverting the screen after a visual bell.
+    pub(crate) fn end_flash(&mut self) {
+        if self.flash_until.take().is_some() {
+            self.needs_render = true;
+        }
+    }
+
+    /// Returns whether `line` matches the [`ScreenBuffer::display_filter`], using
+    /// the result cached by [`ScreenBuffer::cache_filter_matches()`] if there is one.
+    fn is_displayed(&self, line: &Line) -> bool {
+        self.display_filter.as_ref().is_none_or(|filter| {
+            line.filter_match()
+                .unwrap_or_else(|| filter_matches(filter, line))
+        })
+    }
+
+    /// Caches whether the lines that [`ScreenBuffer::displayed_lines()`] looks at
+    /// match the display filter, so that each line is only matched again once
+    /// it has been written to rather than on every render.
+    pub(super) fn cache_filter_matches(&mut self) {
+        let Some(filter) = self.display_filter.as_ref() else {
+            return;
+        };
+        let view_end = (self.view_start + self.height as usize).min(self.lines.len());
+        let mut matched = 0;
+        for line in self.lines.range_mut(..view_end).rev() {
+            if matched == self.height as usize {
+                break;
+            }
+            let line_match = match line.filter_match() {
+                Some(line_match) => line_match,
+                None => {
+                    let line_match = filter_matches(filter, line);
+                    line.set_filter_match(Some(line_match));
+                    line_match
+                }
+            };
+            matched += line_match as usize;
+        }
+    }
+
+    /// Returns the indices of the lines drawn on each row of the screen.
+    ///
+    /// Without a display filter, these are the `height` lines from `view_start`.
+    /// With one, these are the last `height` matching lines that are not past
+    /// the bottom of the view.
+    pub(super) fn displayed_lines(&self) -> Vec<usize> {
+        let view_end = (self.view_start + self.height as usize).min(self.lines.len());
+        if self.display_filter.is_none() {
+            return (self.view_start..view_end).collect();
+        }
+        let mut displayed: Vec<usize> = (0..view_end)
+            .rev()
+            .filter(|&idx| self.is_displayed(&self.lines[idx]))
+            .take(self.height as usize)
+            .collect();
+        displayed.reverse();
+        displayed
+    }
+
+    /// Returns the index of the line drawn on row `screen_y` of the screen.
+    fn screen_line_idx(&self, screen_y: usize) -> usize {
+        match self.display_filter {
+            Some(_) => self
+                .displayed_lines()
+                .get(screen_y)
+                .copied()
+                .unwrap_or(self.lines.len().saturating_sub(1)),
+            None => self.view_start + screen_y,
+        }
+    }
+
     fn set_char_at_cursor(&mut self, ch: char) {
         while self.cursor_pos.y >= self.lines.len() {
             self.lines.push_back(Line::new(self.width as usize));
@@ -106,6 +560,20 @@ impl ScreenBuffer {
             && (self.cursor_pos.x as usize) < line.len()
         {
             line.set_char(self.cursor_pos.x as usize, ch);
+            let cell = &mut line[self.cursor_pos.x as usize];
+            cell.attrs = Attributes::none();
+            (cell.fg_color, cell.bg_color) = (None, None);
+        }
+    }
+
+    /// Moves the cursor one column to the left. At the start of a line, moves the
+    /// cursor to the last column of the previous line like a terminal would.
+    fn backspace(&mut self) {
+        if self.cursor_pos.x == 0 && self.cursor_pos.y > 0 {
+            let last_col = self.wrap_width().saturating_sub(1);
+            self.set_cursor_pos((last_col, self.cursor_pos.y - 1));
+        } else {
+            self.move_cursor_left(1);
         }
     }
 
@@ -117,10 +585,8 @@ impl ScreenBuffer {
 
     fn clear_from_cursor_to_sos(&mut self) {
         self.clear_from_cursor_to_sol();
-        for line in self
-            .lines
-            .range_mut(self.view_start..=self.cursor_pos.y - 1)
-        {
+        let start = self.view_start.min(self.cursor_pos.y);
+        for line in self.lines.range_mut(start..self.cursor_pos.y) {
             line.reset();
         }
     }
@@ -144,7 +610,81 @@ impl ScreenBuffer {
         }
     }
 
+    /// Returns the indices of the first and last lines that scroll, those of the
+    /// [`ScreenBuffer::scroll_region`] or of the whole screen.
+    fn scroll_lines(&self) -> (usize, usize) {
+        let screen_top = self.lines.len().saturating_sub(self.height as usize);
+        let (top, bottom) = self.scroll_region.unwrap_or((0, self.height - 1));
+        (screen_top + top as usize, screen_top + bottom as usize)
+    }
+
+    /// Inserts `count` blank lines at the cursor's line (`ESC[nL`), shifting the lines
+    /// below it down and dropping those shifted past the bottom of the scroll region.
+    fn insert_lines(&mut self, count: u16) {
+        let (top, bottom) = self.scroll_lines();
+        let y = self.cursor_pos.y;
+        if y < top || y > bottom {
+            return;
+        }
+        for _ in 0..(count.max(1) as usize).min(bottom - y + 1) {
+            self.lines.insert(y, Line::new(self.width as usize));
+            if self.lines.len() > bottom + 1 {
+                self.lines.remove(bottom + 1);
+            }
+        }
+        self.set_cursor_col(0);
+    }
+
+    /// Removes `count` lines from the cursor's line (`ESC[nM`), shifting the lines
+    /// below it up and adding blank lines at the bottom of the scroll region.
+    fn delete_lines(&mut self, count: u16) {
+        let (top, bottom) = self.scroll_lines();
+        let y = self.cursor_pos.y;
+        if y < top || y > bottom {
+            return;
+        }
+        for _ in 0..(count.max(1) as usize).min(bottom - y + 1) {
+            if y < self.lines.len() {
+                self.lines.remove(y);
+            }
+            // A screen with fewer lines than its height has no lines below to shift
+            if bottom <= self.lines.len() {
+                self.lines.insert(bottom, Line::new(self.width as usize));
+            }
+        }
+        while self.cursor_pos.y >= self.lines.len() {
+            self.lines.push_back(Line::new(self.width as usize));
+        }
+        self.set_cursor_col(0);
+    }
+
+    /// Removes `count` characters from the cursor (`ESC[nP`), shifting the rest
+    /// of the line to the left.
+    fn delete_chars(&mut self, count: u16) {
+        let x = self.cursor_pos.x as usize;
+        if let Some(line) = self.lines.get_mut(self.cursor_pos.y) {
+            line.delete_cells(x, count.max(1) as usize);
+        }
+    }
+
+    /// Returns whether the view shows the most recent lines.
+    fn at_bottom(&self) -> bool {
+        self.view_start + self.height as usize >= self.lines.len()
+    }
+
     fn new_line(&mut self) {
+        if let Some((top, bottom)) = self.scroll_region {
+            let screen_top = self.lines.len().saturating_sub(self.height as usize);
+            let bottom = screen_top + bottom as usize;
+            if self.cursor_pos.y == bottom {
+                // Only the rows of the region scroll, the top row is dropped
+                // rather than kept in the history
+                self.lines.remove(screen_top + top as usize);
+                self.lines.insert(bottom, Line::new(self.width as usize));
+                self.set_cursor_pos((0, bottom));
+                return;
+            }
+        }
         self.set_cursor_pos((0, self.cursor_pos.y + 1));
 
         if self.cursor_pos.y >= self.lines.len() {
@@ -161,6 +701,71 @@ impl ScreenBuffer {
             if self.view_start > 0 {
                 self.view_start -= 1;
             }
+            self.shift_selection();
+            self.shift_saved_cursor();
+        }
+    }
+
+    /// Sets the [`ScreenBuffer::scroll_region`] to the rows `top` through `bottom`,
+    /// counted from `1` like `ESC[<top>;<bottom>r`, and moves the cursor to the top
+    /// left of the screen. A `0` is the top or bottom of the screen, and a region of
+    /// less than two rows is ignored.
+    pub(super) fn set_scroll_region(&mut self, top: u16, bottom: u16) {
+        let top = top.max(1) - 1;
+        let bottom = match bottom {
+            0 => self.height,
+            bottom => bottom.min(self.height),
+        } - 1;
+        if top >= bottom {
+            return;
+        }
+        self.scroll_region = (top > 0 || bottom < self.height - 1).then_some((top, bottom));
+        self.set_cursor_row(0);
+        self.set_cursor_col(0);
+    }
+
+    /// Moves the selection along with its lines after the oldest line was dropped,
+    /// keeping whatever remains of it when it started on the dropped line.
+    fn shift_selection(&mut self) {
+        let shift = |(x, line): (u16, usize)| match line {
+            0 => (0, 0),
+            line => (x, line - 1),
+        };
+        match (self.selection_start, self.selection_end) {
+            // All of the selection was on the dropped line
+            (Some((_, 0)), Some((_, 0))) => self.clear_selection(),
+            (start, end) => {
+                self.selection_start = start.map(shift);
+                self.selection_end = end.map(shift);
+            }
         }
     }
 }
+
+/// Returns whether the text of `line` matches `filter`.
+fn filter_matches(filter: &crate::configs::LineFilter, line: &Line) -> bool {
+    let text: String = line.into_iter().map(|cell| cell.character).collect();
+    filter.matches(text.trim_end())
+}
+
+#[cfg(test)]
+pub(crate) fn init_test_config() {
+    let _ = crate::configs::CONFIG.set(crate::configs::Config::default());
+}
+
+#[cfg(test)]
+impl ScreenBuffer {
+    /// Returns the characters of the line at `idx` with the trailing blanks trimmed.
+    pub(crate) fn line_text(&self, idx: usize) -> String {
+        self.lines
+            .get(idx)
+            .map(|line| {
+                line.into_iter()
+                    .map(|cell| cell.character)
+                    .collect::<String>()
+            })
+            .unwrap_or_default()
+            .trim_end()
+            .to_string()
+    }
+}
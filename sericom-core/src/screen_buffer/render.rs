@@ -1,25 +1,54 @@
-use crossterm::style::Color;
-use std::io::BufWriter;
 use tracing::instrument;
 
-use super::{Cursor, EscapeState, Line, ScreenBuffer, UIAction};
-use crate::configs::get_config;
+use super::{EscapeState, Line, ScreenBuffer, TIMESTAMP_FORMAT, UIAction};
 
-const MIN_RENDER_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_millis(33);
+/// The number of columns between tab stops.
+pub(crate) const TAB_WIDTH: u16 = 8;
+/// The character drawn for the ruler, see [`ScreenBuffer::ruler_column`].
+const RULER: char = '│';
+/// Drawn in the top-right corner of the screen while new data doesn't scroll
+/// to the bottom, see [`ScreenBuffer::follow`].
+const PAUSED: &str = "[PAUSED]";
 
 impl ScreenBuffer {
     /// Takes incoming data (bytes (`u8`) from a serial connection) and
     /// processes them accordingly, handling ascii escape sequences, to
     /// render as characters/strings in the terminal.
+    ///
+    /// The data is decoded as UTF-8, invalid bytes are shown as `U+FFFD`. A
+    /// character that is split between calls is held until the rest of it is received.
+    ///
+    /// The bytes are also kept for the hexdump shown with
+    /// [`UICommand::ToggleHexView`][super::UICommand::ToggleHexView].
     #[instrument(name = "Add Data", skip(self, data))]
     pub fn add_data(&mut self, data: &[u8]) {
-        let text = String::from_utf8_lossy(data);
+        self.hex_dump.push(data);
+        self.process_data(data);
+    }
+
+    /// Processes `data` without keeping it for the hexdump, i.e. the text that
+    /// [`Self::flush()`] writes for a partial sequence.
+    fn process_data(&mut self, data: &[u8]) {
+        self.notice = None;
+        let data: std::borrow::Cow<[u8]> = if self.utf8_pending.is_empty() {
+            data.into()
+        } else {
+            let mut joined = std::mem::take(&mut self.utf8_pending);
+            joined.extend_from_slice(data);
+            joined.into()
+        };
+        let complete = complete_utf8_len(&data);
+        self.utf8_pending = data[complete..].to_vec();
+        let text = String::from_utf8_lossy(&data[..complete]);
         let mut chars = text.chars().peekable();
 
         while let Some(ch) = chars.next() {
             match self.escape_state {
                 EscapeState::Normal => {
                     match ch {
+                        c if self.show_control_chars && c.is_ascii_control() => {
+                            self.add_caret_notation(c);
+                        }
                         '\r' => {
                             self.cursor_pos.x = 0;
                             if chars.peek() == Some(&'\n') {
@@ -30,7 +59,10 @@ impl ScreenBuffer {
                         '\n' => {
                             self.new_line();
                         }
-                        '\x07' => {}
+                        '\t' => self.horizontal_tab(),
+                        // Form feed, i.e. between pages of `show` output
+                        '\x0C' => self.clear_screen_home(),
+                        '\x07' => self.ring_bell(),
                         '\x0E' => {}
                         '\x0F' => {}
                         '\x08' => {
@@ -42,12 +74,12 @@ impl ScreenBuffer {
                                 // Consume them - to remove from further processing
                                 chars.next();
                                 chars.next();
-                                self.move_cursor_left(1);
+                                self.backspace();
                                 self.set_char_at_cursor(' ');
                             } else {
                                 // If not the deletion sequence, move cursor left
                                 // when receiving a single '\x08'
-                                self.move_cursor_left(1);
+                                self.backspace();
                             }
                         }
                         '\x1B' => self.escape_state = EscapeState::Esc,
@@ -56,7 +88,7 @@ impl ScreenBuffer {
                             while let Some(&next_ch) = chars.peek() {
                                 if next_ch.is_control()
                                     || next_ch == '\x1B'
-                                    || self.cursor_pos.x + batch.len() as u16 >= self.width
+                                    || self.cursor_pos.x + batch.len() as u16 >= self.wrap_width()
                                 {
                                     break;
                                 }
@@ -80,13 +112,42 @@ impl ScreenBuffer {
                         self.escape_sequence.reset();
                         self.escape_state = EscapeState::Normal;
                     }
-                    // NOTE: May need to handle '?', ':', and '>'
+                    c @ ('?' | '>' | '<' | '=') => self.escape_sequence.push_private(c),
+                    // NOTE: May need to handle ':'
                     _ => self.escape_state = EscapeState::Normal,
                 },
             }
         }
-        // Sets `self.needs_render = true`
-        self.scroll_to_bottom();
+        if self.follow {
+            // Sets `self.needs_render = true`
+            self.scroll_to_bottom();
+        } else {
+            self.needs_render = true;
+        }
+    }
+
+    /// Processes an escape sequence that was only partially received.
+    ///
+    /// Escape sequences that are split between calls to [`Self::add_data`] are
+    /// held until the rest of the sequence is received. If the data ends in the
+    /// middle of a sequence, `flush` writes what was held (minus the `ESC` itself)
+    /// to the screen as text, so that the trailing bytes aren't silently dropped.
+    ///
+    /// A partially received UTF-8 character is written as `U+FFFD`.
+    pub fn flush(&mut self) {
+        let incomplete_char = !std::mem::take(&mut self.utf8_pending).is_empty();
+        let mut partial = match self.escape_state {
+            EscapeState::Normal | EscapeState::Esc => String::new(),
+            EscapeState::Csi => format!("[{}", self.escape_sequence.partial_text()),
+        };
+        self.escape_sequence.reset();
+        self.escape_state = EscapeState::Normal;
+        if incomplete_char {
+            partial.push(char::REPLACEMENT_CHARACTER);
+        }
+        if !partial.is_empty() {
+            self.process_data(partial.as_bytes());
+        }
     }
 
     fn add_char_batch(&mut self, chars: &[char]) {
@@ -95,11 +156,20 @@ impl ScreenBuffer {
             self.lines.push_back(Line::new(self.width as usize));
         }
 
+        self.last_char = chars.last().copied().or(self.last_char);
+        let wrap_width = self.wrap_width();
         if let Some(line) = self.lines.get_mut(self.cursor_pos.y) {
             for &ch in chars {
                 line.set_char(self.cursor_pos.x as usize, ch);
+                let cell = &mut line[self.cursor_pos.x as usize];
+                cell.attrs = self.display_attributes;
+                (cell.fg_color, cell.bg_color) = self.display_colors;
                 self.cursor_pos.x += 1;
-                if self.cursor_pos.x >= self.width {
+                if self.cursor_pos.x >= wrap_width && !self.wrap_lines {
+                    // The next characters are drawn over the last column
+                    self.cursor_pos.x = wrap_width - 1;
+                } else if self.cursor_pos.x >= wrap_width {
+                    line.set_wrapped(true);
                     self.new_line();
                     break;
                 }
@@ -107,7 +177,55 @@ impl ScreenBuffer {
         }
     }
 
+    /// Writes a control character in caret notation, i.e. `^C` for `\x03` and `^[`
+    /// for `ESC`. Newlines are also still moved to, so that lines stay readable.
+    fn add_caret_notation(&mut self, ch: char) {
+        let caret = (ch as u8 ^ 0x40) as char;
+        self.add_char_batch(&['^']);
+        self.add_char_batch(&[caret]);
+        if ch == '\n' {
+            self.new_line();
+        }
+    }
+
+    /// Writes the last character written to the screen `count` more times,
+    /// wrapping onto the next lines as needed (`ESC[nb`).
+    pub(super) fn repeat_last_char(&mut self, count: u16) {
+        let Some(ch) = self.last_char else {
+            return;
+        };
+        let mut remaining = count as usize;
+        while remaining > 0 {
+            let space = self.wrap_width().saturating_sub(self.cursor_pos.x).max(1) as usize;
+            let batch = vec![ch; remaining.min(space)];
+            remaining -= batch.len();
+            self.add_char_batch(&batch);
+        }
+    }
+
+    /// Moves the cursor to the next tab stop, tab stops are every [`TAB_WIDTH`] columns.
+    /// Stops at the last column of the line.
+    fn horizontal_tab(&mut self) {
+        let next_stop = (self.cursor_pos.x / TAB_WIDTH + 1) * TAB_WIDTH;
+        self.cursor_pos.x = next_stop.min(self.wrap_width().saturating_sub(1));
+    }
+
+    /// Returns the column at which lines are soft-wrapped onto the next line.
+    ///
+    /// The columns of the timestamp gutter aren't available to the lines, and
+    /// when a wrap marker is configured, the last column is reserved for it.
+    pub(super) fn wrap_width(&self) -> u16 {
+        let width = self.width.saturating_sub(self.gutter_width()).max(1);
+        match self.wrap_marker {
+            Some(_) => width.saturating_sub(1).max(1),
+            None => width,
+        }
+    }
+
     /// A helper function to check whether the terminal's screen should be rendered.
+    ///
+    /// The pace is set by rendering every [`ScreenBuffer::render_interval()`], this
+    /// only guards against rendering much more often than that.
     pub fn should_render_now(&self) -> bool {
         use tokio::time::Instant;
 
@@ -117,112 +235,933 @@ impl ScreenBuffer {
 
         let now = Instant::now();
         match self.last_render {
-            Some(last) => now.duration_since(last) >= MIN_RENDER_INTERVAL,
+            Some(last) => now.duration_since(last) >= self.render_interval / 2,
             None => true,
         }
     }
 
+    /// Returns whether anything changed since the last render.
+    pub fn needs_render(&self) -> bool {
+        self.needs_render
+    }
+
+    /// Returns the time between two frames while data is being received,
+    /// from [`Appearance::max_fps`][crate::configs::Appearance::max_fps].
+    pub fn render_interval(&self) -> tokio::time::Duration {
+        self.render_interval
+    }
+
     /// Writes the lines/characters received from `add_data` to the terminal's screen.
     ///
-    /// As of now, `render` does not involve any diff-ing of previous renders.
-    /// The nature of communicating to devices over a serial connection is similar
-    /// that of a terminal; lines get printed to a screen and with each new line,
-    /// all of the previously rendered characters must be re-rendered one cell higher.
+    /// Only the rows of the screen that changed since the previous frame are
+    /// redrawn, and the frame is skipped if it is identical to the previous one.
     ///
-    /// Because of this, the only diff-ing that would make sense would be
-    /// that of the cells within the screen that are simply blank.
+    /// Rows are compared rather than cells; with each new line all of the lines
+    /// move one row higher, so every row changes while the device is printing,
+    /// but a prompt being typed on or a progress bar only redraws its own row.
     pub fn render(&mut self) -> std::io::Result<()> {
-        use crossterm::{cursor, queue, style};
-        use std::io::{self, Write};
+        self.render_to(&mut std::io::stdout().lock())
+    }
+
+    /// Writes the frame like [`ScreenBuffer::render()`] to `writer` instead of
+    /// stdout, i.e. to embed the screen in another TUI or to snapshot it:
+    ///
+    /// ```
+    /// use sericom_core::screen_buffer::{ScreenBuffer, UIAction};
+    ///
+    /// let mut buffer = ScreenBuffer::new(80, 24);
+    /// buffer.add_data(b"\x1b[1mSwitch#\x1b[0m show version\r\n");
+    /// buffer.scroll_to_top();
+    /// let mut frame = Vec::new();
+    /// buffer.render_to(&mut frame).unwrap();
+    /// assert!(String::from_utf8_lossy(&frame).contains("Switch#"));
+    /// ```
+    ///
+    /// Like [`ScreenBuffer::render()`], only the rows that changed since the previous
+    /// call are written. Call [`ScreenBuffer::invalidate()`] first to write a whole
+    /// frame, i.e. for each snapshot.
+    ///
+    /// No config needs to be initialized, the default config is used until one is.
+    pub fn render_to<W: std::io::Write>(&mut self, writer: &mut W) -> std::io::Result<()> {
         use tokio::time::Instant;
 
         if !self.needs_render {
             return Ok(());
         }
 
-        let mut writer = BufWriter::new(io::stdout());
-        queue!(writer, cursor::Hide)?;
-        let config = get_config();
+        self.cache_filter_matches();
+        let mut frame = Vec::with_capacity(self.frame_capacity);
+        let previous = std::mem::take(&mut self.last_rows);
+        self.last_rows = self.queue_changed_rows(&mut frame, &previous)?;
+        self.frame_capacity = frame.len();
+
+        if self.frame_changed(&frame) {
+            writer.write_all(&frame)?;
+            writer.flush()?;
+        }
+        if std::mem::take(&mut self.pending_bell) {
+            writer.write_all(b"\x07")?;
+            writer.flush()?;
+        }
+
+        self.last_render = Some(Instant::now());
+        self.needs_render = false;
+        Ok(())
+    }
+
+    /// Forgets the previous frame, so that the next [`ScreenBuffer::render()`] or
+    /// [`ScreenBuffer::render_to()`] redraws the whole screen.
+    pub fn invalidate(&mut self) {
+        self.last_frame_hash = None;
+        self.last_rows.clear();
+        self.needs_render = true;
+    }
+
+    /// Returns whether `frame` differs from the last frame that was checked,
+    /// storing a hash of `frame` to compare the next frame against.
+    fn frame_changed(&mut self, frame: &[u8]) -> bool {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        frame.hash(&mut hasher);
+        let frame_hash = hasher.finish();
+        self.last_frame_hash.replace(frame_hash) != Some(frame_hash)
+    }
+
+    /// Queues the commands to draw the visible lines of the buffer to `writer`,
+    /// leaving out the rows of the screen that are the same in `previous`.
+    ///
+    /// Returns the commands of each row, the overlays drawn on a row included,
+    /// to compare the next frame against. The last row is the status line.
+    ///
+    /// Each [`Cell`][super::Cell]'s attributes are applied as they change and reset
+    /// at the start of every line. Selected cells swap their foreground and
+    /// background colors while keeping their attributes.
+    fn queue_changed_rows<W: std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        previous: &[Vec<u8>],
+    ) -> std::io::Result<Vec<Vec<u8>>> {
+        use crossterm::{
+            cursor, queue,
+            style::{self, Attribute, Attributes},
+        };
+
+        let appearance = self.current_appearance;
+        // Inverts the screen for a visual bell
+        let flashing = self.flash_until.is_some();
+
+        let displayed_lines = self.displayed_lines();
+        let hex_rows = match self.hex_view {
+            true => self.displayed_hex_rows(),
+            false => Vec::new(),
+        };
+        let mut rows = vec![Vec::new(); self.height as usize + 1];
+        let last_row = self.height.saturating_sub(1) as usize;
 
         for screen_y in 0..self.height {
-            let line_idx = self.view_start + screen_y as usize;
-            queue!(writer, cursor::MoveTo(0, screen_y))?;
+            let row = &mut rows[screen_y as usize];
+            queue!(row, cursor::MoveTo(0, screen_y))?;
 
-            if let Some(line) = self.lines.get_mut(line_idx) {
-                let mut current_fg = Color::from(&config.appearance.fg);
-                let mut current_bg = Color::from(&config.appearance.bg);
+            if self.hex_view {
+                let hex_row = hex_rows.get(screen_y as usize).map_or("", String::as_str);
+                let hex_row: String = hex_row.chars().take(self.width as usize).collect();
                 queue!(
-                    writer,
+                    row,
+                    style::SetAttribute(Attribute::Reset),
+                    style::SetForegroundColor(appearance.fg),
+                    style::SetBackgroundColor(appearance.bg),
+                    style::Print(format!("{hex_row:<width$}", width = self.width as usize))
+                )?;
+            } else if let Some(line) = displayed_lines
+                .get(screen_y as usize)
+                .and_then(|&idx| self.lines.get(idx))
+            {
+                let mut current_fg = appearance.fg;
+                let mut current_bg = appearance.bg;
+                let mut current_attrs = Attributes::none();
+                queue!(
+                    row,
+                    style::SetAttribute(Attribute::Reset),
                     style::SetForegroundColor(current_fg),
                     style::SetBackgroundColor(current_bg)
                 )?;
 
-                for cell in line {
-                    let global_reverse = self.display_attributes.has(style::Attribute::Reverse);
-
-                    let fg = if (cell.is_selected && !global_reverse)
-                        || (!cell.is_selected && global_reverse)
-                    {
-                        cell.bg_color
-                    } else {
-                        cell.fg_color
-                    };
+                let gutter_width = self.gutter_width();
+                if gutter_width > 0 {
+                    let timestamp = line.timestamp().format(TIMESTAMP_FORMAT).to_string();
+                    let timestamp: String = timestamp.chars().take(gutter_width as usize).collect();
+                    queue!(
+                        row,
+                        style::SetAttribute(Attribute::Dim),
+                        style::Print(timestamp),
+                        style::SetAttribute(Attribute::NormalIntensity)
+                    )?;
+                }
 
-                    let bg = if (cell.is_selected && !global_reverse)
-                        || (!cell.is_selected && global_reverse)
-                    {
-                        cell.fg_color
+                let visible_cells = (self.width - gutter_width) as usize;
+                for cell in line.into_iter().take(visible_cells) {
+                    // Reverse is applied by swapping the colors, selected cells
+                    // always use the highlight colors
+                    let cell_fg = cell.fg_color.unwrap_or(appearance.fg);
+                    let cell_bg = cell.bg_color.unwrap_or(appearance.bg);
+                    let (fg, bg) = if cell.is_selected {
+                        (appearance.hl_fg, appearance.hl_bg)
+                    } else if cell.attrs.has(Attribute::Reverse) {
+                        (cell_bg, cell_fg)
                     } else {
-                        cell.bg_color
+                        (cell_fg, cell_bg)
                     };
+                    let (fg, bg) = if flashing { (bg, fg) } else { (fg, bg) };
+                    let mut attrs = cell.attrs;
+                    attrs.unset(Attribute::Reverse);
 
+                    if attrs != current_attrs {
+                        // `Attribute::Reset` also resets the colors
+                        queue!(
+                            row,
+                            style::SetAttribute(Attribute::Reset),
+                            style::SetAttributes(attrs),
+                            style::SetForegroundColor(fg),
+                            style::SetBackgroundColor(bg)
+                        )?;
+                        current_attrs = attrs;
+                        current_fg = fg;
+                        current_bg = bg;
+                    }
                     if fg != current_fg {
-                        queue!(writer, style::SetForegroundColor(fg))?;
+                        queue!(row, style::SetForegroundColor(fg))?;
                         current_fg = fg;
                     }
                     if bg != current_bg {
-                        queue!(writer, style::SetBackgroundColor(bg))?;
+                        queue!(row, style::SetBackgroundColor(bg))?;
                         current_bg = bg;
                     }
-
-                    if self.display_attributes.has(style::Attribute::Bold) {
-                        queue!(
-                            writer,
-                            style::SetAttribute(style::Attribute::Bold),
-                            style::Print(cell.character)
-                        )?;
-                    } else {
-                        queue!(writer, style::Print(cell.character))?;
-                    }
+                    queue!(row, style::Print(cell.character))?;
+                }
+                queue!(row, style::SetAttribute(Attribute::Reset))?;
+                if let Some(marker) = self.wrap_marker
+                    && line.is_wrapped()
+                {
+                    queue!(
+                        row,
+                        cursor::MoveTo(self.width.saturating_sub(1), screen_y),
+                        style::SetForegroundColor(appearance.fg),
+                        style::SetBackgroundColor(appearance.bg),
+                        style::Print(marker)
+                    )?;
                 }
+            } else if flashing {
+                queue!(
+                    row,
+                    style::SetBackgroundColor(appearance.fg),
+                    style::Print(" ".repeat(self.width as usize)),
+                    style::ResetColor
+                )?;
             } else {
                 queue!(
-                    writer,
+                    row,
                     style::ResetColor,
                     style::Print(" ".repeat(self.width as usize))
                 )?;
             }
+
+            let gutter_width = self.gutter_width();
+            if let Some(column) = self.ruler_column
+                && gutter_width + column <= self.width
+            {
+                // Characters past the ruler are dimmed rather than hidden
+                let ch = displayed_lines
+                    .get(screen_y as usize)
+                    .and_then(|&idx| self.lines.get(idx))
+                    .and_then(|line| line.get_cell(column as usize - 1))
+                    .map(|cell| cell.character)
+                    .filter(|&ch| ch != ' ')
+                    .unwrap_or(RULER);
+                queue!(
+                    row,
+                    cursor::MoveTo(gutter_width + column - 1, screen_y),
+                    style::SetForegroundColor(appearance.fg),
+                    style::SetBackgroundColor(appearance.bg),
+                    style::SetAttribute(Attribute::Dim),
+                    style::Print(ch),
+                    style::SetAttribute(Attribute::Reset)
+                )?;
+            }
+        }
+
+        if let Some(notice) = self.prompt.as_ref().or(self.notice.as_ref()) {
+            let notice: String = notice.chars().take(self.width as usize).collect();
+            queue!(
+                &mut rows[last_row],
+                cursor::MoveTo(0, self.height.saturating_sub(1)),
+                style::SetAttribute(Attribute::Reverse),
+                style::Print(format!("{notice:<width$}", width = self.width as usize)),
+                style::SetAttribute(Attribute::NoReverse)
+            )?;
+        } else if let Some(ref sparkline) = self.sparkline {
+            let len = sparkline.chars().count().min(self.width as usize);
+            let sparkline: String = sparkline.chars().take(len).collect();
+            queue!(
+                &mut rows[last_row],
+                cursor::MoveTo(self.width - len as u16, self.height.saturating_sub(1)),
+                style::SetForegroundColor(appearance.fg),
+                style::SetBackgroundColor(appearance.bg),
+                style::SetAttribute(Attribute::Reverse),
+                style::Print(sparkline),
+                style::SetAttribute(Attribute::NoReverse)
+            )?;
+        }
+
+        if !self.follow && !self.hex_view {
+            let len = PAUSED.len().min(self.width as usize);
+            queue!(
+                &mut rows[0],
+                cursor::MoveTo(self.width - len as u16, 0),
+                style::SetForegroundColor(appearance.fg),
+                style::SetBackgroundColor(appearance.bg),
+                style::SetAttribute(Attribute::Reverse),
+                style::Print(&PAUSED[..len]),
+                style::SetAttribute(Attribute::NoReverse)
+            )?;
+        }
+
+        if self.show_help {
+            let help_rows = self.help_rows();
+            let top = self.height.saturating_sub(help_rows.len() as u16);
+            for (y, help_row) in (top..).zip(help_rows) {
+                queue!(
+                    &mut rows[y as usize],
+                    cursor::MoveTo(0, y),
+                    style::SetForegroundColor(appearance.fg),
+                    style::SetBackgroundColor(appearance.bg),
+                    style::SetAttribute(Attribute::Reverse),
+                    style::Print(format!("{help_row:<width$}", width = self.width as usize)),
+                    style::SetAttribute(Attribute::NoReverse)
+                )?;
+            }
+        }
+
+        if let Some(ref status) = self.status {
+            let status: String = status.chars().take(self.width as usize).collect();
+            queue!(
+                &mut rows[self.height as usize],
+                cursor::MoveTo(0, self.height),
+                style::SetForegroundColor(appearance.fg),
+                style::SetBackgroundColor(appearance.bg),
+                style::SetAttribute(Attribute::Reverse),
+                style::Print(format!("{status:<width$}", width = self.width as usize)),
+                style::SetAttribute(Attribute::NoReverse)
+            )?;
+        }
+
+        queue!(writer, cursor::Hide)?;
+        for (y, row) in rows.iter().enumerate() {
+            if previous.get(y) != Some(row) {
+                writer.write_all(row)?;
+            }
         }
 
         // This is relative the the terminal's L x W, whereas
         // self.cursor_pos.y is within the entire line buf
-        let screen_cursor_y = if self.cursor_pos.y >= self.view_start
-            && self.cursor_pos.y < self.view_start + self.height as usize
-        {
-            (self.cursor_pos.y - self.view_start) as u16
-        } else {
-            self.height - 1
-        };
+        let screen_cursor_y = displayed_lines
+            .iter()
+            .position(|&idx| idx == self.cursor_pos.y)
+            .map_or(self.height - 1, |y| y as u16);
 
-        queue!(
-            writer,
-            cursor::MoveTo(self.cursor_pos.x, screen_cursor_y),
-            cursor::Show
-        )?;
-        writer.flush()?;
+        let screen_cursor_x =
+            (self.cursor_pos.x + self.gutter_width()).min(self.width.saturating_sub(1));
 
-        self.last_render = Some(Instant::now());
-        self.needs_render = false;
-        Ok(())
+        // The device's cursor has no place in the hexdump, and stays hidden
+        // while the device has hidden it with `ESC[?25l`
+        if !self.hex_view && self.cursor_visible {
+            queue!(
+                writer,
+                cursor::MoveTo(screen_cursor_x, screen_cursor_y),
+                cursor::Show
+            )?;
+        }
+        Ok(rows)
+    }
+}
+
+impl ScreenBuffer {
+    /// Returns the [`Keybindings::help_entries()`][crate::configs::Keybindings::help_entries]
+    /// wrapped into rows of the screen's width, at most the screen's height of them.
+    fn help_rows(&self) -> Vec<String> {
+        let width = self.width as usize;
+        let mut rows: Vec<String> = Vec::new();
+        for entry in crate::configs::get_config().keybindings.help_entries() {
+            match rows.last_mut() {
+                Some(row) if row.chars().count() + 3 + entry.chars().count() <= width => {
+                    row.push_str(" | ");
+                    row.push_str(&entry);
+                }
+                _ => rows.push(entry.chars().take(width).collect()),
+            }
+        }
+        rows.truncate(self.height as usize);
+        rows
+    }
+}
+
+/// Returns the length of `data` without the incomplete UTF-8 character at its end,
+/// if it ends with the first bytes of one.
+fn complete_utf8_len(data: &[u8]) -> usize {
+    // Characters are at most 4 bytes, only the last 3 can be incomplete
+    let tail_start = data.len().saturating_sub(3);
+    let Some(lead) = (tail_start..data.len())
+        .rev()
+        .find(|&idx| data[idx] & 0xC0 != 0x80)
+    else {
+        return data.len();
+    };
+    let incomplete = std::str::from_utf8(&data[lead..])
+        .is_err_and(|e| e.valid_up_to() == 0 && e.error_len().is_none());
+    if incomplete { lead } else { data.len() }
+}
+
+/// Renders the whole of `buffer`, as it is drawn after the terminal is cleared.
+#[cfg(test)]
+fn rendered(buffer: &mut ScreenBuffer) -> String {
+    let mut frame = Vec::new();
+    buffer.invalidate();
+    buffer.render_to(&mut frame).unwrap();
+    String::from_utf8(frame).unwrap()
+}
+
+#[test]
+fn flush_partial_csi() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 5);
+    buffer.add_data(b"ab\x1b[12;3");
+    assert_eq!(buffer.line_text(0), "ab");
+
+    buffer.flush();
+    assert_eq!(buffer.escape_state, EscapeState::Normal);
+    assert_eq!(buffer.line_text(0), "ab[12;3");
+
+    // Later data is processed normally
+    buffer.add_data(b"\x1b[2Dc");
+    assert_eq!(buffer.line_text(0), "ab[12c3");
+}
+
+#[test]
+fn csi_split_between_feeds() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 5);
+    buffer.add_data(b"abc\x1b[");
+    buffer.add_data(b"2");
+    buffer.add_data(b"Dx");
+    buffer.flush();
+    assert_eq!(buffer.line_text(0), "axc");
+}
+
+#[test]
+fn no_scrollback_keeps_visible_lines() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 3);
+    buffer.disable_scrollback();
+    buffer.add_data(b"1\r\n2\r\n3\r\n4\r\n5");
+    assert_eq!(buffer.lines.len(), 3);
+    assert_eq!(buffer.line_text(0), "3");
+    assert_eq!(buffer.line_text(2), "5");
+
+    buffer.scroll_up(1);
+    assert_eq!(buffer.view_start, 0);
+    assert!(buffer.notice.is_some());
+}
+
+#[test]
+fn render_mixed_attributes() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.add_data(b"\x1b[1mB\x1b[0mn\x1b[7mR");
+    let out = rendered(&mut buffer);
+
+    // Colors are re-applied after the attributes, before the character
+    let bold = out.find("\x1b[1m").expect("bold is set");
+    let b = out.find('B').unwrap();
+    let n = out.find('n').unwrap();
+    assert!(bold < b, "bold is set before 'B'");
+    assert!(out[b..n].contains("\x1b[0m"), "bold is reset before 'n'");
+
+    // Reverse swaps the colors rather than emitting the attribute
+    assert!(!out.contains("\x1b[7m"));
+
+    // Selecting keeps the cell's attributes
+    buffer.start_selection((0_u16, 0_u16).into());
+    buffer.update_selection((0_u16, 0_u16).into());
+    assert!(rendered(&mut buffer).contains("\x1b[1m"));
+}
+
+#[test]
+fn wrap_marker_reserves_last_column() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(5, 3);
+    buffer.wrap_marker = Some('~');
+    buffer.add_data(b"abcdefg\r\nxy");
+    assert_eq!(buffer.line_text(0), "abcd");
+    assert_eq!(buffer.line_text(1), "efg");
+    assert!(buffer.lines[0].is_wrapped());
+    assert!(!buffer.lines[1].is_wrapped());
+
+    assert_eq!(rendered(&mut buffer).matches('~').count(), 1);
+}
+
+#[test]
+fn toggle_timestamp_gutter() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 2);
+    buffer.add_data(b"hello");
+    buffer.toggle_timestamps();
+
+    let out = rendered(&mut buffer);
+    let stamp = buffer.lines[0]
+        .timestamp()
+        .format(TIMESTAMP_FORMAT)
+        .to_string();
+    assert!(out.contains(&format!("{stamp}\x1b[22m")));
+    // The gutter is only drawn, not stored within the line
+    assert_eq!(buffer.line_text(0), "hello");
+
+    buffer.toggle_timestamps();
+    assert!(!rendered(&mut buffer).contains(&stamp));
+}
+
+#[test]
+fn timestamp_gutter_rewraps_lines() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 4);
+    buffer.add_data(b"abcdefghijkl\r\n");
+    assert_eq!(buffer.line_text(0), "abcdefghijkl");
+
+    // The lines are wrapped to the columns next to the gutter
+    buffer.toggle_timestamps();
+    let wrap_width = buffer.wrap_width() as usize;
+    assert_eq!(wrap_width, 20 - buffer.gutter_width() as usize);
+    assert_eq!(buffer.line_text(0), "abcdefghijkl"[..wrap_width]);
+    assert_eq!(buffer.line_text(1), "abcdefghijkl"[wrap_width..]);
+    assert!(rendered(&mut buffer).contains('l'));
+
+    buffer.toggle_timestamps();
+    assert_eq!(buffer.line_text(0), "abcdefghijkl");
+    assert_eq!(buffer.line_text(1), "");
+}
+
+#[test]
+fn skip_identical_frames() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.add_data(b"abc");
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    assert!(String::from_utf8_lossy(&frame).contains("abc"));
+
+    // Scrolling to the bottom again marks the buffer as needing to render
+    buffer.scroll_to_bottom();
+    let mut unchanged = Vec::new();
+    buffer.render_to(&mut unchanged).unwrap();
+    assert!(!String::from_utf8_lossy(&unchanged).contains("abc"));
+    // Only the cursor was written, and writing it again is skipped
+    buffer.scroll_to_bottom();
+    let mut skipped = Vec::new();
+    buffer.render_to(&mut skipped).unwrap();
+    assert!(skipped.is_empty());
+
+    buffer.add_data(b"d");
+    let mut changed = Vec::new();
+    buffer.render_to(&mut changed).unwrap();
+    assert!(String::from_utf8_lossy(&changed).contains("abcd"));
+
+    // The whole frame is written again once invalidated
+    buffer.invalidate();
+    let mut full = Vec::new();
+    buffer.render_to(&mut full).unwrap();
+    assert!(String::from_utf8_lossy(&full).contains("abcd"));
+    assert!(full.len() > changed.len());
+}
+
+#[test]
+fn backspace_at_start_of_line() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(5, 3);
+    buffer.add_data(b"abcdefg");
+    assert_eq!(buffer.cursor_pos, (2_u16, 1_usize).into());
+
+    buffer.add_data(b"\x08\x08\x08");
+    assert_eq!(buffer.cursor_pos, (4_u16, 0_usize).into());
+    buffer.add_data(b"\x08 \x08");
+    assert_eq!(buffer.line_text(0), "abc e");
+    assert_eq!(buffer.cursor_pos, (3_u16, 0_usize).into());
+
+    // There is no line before the first line
+    buffer.add_data(b"\r\x08");
+    assert_eq!(buffer.cursor_pos, (0_u16, 0_usize).into());
+}
+
+#[test]
+fn set_foreground_recolors_default_cells() {
+    use crossterm::{Command, style};
+
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.add_data(b"abc");
+    buffer.set_foreground(crate::configs::SeriColor::Red);
+    buffer.set_prompt(Some("prompt: ".to_string()));
+
+    let out = rendered(&mut buffer);
+    let mut red = String::new();
+    style::SetForegroundColor(style::Color::Red)
+        .write_ansi(&mut red)
+        .unwrap();
+    let red_at = out.find(&red).expect("the new color is used");
+    assert!(red_at < out.find("abc").unwrap());
+    assert!(out.contains("prompt: "));
+}
+
+#[test]
+fn reset_clears_buffer_and_attributes() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.add_data(b"\x1b[1mbold\r\nnext\x1b[");
+    buffer.reset();
+    assert_eq!(buffer.lines.len(), 1);
+    assert_eq!(buffer.escape_state, EscapeState::Normal);
+
+    buffer.add_data(b"plain");
+    assert_eq!(buffer.line_text(0), "plain");
+    assert!(buffer.lines[0][0].attrs.is_empty());
+}
+
+#[test]
+fn repeat_last_character() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 4);
+    buffer.add_data(b"-\x1b[10b");
+    assert_eq!(buffer.line_text(0), "-".repeat(11));
+    assert_eq!(buffer.cursor_pos, (11, 0u16).into());
+
+    // Wraps onto the next line
+    buffer.add_data(b"\x1b[ba\x1b[12b");
+    assert_eq!(buffer.line_text(0), "------------aaaaaaaa");
+    assert_eq!(buffer.line_text(1), "aaaaa");
+
+    // A count of 0 repeats it once
+    buffer.add_data(b"\x1b[0b");
+    assert_eq!(buffer.line_text(1), "aaaaaa");
+}
+
+#[test]
+fn clear_screen_keeps_history() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 3);
+    buffer.add_data(b"one\r\ntwo\r\nthree");
+    buffer.clear_screen_home();
+    assert_eq!(buffer.lines.len(), 6);
+    assert_eq!(buffer.view_start, 3);
+    buffer.add_data(b"four");
+    assert_eq!(buffer.line_text(3), "four");
+    assert_eq!(buffer.line_text(1), "two");
+}
+
+#[test]
+fn draw_sparkline_in_corner() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 3);
+    buffer.set_sparkline(Some("▁█ 2 B/s".to_string()));
+    let frame = rendered(&mut buffer);
+    assert!(frame.contains("▁█ 2 B/s"));
+
+    // Hidden by notices
+    buffer.set_notice("notice");
+    assert!(!rendered(&mut buffer).contains("▁█"));
+}
+
+#[test]
+fn show_control_chars_in_caret_notation() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 4);
+    buffer.toggle_control_chars();
+    buffer.add_data(b"a\x03\x1b[1mb\x7f\r\nc");
+    assert_eq!(buffer.line_text(0), "a^C^[[1mb^?^M^J");
+    assert_eq!(buffer.line_text(1), "c");
+
+    buffer.toggle_control_chars();
+    buffer.add_data(b"\x1b[1md\r\ne");
+    assert_eq!(buffer.line_text(1), "cd");
+    assert_eq!(buffer.line_text(2), "e");
+}
+
+#[test]
+fn page_up_and_down() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 4);
+    for i in 0..10 {
+        buffer.add_data(format!("{i}\r\n").as_bytes());
+    }
+    assert_eq!(buffer.view_start, 7);
+    buffer.page_up();
+    assert_eq!(buffer.view_start, 4);
+    buffer.page_up();
+    buffer.page_up();
+    assert_eq!(buffer.view_start, 0);
+    buffer.page_down();
+    assert_eq!(buffer.view_start, 3);
+}
+
+#[test]
+fn display_filter_hides_lines() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 3);
+    buffer.add_data(b"up\r\nerr 1\r\nup\r\nup\r\nerr 2\r\nup");
+    buffer.set_display_filter(crate::configs::LineFilter {
+        include: Some(crate::configs::Pattern(regex::Regex::new("err").unwrap())),
+        exclude: None,
+    });
+    assert_eq!(buffer.displayed_lines(), [1, 4]);
+    rendered(&mut buffer);
+
+    buffer.start_selection((0_u16, 0_u16).into());
+    buffer.update_selection((4_u16, 1_u16).into());
+    let selected = buffer.get_selected_text();
+    let selected: Vec<&str> = selected.lines().map(str::trim_end).collect();
+    assert_eq!(selected, ["err 1", "err 2"]);
+
+    // The lines are only matched again once they are written to
+    assert_eq!(buffer.lines[5].filter_match(), Some(false));
+    buffer.add_data(b" err 3");
+    assert_eq!(buffer.lines[5].filter_match(), None);
+    buffer.cache_filter_matches();
+    assert_eq!(buffer.lines[5].filter_match(), Some(true));
+    assert_eq!(buffer.displayed_lines(), [1, 4, 5]);
+
+    // Every line is still in the buffer
+    buffer.set_display_filter(crate::configs::LineFilter::default());
+    assert_eq!(buffer.displayed_lines(), [3, 4, 5]);
+    assert_eq!(buffer.line_text(2), "up");
+}
+
+#[test]
+fn draw_ruler_column() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.ruler_column = Some(4);
+    buffer.add_data(b"ab\r\nabcdef");
+    let frame = rendered(&mut buffer);
+    assert!(frame.contains(&format!("\x1b[1;4H\x1b[38;5;10m\x1b[49m\x1b[2m{RULER}")));
+    assert!(frame.contains("\x1b[2;4H\x1b[38;5;10m\x1b[49m\x1b[2md"));
+}
+
+#[test]
+fn resize_rewraps_lines() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(4, 3);
+    buffer.add_data(b"abcdefghij\r\nxy");
+    assert_eq!(buffer.line_text(2), "ij");
+
+    buffer.resize(8, 3);
+    assert_eq!(buffer.line_text(0), "abcdefgh");
+    assert_eq!(buffer.line_text(1), "ij");
+    assert_eq!(buffer.line_text(2), "xy");
+    assert_eq!((buffer.cursor_pos.x, buffer.cursor_pos.y), (2, 2));
+
+    buffer.resize(3, 2);
+    let lines: Vec<String> = (0..6).map(|idx| buffer.line_text(idx)).collect();
+    assert_eq!(lines, ["abc", "def", "ghi", "j", "xy", ""]);
+    assert_eq!((buffer.cursor_pos.x, buffer.cursor_pos.y), (2, 4));
+    assert_eq!(buffer.view_start, 3);
+    buffer.add_data(b"z");
+    assert_eq!(buffer.line_text(4), "xyz");
+}
+
+#[test]
+fn tabs_move_to_tab_stops() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 2);
+    buffer.add_data(b"a\tb\tc\td");
+    assert_eq!(buffer.line_text(0), "a       b       c  d");
+}
+
+#[test]
+fn no_line_wrap() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(5, 3);
+    buffer.wrap_lines = false;
+    buffer.add_data(b"hello world\r\nok");
+    assert_eq!(buffer.line_text(0), "helld");
+    assert!(!buffer.lines[0].is_wrapped());
+    assert_eq!(buffer.line_text(1), "ok");
+}
+
+#[test]
+fn tab_and_form_feed() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 3);
+    buffer.add_data(b"a\tb\n");
+    assert_eq!(buffer.line_text(0), "a       b");
+    buffer.add_data(b"page 1\r\n\x0Cpage 2");
+    assert_eq!(buffer.view_start, 3);
+    assert_eq!(buffer.cursor_pos.y, 3);
+    assert_eq!(buffer.line_text(3), "page 2");
+    assert_eq!(buffer.line_text(1), "page 1");
+}
+
+#[test]
+fn tiny_screen_buffer() {
+    super::init_test_config();
+    for (width, height) in [(0, 0), (1, 1), (0, 5), (5, 0)] {
+        let mut buffer = ScreenBuffer::new(width, height);
+        buffer.toggle_timestamps();
+        buffer.add_data(b"hello\tworld\r\n\x1b[2J\x1b[3;4Hab\x08\x08\x1b[5b\x1b[1J\r\nend");
+        buffer.set_sparkline(Some("graph".to_string()));
+        rendered(&mut buffer);
+        buffer.scroll_up(5);
+        buffer.clear_screen_home();
+        rendered(&mut buffer);
     }
 }
+
+#[test]
+fn unbounded_scrollback() {
+    super::init_test_config();
+    let config: crate::configs::Config = toml::from_str("[defaults]\nscrollback = 0").unwrap();
+    let mut buffer = ScreenBuffer::new(10, 4).with_scrollback(config.defaults.scrollback);
+    assert_eq!(buffer.max_scrollback, usize::MAX);
+    for i in 0..50 {
+        buffer.add_data(format!("{i}\r\n").as_bytes());
+    }
+    assert_eq!(buffer.lines.len(), 51);
+    assert_eq!(buffer.line_text(0), "0");
+    buffer.scroll_up(usize::MAX);
+    assert_eq!(buffer.view_start, 0);
+    buffer.scroll_down(usize::MAX);
+    assert_eq!(buffer.view_start, 47);
+}
+
+#[test]
+fn status_line_reserves_last_row() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 3);
+    buffer.add_data(b"one\r\ntwo\r\nthree");
+    buffer.set_status("Connected | COM4".to_string());
+    assert_eq!(buffer.height, 2);
+    assert_eq!(buffer.view_start, 1);
+    let frame = rendered(&mut buffer);
+    assert!(frame.contains("Connected | COM4"));
+    assert!(!frame.contains("one"));
+
+    // Resizes are for the whole terminal, including the status line
+    buffer.resize(20, 4);
+    assert_eq!(buffer.height, 3);
+    buffer.set_status("Disconnected | COM4".to_string());
+    assert_eq!(buffer.height, 3);
+}
+
+#[test]
+fn bell_modes() {
+    use crate::configs::BellMode;
+
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 3);
+    buffer.add_data(b"done\x07");
+    assert_eq!(buffer.line_text(0), "done");
+    assert!(!buffer.pending_bell && buffer.flash_deadline().is_none());
+
+    buffer.bell = BellMode::Audible;
+    buffer.add_data(b"\x07");
+    let mut out = Vec::new();
+    buffer.render_to(&mut out).unwrap();
+    assert!(out.ends_with(b"\x07"));
+    assert!(!buffer.pending_bell);
+
+    buffer.bell = BellMode::Visual;
+    buffer.add_data(b"\x07");
+    assert!(buffer.flash_deadline().is_some());
+    let inverted = rendered(&mut buffer);
+    buffer.end_flash();
+    assert!(buffer.flash_deadline().is_none());
+    assert_ne!(inverted, rendered(&mut buffer));
+}
+
+#[test]
+fn help_overlay() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(40, 5);
+    buffer.add_data(b"one\r\ntwo\r\nthree");
+    let text = |buffer: &ScreenBuffer| -> Vec<String> {
+        (0..buffer.lines.len())
+            .map(|idx| buffer.line_text(idx))
+            .collect()
+    };
+    let lines = text(&buffer);
+    buffer.toggle_help();
+    let rows = buffer.help_rows();
+    assert!(rows.len() > 1);
+    assert!(rows.iter().all(|row| row.chars().count() <= 40));
+    assert!(rows[0].starts_with("Ctrl+q quit | Ctrl+l clear"));
+
+    let frame = rendered(&mut buffer);
+    assert!(frame.contains("Ctrl+q quit"));
+    assert_eq!(text(&buffer), lines);
+
+    buffer.toggle_help();
+    assert!(!rendered(&mut buffer).contains("Ctrl+q quit"));
+}
+
+#[test]
+fn utf8_split_between_chunks() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 3);
+    let text = "┌─ café ─┐".as_bytes();
+    // Splits within the 3 byte box-drawing character and the 2 byte `é`
+    buffer.add_data(&text[..1]);
+    buffer.add_data(&text[1..9]);
+    buffer.add_data(&text[9..]);
+    assert_eq!(buffer.line_text(0), "┌─ café ─┐");
+
+    // Invalid bytes are replaced rather than dropped
+    buffer.add_data(b"\r\na\xFFb\xE2\x94");
+    assert_eq!(buffer.line_text(1), "a\u{FFFD}b");
+    buffer.flush();
+    assert_eq!(buffer.line_text(1), "a\u{FFFD}b\u{FFFD}");
+
+    assert_eq!(complete_utf8_len(b"ab\xF0\x9F\x98"), 2);
+    assert_eq!(complete_utf8_len(b"ab\xF0\x9F\x98\x80"), 6);
+    assert_eq!(complete_utf8_len(b"ab\xE0\x80"), 4);
+}
+
+#[test]
+fn redraw_changed_rows() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 3);
+    buffer.add_data(b"one\r\ntwo\r\nthree");
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    let frame = String::from_utf8_lossy(&frame).to_string();
+    assert!(frame.contains("one") && frame.contains("two") && frame.contains("three"));
+
+    // Only the row being typed on is redrawn
+    buffer.add_data(b"!");
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    let frame = String::from_utf8_lossy(&frame).to_string();
+    assert!(frame.contains("three!"));
+    assert!(!frame.contains("one") && !frame.contains("two"));
+
+    // Removing an overlay redraws the row underneath it
+    buffer.set_notice("Saved");
+    buffer.render_to(&mut Vec::new()).unwrap();
+    buffer.add_data(b"?");
+    assert!(buffer.notice.is_none());
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    let frame = String::from_utf8_lossy(&frame).to_string();
+    assert!(frame.contains("three!?") && !frame.contains("two"));
+
+    // Everything is redrawn after a resize
+    buffer.resize(12, 3);
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    assert!(String::from_utf8_lossy(&frame).contains("two"));
+}
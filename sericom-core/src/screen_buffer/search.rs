@@ -0,0 +1,197 @@
+use super::{Line, ScreenBuffer, UIAction};
+
+/// The direction a search looks in from the current match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    /// Towards the older lines, the top of the history.
+    Older,
+    /// Towards the newer lines, the bottom of the history.
+    Newer,
+}
+
+/// The text being searched for, see [`UICommand::Search`][super::UICommand::Search].
+#[derive(Clone, Debug)]
+pub(super) struct Search {
+    query: Vec<char>,
+    ignore_case: bool,
+    /// The line and column of the match that is highlighted.
+    current: Option<(usize, usize)>,
+}
+
+impl ScreenBuffer {
+    /// Searches the history for `query`, starting from the bottom of the screen
+    /// towards the older lines, and highlights the first match.
+    ///
+    /// Matching is case-insensitive and wraps around the history unless disabled with
+    /// [`Defaults::search_ignore_case`][crate::configs::Defaults::search_ignore_case]
+    /// and [`Defaults::search_wrap`][crate::configs::Defaults::search_wrap].
+    pub(crate) fn search(&mut self, query: String) {
+        if query.is_empty() {
+            self.search = None;
+            self.clear_selection();
+            return;
+        }
+        let ignore_case = crate::configs::get_config().defaults.search_ignore_case;
+        self.search = Some(Search {
+            query: query.chars().map(|c| fold_case(c, ignore_case)).collect(),
+            ignore_case,
+            current: None,
+        });
+        self.find(Direction::Older);
+    }
+
+    /// Highlights the next match, towards the older lines.
+    pub(crate) fn search_next(&mut self) {
+        self.find(Direction::Older);
+    }
+
+    /// Highlights the previous match, towards the newer lines.
+    pub(crate) fn search_prev(&mut self) {
+        self.find(Direction::Newer);
+    }
+
+    fn find(&mut self, direction: Direction) {
+        let Some(search) = self.search.clone() else {
+            self.set_notice("No search, start one with Alt + /");
+            return;
+        };
+        let defaults = &crate::configs::get_config().defaults;
+        let last = self.lines.len().saturating_sub(1);
+        // Starts after the current match, or at the bottom of the screen
+        let (start_line, start_x) = match search.current {
+            Some((line, x)) if line <= last => (line, Some(x)),
+            _ => match direction {
+                Direction::Older => (
+                    (self.view_start + self.height as usize).min(self.lines.len()),
+                    None,
+                ),
+                Direction::Newer => (self.view_start, None),
+            },
+        };
+
+        let found = match self.find_from(&search, start_line, start_x, direction) {
+            None if defaults.search_wrap => {
+                let wrapped_start = match direction {
+                    Direction::Older => self.lines.len(),
+                    Direction::Newer => 0,
+                };
+                self.find_from(&search, wrapped_start, None, direction)
+            }
+            found => found,
+        };
+        let Some((line_idx, x)) = found else {
+            self.set_notice("No matches");
+            return;
+        };
+
+        let len = search.query.len() as u16;
+        if let Some(search) = self.search.as_mut() {
+            search.current = Some((line_idx, x));
+        }
+        self.clear_selection();
+        let height = self.height as usize;
+        if line_idx < self.view_start || line_idx >= self.view_start + height {
+            let max_view_start = self.lines.len().saturating_sub(height);
+            self.view_start = line_idx.saturating_sub(height / 2).min(max_view_start);
+            self.follow = self.at_bottom();
+        }
+        self.selection_start = Some((x as u16, line_idx));
+        self.selection_end = Some((x as u16 + len - 1, line_idx));
+        self.update_selection_highlighting();
+        self.ui_state.set_selection_active(true);
+        self.needs_render = true;
+    }
+
+    /// Returns the first match in `direction` from the match at `start_x` within
+    /// the line `start_line`. Without a `start_x`, the search starts above
+    /// `start_line` towards the older lines, or at it towards the newer lines.
+    fn find_from(
+        &self,
+        search: &Search,
+        start_line: usize,
+        start_x: Option<usize>,
+        direction: Direction,
+    ) -> Option<(usize, usize)> {
+        let matches_in = |line_idx: usize| -> Vec<usize> {
+            self.lines
+                .get(line_idx)
+                .filter(|line| self.is_displayed(line))
+                .map(|line| search.matches(line))
+                .unwrap_or_default()
+        };
+        match direction {
+            Direction::Older => {
+                if let Some(start_x) = start_x
+                    && let Some(&x) = matches_in(start_line).iter().rev().find(|&&x| x < start_x)
+                {
+                    return Some((start_line, x));
+                }
+                (0..start_line)
+                    .rev()
+                    .find_map(|idx| matches_in(idx).last().map(|&x| (idx, x)))
+            }
+            Direction::Newer => {
+                if let Some(start_x) = start_x
+                    && let Some(&x) = matches_in(start_line).iter().find(|&&x| x > start_x)
+                {
+                    return Some((start_line, x));
+                }
+                let begin = if start_x.is_some() {
+                    start_line + 1
+                } else {
+                    start_line
+                };
+                (begin..self.lines.len()).find_map(|idx| matches_in(idx).first().map(|&x| (idx, x)))
+            }
+        }
+    }
+}
+
+impl Search {
+    /// Returns the columns where the query starts within `line`.
+    fn matches(&self, line: &Line) -> Vec<usize> {
+        let text: Vec<char> = line
+            .into_iter()
+            .map(|cell| fold_case(cell.character, self.ignore_case))
+            .collect();
+        if self.query.is_empty() || self.query.len() > text.len() {
+            return Vec::new();
+        }
+        text.windows(self.query.len())
+            .enumerate()
+            .filter(|(_, window)| *window == self.query)
+            .map(|(x, _)| x)
+            .collect()
+    }
+}
+
+fn fold_case(c: char, ignore_case: bool) -> char {
+    match ignore_case {
+        true => c.to_lowercase().next().unwrap_or(c),
+        false => c,
+    }
+}
+
+#[test]
+fn search_history() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 3);
+    buffer.add_data(b"Gi0/1 up\r\nGi0/2 down\r\nlink UP\r\nfoo\r\nbar\r\nbaz");
+    buffer.search("up".to_string());
+    assert_eq!(buffer.search.as_ref().unwrap().current, Some((2, 5)));
+    assert_eq!(buffer.get_selected_text(), "UP");
+    assert!(buffer.ui_state.selection_active());
+
+    buffer.search_next();
+    assert_eq!(buffer.search.as_ref().unwrap().current, Some((0, 6)));
+    assert_eq!(buffer.view_start, 0);
+    // Wraps around to the newest match
+    buffer.search_next();
+    assert_eq!(buffer.search.as_ref().unwrap().current, Some((2, 5)));
+    buffer.search_prev();
+    assert_eq!(buffer.search.as_ref().unwrap().current, Some((0, 6)));
+
+    buffer.search("missing".to_string());
+    assert!(buffer.search.as_ref().unwrap().current.is_none());
+    assert_eq!(buffer.notice.as_deref(), Some("No matches"));
+}
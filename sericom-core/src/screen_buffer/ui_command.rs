@@ -1,7 +1,17 @@
 use crate::screen_buffer::Position;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::{Cursor, Line, ScreenBuffer};
 
+const SCROLLBACK_DISABLED: &str = "Scrollback is disabled";
+/// Set to `1` to always copy with [`osc52_sequence`] instead of crossterm's clipboard.
+const OSC52_ENV: &str = "SERICOM_OSC52";
+
+/// The format of the timestamps shown in the gutter, see [`UICommand::ToggleTimestamps`].
+pub(super) const TIMESTAMP_FORMAT: &str = "%H:%M:%S ";
+/// The width of the timestamp gutter, the length of [`TIMESTAMP_FORMAT`] once formatted.
+pub(super) const TIMESTAMP_WIDTH: u16 = 9;
+
 /// `UICommand` is used for communication between stdin and the [`ScreenBuffer`].
 #[non_exhaustive]
 #[derive(Clone, Debug)]
@@ -10,6 +20,10 @@ pub enum UICommand {
     ScrollUp(usize),
     /// Scrolls down by `usize` lines
     ScrollDown(usize),
+    /// Scrolls up by the height of the screen
+    PageUp,
+    /// Scrolls down by the height of the screen
+    PageDown,
     /// Scrolls to the last line (most recent)
     ScrollBottom,
     /// Scrolls to the beginning of the scrollback buffer (oldest line)
@@ -22,9 +36,76 @@ pub enum UICommand {
     CopySelection,
     /// Completely clears the lines in the scrollback buffer
     ClearBuffer,
+    /// Clears the screen, keeping the lines that were on it in the scrollback buffer
+    ClearScreen,
+    /// Cancels the current text-selection without copying it
+    CancelSelection,
+    /// Shows/hides the time that each line was received in a gutter on the left
+    ToggleTimestamps,
+    /// Shows/hides a graph of the bytes received per second in the bottom-right
+    /// corner of the screen
+    ToggleThroughput,
+    /// Shows/hides the control characters received from the device in caret
+    /// notation (i.e. `^C`) instead of acting on them
+    ToggleControlChars,
+    /// Shows a line of user input, i.e. a prompt and the text typed so far,
+    /// over the last line of the screen until [`UICommand::HidePrompt`]
+    ShowPrompt(String),
+    /// Hides the line shown with [`UICommand::ShowPrompt`]
+    HidePrompt,
+    /// Sets the foreground color of the text for the rest of the session
+    SetForeground(crate::configs::SeriColor),
+    /// Clears the lines in the scrollback buffer and resets the state set by
+    /// escape sequences, i.e. text attributes
+    Reset,
+    /// Shows a message on the last line of the screen until more data is received
+    ShowNotice(String),
+    /// Only displays the lines matching the filter, an empty filter displays all lines
+    SetDisplayFilter(crate::configs::LineFilter),
+    /// The terminal was resized to `(width, height)`
+    Resize(u16, u16),
+    /// Searches the history for the text, from the bottom of the screen towards
+    /// the older lines, and highlights the match. An empty text ends the search
+    Search(String),
+    /// Highlights the next match of [`UICommand::Search`], towards the older lines
+    SearchNext,
+    /// Highlights the previous match of [`UICommand::Search`], towards the newer lines
+    SearchPrev,
+    /// Shows/hides the list of keys over the bottom of the screen
+    ToggleHelp,
+    /// Switches between showing the lines and a hexdump of the bytes received,
+    /// with their offset, 16 bytes per row and their ASCII characters
+    ToggleHexView,
+    /// Writes every line in the scrollback buffer to the file as plain text
+    DumpScrollback(std::path::PathBuf),
+}
+
+/// State of the UI that is shared between the [`ScreenBuffer`] and the task
+/// processing user input.
+///
+/// Allows the input task to decide whether a key should act on the UI or be
+/// sent to the device, i.e. <kbd>Esc</kbd> cancels a selection while one is
+/// active and is otherwise sent to the device.
+#[derive(Debug, Default)]
+pub struct UIState {
+    selection_active: AtomicBool,
+}
+
+impl UIState {
+    /// Returns whether text is currently being selected.
+    pub fn selection_active(&self) -> bool {
+        self.selection_active.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_selection_active(&self, active: bool) {
+        self.selection_active.store(active, Ordering::Relaxed);
+    }
 }
 
-pub(crate) trait UIAction {
+/// The actions on the [`ScreenBuffer`] that the [`UICommand`]s trigger, i.e. scrolling
+/// and selecting text. Also usable to drive a `ScreenBuffer` directly, see
+/// [`ScreenBuffer::render_to()`].
+pub trait UIAction {
     fn scroll_up(&mut self, lines: usize);
     fn scroll_down(&mut self, lines: usize);
     fn scroll_to_bottom(&mut self);
@@ -35,25 +116,47 @@ pub(crate) trait UIAction {
     fn copy_to_clipboard(&mut self) -> std::io::Result<()>;
     fn clear_buffer(&mut self);
     fn clear_screen(&mut self);
+    fn clear_screen_home(&mut self);
+    fn toggle_timestamps(&mut self);
+    fn toggle_control_chars(&mut self);
+    fn set_prompt(&mut self, prompt: Option<String>);
+    fn set_foreground(&mut self, color: crate::configs::SeriColor);
+    fn set_display_filter(&mut self, filter: crate::configs::LineFilter);
+    fn reset(&mut self);
 }
 
 impl UIAction for ScreenBuffer {
     /// Called to scroll the terminal up by `lines`.
     fn scroll_up(&mut self, lines: usize) {
+        if !self.scrollback_enabled {
+            self.set_notice(SCROLLBACK_DISABLED);
+            return;
+        }
         if self.view_start >= lines {
             self.view_start -= lines;
         } else {
             self.view_start = 0;
         }
-        self.clear_selection();
+        self.follow = self.at_bottom();
+        if !self.dragging {
+            self.clear_selection();
+        }
         self.needs_render = true;
     }
 
     /// Called to scroll the terminal down by `lines`.
     fn scroll_down(&mut self, lines: usize) {
+        if !self.scrollback_enabled {
+            self.set_notice(SCROLLBACK_DISABLED);
+            return;
+        }
         let max_view_start = self.lines.len().saturating_sub(self.height as usize);
-        self.view_start = (self.view_start + lines).min(max_view_start);
-        self.clear_selection();
+        self.view_start = self.view_start.saturating_add(lines).min(max_view_start);
+        // Scrolling back down to the bottom follows the new data again
+        self.follow = self.at_bottom();
+        if !self.dragging {
+            self.clear_selection();
+        }
         self.needs_render = true;
     }
 
@@ -61,30 +164,46 @@ impl UIAction for ScreenBuffer {
     /// the same as the most recent lines received from the serial connection
     fn scroll_to_bottom(&mut self) {
         self.view_start = self.lines.len().saturating_sub(self.height as usize);
+        self.follow = true;
         self.needs_render = true;
     }
 
     /// Scrolls to the top of the serial connection's history.
     fn scroll_to_top(&mut self) {
+        if !self.scrollback_enabled {
+            self.set_notice(SCROLLBACK_DISABLED);
+            return;
+        }
         self.view_start = 0;
+        self.follow = self.at_bottom();
         self.needs_render = true;
     }
 
     /// Sets the position within the screen for the start of a selection.
     /// Where `screen_x` is the x-position of the start of the selection,
     /// and `screen_y` is the y-position (line) of the start of the selection.
+    ///
+    /// The start is kept as the absolute line within the buffer, so it stays
+    /// anchored to its text when scrolling while dragging the selection.
     fn start_selection(&mut self, pos: Position) {
-        let absolute_line = self.view_start + pos.y;
+        let absolute_line = self.selection_line_idx(pos.y);
         self.clear_selection();
-        self.selection_start = Some((pos.x, absolute_line));
+        self.selection_start = Some((pos.x.saturating_sub(self.gutter_width()), absolute_line));
+        self.dragging = true;
         self.needs_render = true;
     }
 
     /// Update's a selection to include the position passed to it.
     /// Where `screen_x` is the x-position and `screen_y` is the y-position (line).
+    ///
+    /// The selection only counts as active, i.e. for Esc to clear it, once it
+    /// extends past the cell it was started on.
     fn update_selection(&mut self, pos: Position) {
-        let absolute_line = self.view_start + pos.y;
-        self.selection_end = Some((pos.x, absolute_line));
+        let absolute_line = self.selection_line_idx(pos.y);
+        self.selection_end = Some((pos.x.saturating_sub(self.gutter_width()), absolute_line));
+        if self.selection_end != self.selection_start {
+            self.ui_state.set_selection_active(true);
+        }
         self.update_selection_highlighting();
         self.needs_render = true;
     }
@@ -96,19 +215,33 @@ impl UIAction for ScreenBuffer {
         }
         self.selection_start = None;
         self.selection_end = None;
+        self.dragging = false;
+        self.ui_state.set_selection_active(false);
         self.needs_render = true;
     }
 
     /// Copy's the currently selected text to the user's clipboard.
+    ///
+    /// Falls back to writing an OSC 52 sequence to stdout when crossterm's
+    /// clipboard fails (i.e. the Windows console API) or `SERICOM_OSC52=1` is set.
     fn copy_to_clipboard(&mut self) -> std::io::Result<()> {
         use crossterm::{clipboard, execute};
+        use std::io::Write;
 
         let selected_text = self.get_selected_text();
         if !selected_text.is_empty() {
-            execute!(
-                std::io::stdout(),
-                clipboard::CopyToClipboard::to_clipboard_from(selected_text)
-            )?;
+            let forced = std::env::var_os(OSC52_ENV).is_some_and(|var| var == "1");
+            let copied = !forced
+                && execute!(
+                    std::io::stdout(),
+                    clipboard::CopyToClipboard::to_clipboard_from(&selected_text)
+                )
+                .is_ok();
+            if !copied {
+                let mut stdout = std::io::stdout();
+                stdout.write_all(osc52_sequence(&selected_text).as_bytes())?;
+                stdout.flush()?;
+            }
         }
         self.clear_selection();
         Ok(())
@@ -119,7 +252,9 @@ impl UIAction for ScreenBuffer {
     /// except this will reset the connection's message history (on the user's side).
     fn clear_buffer(&mut self) {
         self.lines.clear();
+        self.hex_dump.clear();
         self.view_start = 0;
+        self.follow = true;
         self.set_cursor_pos((0_u16, 0_usize));
         self.lines.push_back(Line::new(self.width as usize));
         self.needs_render = true;
@@ -131,12 +266,124 @@ impl UIAction for ScreenBuffer {
             self.lines.push_back(Line::new(self.width as usize));
         }
         self.view_start = self.lines.len().saturating_sub(self.height as usize);
+        self.follow = true;
         self.needs_render = true;
     }
+
+    /// Clears the visible screen like [`UIAction::clear_screen`] and moves the
+    /// cursor to the top-left of the cleared screen, like [`UIAction::clear_buffer`].
+    fn clear_screen_home(&mut self) {
+        self.clear_selection();
+        self.clear_screen();
+        self.set_cursor_pos((0_u16, self.view_start));
+    }
+
+    /// Shows/hides the timestamp gutter, re-wrapping the lines to the columns
+    /// that are left for them.
+    fn toggle_timestamps(&mut self) {
+        let old_wrap_width = self.wrap_width() as usize;
+        self.show_timestamps = !self.show_timestamps;
+        self.clear_selection();
+        self.reflow(old_wrap_width);
+        self.needs_render = true;
+    }
+
+    /// Switches between showing control characters in caret notation and acting on them.
+    fn toggle_control_chars(&mut self) {
+        self.show_control_chars = !self.show_control_chars;
+        self.escape_sequence.reset();
+        self.escape_state = super::EscapeState::Normal;
+        let notice = if self.show_control_chars {
+            "Showing control characters"
+        } else {
+            "Hiding control characters"
+        };
+        self.set_notice(notice);
+    }
+
+    /// Shows/hides a line of user input over the last line of the screen.
+    fn set_prompt(&mut self, prompt: Option<String>) {
+        self.prompt = prompt;
+        self.needs_render = true;
+    }
+
+    /// Sets the color used for text that doesn't have a color of its own.
+    fn set_foreground(&mut self, color: crate::configs::SeriColor) {
+        self.current_appearance.fg = (&color).into();
+        self.needs_render = true;
+    }
+
+    /// Sets the filter for the lines that are displayed, keeping every line in
+    /// the buffer. An empty filter displays every line.
+    fn set_display_filter(&mut self, filter: crate::configs::LineFilter) {
+        self.clear_selection();
+        let notice = if filter.is_empty() {
+            "Showing all lines"
+        } else {
+            "Filtering the displayed lines"
+        };
+        self.display_filter = (!filter.is_empty()).then_some(filter);
+        for line in &mut self.lines {
+            line.set_filter_match(None);
+        }
+        self.cache_filter_matches();
+        self.set_notice(notice);
+    }
+
+    /// Clears the buffer like [`UIAction::clear_buffer`] and resets the state
+    /// that was set by escape sequences.
+    fn reset(&mut self) {
+        self.clear_buffer();
+        self.scroll_region = None;
+        self.saved_cursor = None;
+        self.cursor_visible = true;
+        self.display_attributes = crossterm::style::Attributes::none();
+        self.display_colors = (None, None);
+        self.escape_sequence.reset();
+        self.escape_state = super::EscapeState::Normal;
+        self.utf8_pending.clear();
+        self.last_char = None;
+    }
 }
 
 impl ScreenBuffer {
-    fn update_selection_highlighting(&mut self) {
+    /// Scrolls up by the height of the screen, keeping the top line visible.
+    pub(crate) fn page_up(&mut self) {
+        self.scroll_up((self.height as usize).saturating_sub(1).max(1));
+    }
+
+    /// Scrolls down by the height of the screen, keeping the bottom line visible.
+    pub(crate) fn page_down(&mut self) {
+        self.scroll_down((self.height as usize).saturating_sub(1).max(1));
+    }
+
+    /// Shows/hides the list of the keys from [`Keybindings::help_entries()`][crate::configs::Keybindings::help_entries],
+    /// which is drawn over the lines without changing them.
+    pub(crate) fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+        self.needs_render = true;
+    }
+
+    /// Returns the index of the line on row `screen_y`, clamped to the rows of
+    /// the screen, i.e. when dragging onto the status line.
+    fn selection_line_idx(&self, screen_y: usize) -> usize {
+        let screen_y = screen_y.min(self.height.saturating_sub(1) as usize);
+        self.screen_line_idx(screen_y)
+            .min(self.lines.len().saturating_sub(1))
+    }
+
+    /// Returns the width of the timestamp gutter, `0` when it is hidden.
+    pub(super) fn gutter_width(&self) -> u16 {
+        if self.show_timestamps {
+            TIMESTAMP_WIDTH.min(self.width.saturating_sub(1))
+        } else {
+            0
+        }
+    }
+}
+
+impl ScreenBuffer {
+    pub(super) fn update_selection_highlighting(&mut self) {
         for line in &mut self.lines {
             line.clear_selection();
         }
@@ -171,7 +418,8 @@ impl ScreenBuffer {
         }
     }
 
-    fn get_selected_text(&self) -> String {
+    /// Returns the text that is selected, an empty string when nothing is selected.
+    pub fn get_selected_text(&self) -> String {
         if let (Some((start_x, start_line)), Some((end_x, end_line))) =
             (self.selection_start, self.selection_end)
         {
@@ -183,9 +431,13 @@ impl ScreenBuffer {
                 };
 
             let mut result = String::new();
+            // The lines may have been dropped from the history since being selected
+            let end_line = end_line.min(self.lines.len().saturating_sub(1));
 
             for line_idx in start_line..=end_line {
-                if let Some(line) = self.lines.get(line_idx) {
+                if let Some(line) = self.lines.get(line_idx)
+                    && self.is_displayed(line)
+                {
                     let line_start_x = if line_idx == start_line { start_x } else { 0 };
                     let line_end_x = if line_idx == end_line {
                         end_x
@@ -210,4 +462,170 @@ impl ScreenBuffer {
             String::new()
         }
     }
+
+    /// Returns every line in the scrollback buffer as plain text, with the
+    /// trailing blanks of each line and the blank lines at the end trimmed.
+    pub(crate) fn history_text(&self) -> String {
+        let mut result = String::new();
+        for line in &self.lines {
+            let text: String = line.into_iter().map(|cell| cell.character).collect();
+            result.push_str(text.trim_end());
+            result.push('\n');
+        }
+        let len = result.trim_end().len();
+        result.truncate(len);
+        if !result.is_empty() {
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Writes [`ScreenBuffer::history_text()`] to `path`, see [`UICommand::DumpScrollback`],
+    /// and shows whether it succeeded as a notice.
+    pub(crate) fn dump_scrollback(&mut self, path: &std::path::Path) {
+        let text = self.history_text();
+        let notice = match std::fs::write(path, &text) {
+            Ok(()) => format!(
+                "Saved {} lines to '{}'",
+                text.lines().count(),
+                path.display()
+            ),
+            Err(e) => format!("Failed to save to '{}': {e}", path.display()),
+        };
+        self.set_notice(notice);
+    }
+}
+
+/// Describes how [`UICommand::CopySelection`] would copy text from this
+/// terminal, i.e. for `sericom doctor`. Both ways write an OSC 52 sequence, the
+/// terminal (or multiplexer) has to support it for the text to be copied.
+pub fn clipboard_status() -> String {
+    use std::io::IsTerminal;
+
+    if !std::io::stdout().is_terminal() {
+        return "unavailable, stdout isn't a terminal".to_string();
+    }
+    // crossterm's clipboard fails on consoles that only have the Windows API
+    #[cfg(windows)]
+    let legacy_console = !crossterm::ansi_support::supports_ansi();
+    #[cfg(not(windows))]
+    let legacy_console = false;
+    let mut status = if std::env::var_os(OSC52_ENV).is_some_and(|var| var == "1") {
+        format!("OSC 52 written directly ({OSC52_ENV}=1)")
+    } else if legacy_console {
+        "OSC 52 written directly, the console doesn't support crossterm's clipboard".to_string()
+    } else {
+        "OSC 52 through crossterm".to_string()
+    };
+    if std::env::var_os("TMUX").is_some() {
+        status.push_str(", inside tmux (requires `set -g set-clipboard on`)");
+    } else if std::env::var("TERM").is_ok_and(|term| term.starts_with("screen")) {
+        status.push_str(", inside screen (which doesn't pass OSC 52 on)");
+    }
+    status
+}
+
+/// Returns the OSC 52 escape sequence that sets the terminal's clipboard to `text`,
+/// which terminals and multiplexers (i.e. tmux) pass through SSH sessions.
+fn osc52_sequence(text: &str) -> String {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+#[test]
+fn encode_osc52() {
+    assert_eq!(osc52_sequence("show run"), "\x1b]52;c;c2hvdyBydW4=\x07");
+}
+
+#[test]
+fn scroll_during_selection() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 3);
+    buffer.add_data(b"one\r\ntwo\r\nthree\r\nfour\r\nfive");
+    assert_eq!(buffer.view_start, 2);
+
+    // Drag from the end of "three" on the top row, scrolling up to "two" mid-drag
+    buffer.start_selection((4_u16, 0_u16).into());
+    buffer.scroll_up(1);
+    buffer.update_selection((0_u16, 0_u16).into());
+    assert!(buffer.ui_state.selection_active());
+    assert_eq!(buffer.selection_start, Some((4, 2)));
+    assert_eq!(buffer.selection_end, Some((0, 1)));
+    assert_eq!(buffer.get_selected_text(), "two       \nthree");
+
+    // Rows past the screen, i.e. the status line, are clamped to the last row
+    buffer.update_selection((3_u16, 10_u16).into());
+    assert_eq!(buffer.get_selected_text(), "e     \nfour");
+
+    // The selection moves with its lines as the oldest are dropped
+    buffer.max_scrollback = 4;
+    buffer.add_data(b"\r\nsix");
+    assert_eq!(buffer.selection_start, Some((4, 0)));
+    assert_eq!(buffer.get_selected_text(), "e     \nfour");
+    buffer.add_data(b"\r\nseven\r\neight\r\nnine");
+    assert_eq!(buffer.get_selected_text(), "");
+    assert!(buffer.selection_start.is_none());
+
+    // Scrolling without dragging still clears the selection
+    buffer.search("eight".to_string());
+    buffer.scroll_up(1);
+    assert!(!buffer.ui_state.selection_active());
+}
+
+#[test]
+fn click_without_drag_isnt_a_selection() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 3);
+    buffer.add_data(b"one\r\ntwo");
+    buffer.start_selection((1_u16, 0_u16).into());
+    buffer.update_selection((1_u16, 0_u16).into());
+    assert!(!buffer.ui_state.selection_active());
+    buffer.update_selection((2_u16, 0_u16).into());
+    assert!(buffer.ui_state.selection_active());
+}
+
+#[test]
+fn pause_following_while_scrolled_up() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(20, 3);
+    for i in 0..6 {
+        buffer.add_data(format!("{i}\r\n").as_bytes());
+    }
+    assert_eq!(buffer.view_start, 4);
+    buffer.scroll_up(2);
+    assert!(!buffer.follow);
+    // New data doesn't move the view while paused
+    buffer.add_data(b"6\r\n7\r\n");
+    assert_eq!(buffer.view_start, 2);
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    assert!(String::from_utf8_lossy(&frame).contains("[PAUSED]"));
+
+    // Scrolling back down to the bottom follows again
+    buffer.scroll_down(10);
+    assert!(buffer.follow);
+    buffer.add_data(b"8\r\n");
+    assert_eq!(buffer.view_start, 7);
+
+    buffer.scroll_to_top();
+    buffer.add_data(b"9\r\n");
+    assert_eq!(buffer.view_start, 0);
+    buffer.scroll_to_bottom();
+    assert!(buffer.follow);
+    assert_eq!(buffer.view_start, 8);
+}
+
+#[test]
+fn dump_scrollback_text() {
+    super::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 4);
+    buffer.add_data(b"first  \r\n\r\n\x1b[1msecond\x1b[0m\r\n");
+    assert_eq!(buffer.history_text(), "first\n\nsecond\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dump.txt");
+    buffer.dump_scrollback(&path);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\n\nsecond\n");
+
+    assert_eq!(ScreenBuffer::new(10, 4).history_text(), "");
 }
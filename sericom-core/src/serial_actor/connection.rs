@@ -0,0 +1,67 @@
+use std::{future::Future, io};
+
+/// Abstracts the connection that the [`SerialActor`][super::SerialActor] communicates over.
+///
+/// Implemented for [`serial2_tokio::SerialPort`], which is what `sericom` uses, and for
+/// [`tokio::io::DuplexStream`] which makes for an in-memory connection that can be used
+/// to drive the [`SerialActor`][super::SerialActor] with scripted data, i.e. in tests.
+pub trait SerialConnection {
+    /// Reads data from the connection into `buf`, returning the number of bytes read.
+    /// Returning `Ok(0)` signals that the connection has been closed.
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>> + Send;
+    /// Writes all of `buf` to the connection.
+    fn write_all(&mut self, buf: &[u8]) -> impl Future<Output = io::Result<()>> + Send;
+    /// Sets or clears the 'break' condition of the connection.
+    fn set_break(&mut self, enable: bool) -> io::Result<()>;
+    /// Sets the state of the Data Terminal Ready line.
+    fn set_dtr(&mut self, state: bool) -> io::Result<()>;
+    /// Sets the state of the Request To Send line.
+    fn set_rts(&mut self, state: bool) -> io::Result<()>;
+}
+
+impl SerialConnection for serial2_tokio::SerialPort {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        serial2_tokio::SerialPort::read(self, buf).await
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        serial2_tokio::SerialPort::write_all(self, buf).await
+    }
+
+    fn set_break(&mut self, enable: bool) -> io::Result<()> {
+        serial2_tokio::SerialPort::set_break(self, enable)
+    }
+
+    fn set_dtr(&mut self, state: bool) -> io::Result<()> {
+        serial2_tokio::SerialPort::set_dtr(self, state)
+    }
+
+    fn set_rts(&mut self, state: bool) -> io::Result<()> {
+        serial2_tokio::SerialPort::set_rts(self, state)
+    }
+}
+
+impl SerialConnection for tokio::io::DuplexStream {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        tokio::io::AsyncReadExt::read(self, buf).await
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        tokio::io::AsyncWriteExt::write_all(self, buf).await
+    }
+
+    /// An in-memory stream has no 'break' condition, this does nothing.
+    fn set_break(&mut self, _enable: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// An in-memory stream has no control lines, this does nothing.
+    fn set_dtr(&mut self, _state: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// An in-memory stream has no control lines, this does nothing.
+    fn set_rts(&mut self, _state: bool) -> io::Result<()> {
+        Ok(())
+    }
+}
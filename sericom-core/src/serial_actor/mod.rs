@@ -1,7 +1,11 @@
 //! This module holds all of the code directly responsible for interacting
 //! with the serial connection and tasks within the program.
 
+mod connection;
+mod sink;
 pub mod tasks;
+pub use connection::*;
+pub use sink::*;
 
 /// Represents messages/commands that are sent from worker tasks
 /// to the [`SerialActor`] to process.
@@ -12,10 +16,48 @@ pub enum SerialMessage {
     Write(Vec<u8>),
     /// Instructs the [`SerialActor`] to send a 'break' signal over the serial connection.
     SendBreak,
+    /// Instructs the [`SerialActor`] to set the Data Terminal Ready line.
+    SetDtr(bool),
+    /// Instructs the [`SerialActor`] to set the Request To Send line.
+    SetRts(bool),
     /// Instructs the [`SerialActor`] to shutdown the serial connection.
     Shutdown,
 }
 
+/// A sequence of the DTR and RTS lines that resets a device, i.e. into its
+/// bootloader, performed with [`run_device_reset`][tasks::run_device_reset].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceReset {
+    /// The reset into the bootloader of ESP32 (and ESP8266) development boards,
+    /// where DTR drives `IO0` and RTS drives `EN` through transistors.
+    Esp32,
+}
+
+impl DeviceReset {
+    /// Returns the states of the `(DTR, RTS)` lines that make up the sequence,
+    /// each held for the given time.
+    pub fn steps(self) -> &'static [(bool, bool, std::time::Duration)] {
+        use std::time::Duration;
+        // Holds `EN` low, then releases it while `IO0` is held low
+        const ESP32: [(bool, bool, Duration); 3] = [
+            (false, true, Duration::from_millis(100)),
+            (true, false, Duration::from_millis(50)),
+            (false, false, Duration::ZERO),
+        ];
+        match self {
+            Self::Esp32 => &ESP32,
+        }
+    }
+
+    /// Returns the states of the `(DTR, RTS)` lines once the sequence is done.
+    pub fn final_lines(self) -> (bool, bool) {
+        self.steps()
+            .last()
+            .map_or((true, true), |&(dtr, rts, _)| (dtr, rts))
+    }
+}
+
 /// Represents events from the [`SerialActor`] that will be
 /// received and processed by worker tasks accordingly.
 #[non_exhaustive]
@@ -27,6 +69,61 @@ pub enum SerialEvent {
     Error(String),
     /// Tells the [`SerialActor`]s tasks that the serial connection has been closed.
     ConnectionClosed,
+    /// The connection was lost and the [`SerialActor`] is trying to reopen it,
+    /// see [`Reconnect`].
+    Reconnecting,
+    /// The connection was reopened after [`SerialEvent::Reconnecting`].
+    Reconnected,
+    /// The state of the connection changed, i.e. `Connected | /dev/ttyUSB0 9600 8N1`.
+    /// Only broadcast with [`SerialActor::with_status()`].
+    Status(String),
+}
+
+/// The longest that [`Reconnect`] waits between attempts to reopen the connection.
+const MAX_RECONNECT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How the [`SerialActor`] reopens its connection after losing it, i.e. when a
+/// USB-serial adapter is unplugged, see [`SerialActor::with_reconnect()`].
+///
+/// The time between attempts starts at `interval` and doubles after each failed
+/// attempt, up to 30 seconds, which is also the most `interval` is waited.
+pub struct Reconnect<C> {
+    open: Box<dyn FnMut() -> std::io::Result<C> + Send>,
+    /// The number of attempts before giving up, `0` never gives up.
+    pub retries: u32,
+    /// The time to wait before the first attempt.
+    pub interval: std::time::Duration,
+    /// Written to the connection once it has been reopened, i.e.
+    /// [`Defaults::on_reconnect_lines()`][crate::configs::Defaults::on_reconnect_lines].
+    pub on_reconnect: Vec<Vec<u8>>,
+}
+
+impl<C> Reconnect<C> {
+    /// Constructs a `Reconnect` that reopens the connection with `open`, using
+    /// the config's [`reconnect_retries`][crate::configs::Defaults::reconnect_retries],
+    /// [`reconnect_interval_ms`][crate::configs::Defaults::reconnect_interval_ms] and
+    /// [`on_reconnect`][crate::configs::Defaults::on_reconnect].
+    pub fn new<F: FnMut() -> std::io::Result<C> + Send + 'static>(open: F) -> Self {
+        let defaults = &crate::configs::get_config().defaults;
+        Self {
+            open: Box::new(open),
+            retries: defaults.reconnect_retries,
+            interval: std::time::Duration::from_millis(defaults.reconnect_interval_ms),
+            on_reconnect: defaults
+                .on_reconnect_lines()
+                .map(String::into_bytes)
+                .collect(),
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for Reconnect<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reconnect")
+            .field("retries", &self.retries)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
 }
 
 /// Responsible for passing data and messages between the serial connection and tasks.
@@ -36,17 +133,23 @@ pub enum SerialEvent {
 /// It broadcasts [`SerialEvent`]s to worker tasks via a [`tokio::sync::broadcast`]
 /// channel, and receives [`SerialMessage`]s from worker tasks via a [`tokio::sync::mpsc`]
 /// channel.
-pub struct SerialActor {
-    connection: serial2_tokio::SerialPort,
+///
+/// The connection can be anything that implements [`SerialConnection`], by default
+/// this is a [`serial2_tokio::SerialPort`].
+pub struct SerialActor<C: SerialConnection = serial2_tokio::SerialPort> {
+    connection: C,
     command_rx: tokio::sync::mpsc::Receiver<SerialMessage>,
     broadcast_channel: tokio::sync::broadcast::Sender<SerialEvent>,
+    reconnect: Option<Reconnect<C>>,
+    /// Describes the connection in [`SerialEvent::Status`], see [`SerialActor::with_status()`].
+    status_label: Option<String>,
 }
 
-impl SerialActor {
+impl<C: SerialConnection> SerialActor<C> {
     /// Constructs a [`SerialActor`] Takes a serial port connection,
     /// receiver to a command channel, and a sender to a broadcast channel.
     pub fn new(
-        connection: serial2_tokio::SerialPort,
+        connection: C,
         command_rx: tokio::sync::mpsc::Receiver<SerialMessage>,
         broadcast_channel: tokio::sync::broadcast::Sender<SerialEvent>,
     ) -> Self {
@@ -54,9 +157,33 @@ impl SerialActor {
             connection,
             command_rx,
             broadcast_channel,
+            reconnect: None,
+            status_label: None,
         }
     }
 
+    /// Broadcasts a [`SerialEvent::Status`] when the actor starts running and when
+    /// the connection is lost or reopened, with `label` describing the connection,
+    /// i.e. the port and its settings.
+    pub fn with_status<S: Into<String>>(mut self, label: S) -> Self {
+        self.status_label = Some(label.into());
+        self
+    }
+
+    fn send_status(&self, state: &str) {
+        if let Some(ref label) = self.status_label {
+            let status = SerialEvent::Status(format!("{state} | {label}"));
+            self.broadcast_channel.send(status).ok();
+        }
+    }
+
+    /// Reopens the connection with `reconnect` when it is lost instead of shutting
+    /// down, broadcasting [`SerialEvent::Reconnecting`] and [`SerialEvent::Reconnected`].
+    pub fn with_reconnect(mut self, reconnect: Reconnect<C>) -> Self {
+        self.reconnect = Some(reconnect);
+        self
+    }
+
     /// This is the heart and soul of the [`SerialActor`].
     /// `sericom` uses the Actor model to receive data from a serial connection
     /// and forward to other tasks for them to process. It also receives [`SerialEvent`]s
@@ -68,6 +195,7 @@ impl SerialActor {
     /// batch the data before sending it to other tasks to reduce the number of syscalls.
     pub async fn run(mut self) {
         let mut buffer = vec![0u8; 4096];
+        self.send_status("Connected");
         loop {
             tokio::select! {
                 // Handle commands/input from tasks
@@ -80,10 +208,21 @@ impl SerialActor {
                         }
                         Some(SerialMessage::Shutdown) => {
                             self.broadcast_channel.send(SerialEvent::ConnectionClosed).ok();
+                            break;
                         }
                         Some(SerialMessage::SendBreak) => {
                             self.send_break().await;
                         }
+                        Some(SerialMessage::SetDtr(state)) => {
+                            if let Err(e) = self.connection.set_dtr(state) {
+                                self.broadcast_channel.send(SerialEvent::Error(e.to_string())).ok();
+                            }
+                        }
+                        Some(SerialMessage::SetRts(state)) => {
+                            if let Err(e) = self.connection.set_rts(state) {
+                                self.broadcast_channel.send(SerialEvent::Error(e.to_string())).ok();
+                            }
+                        }
                         None => break,
                     }
                 }
@@ -91,8 +230,13 @@ impl SerialActor {
                 read_result = self.connection.read(&mut buffer) => {
                     match read_result {
                         Ok(0) => {
-                            self.broadcast_channel.send(SerialEvent::ConnectionClosed).ok();
-                            break;
+                            if self.reconnect.is_none() {
+                                self.broadcast_channel.send(SerialEvent::ConnectionClosed).ok();
+                                break;
+                            }
+                            if !self.reconnect().await {
+                                break;
+                            }
                         }
                         Ok(n) => {
                             let data: std::sync::Arc<[u8]> = buffer[..n].into();
@@ -100,12 +244,61 @@ impl SerialActor {
                         }
                         Err(e) => {
                             self.broadcast_channel.send(SerialEvent::Error(e.to_string())).ok();
-                            break;
+                            if !self.reconnect().await {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tries to reopen the connection, returns whether it was reopened. Gives up
+    /// after [`Reconnect::retries`] or when receiving [`SerialMessage::Shutdown`],
+    /// broadcasting [`SerialEvent::ConnectionClosed`]. Writes are dropped while
+    /// the connection is lost.
+    async fn reconnect(&mut self) -> bool {
+        // Taken while reconnecting and put back once reconnected
+        let Some(mut reconnect) = self.reconnect.take() else {
+            return false;
+        };
+        self.broadcast_channel.send(SerialEvent::Reconnecting).ok();
+        self.send_status("Disconnected, reconnecting...");
+        let mut interval = reconnect.interval.min(MAX_RECONNECT_INTERVAL);
+        let mut attempts = 0;
+        loop {
+            tokio::select! {
+                cmd = self.command_rx.recv() => {
+                    if matches!(cmd, Some(SerialMessage::Shutdown) | None) {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(interval) => {
+                    attempts += 1;
+                    match (reconnect.open)() {
+                        Ok(connection) => {
+                            self.connection = connection;
+                            self.broadcast_channel.send(SerialEvent::Reconnected).ok();
+                            self.send_status("Connected");
+                            for line in &reconnect.on_reconnect {
+                                if let Err(e) = self.connection.write_all(line).await {
+                                    self.broadcast_channel.send(SerialEvent::Error(e.to_string())).ok();
+                                }
+                            }
+                            self.reconnect = Some(reconnect);
+                            return true;
                         }
+                        Err(_) if reconnect.retries != 0 && attempts >= reconnect.retries => break,
+                        Err(_) => interval = interval.saturating_mul(2).min(MAX_RECONNECT_INTERVAL),
                     }
                 }
             }
         }
+        self.broadcast_channel
+            .send(SerialEvent::ConnectionClosed)
+            .ok();
+        false
     }
 
     async fn send_break(&mut self) {
@@ -115,3 +308,96 @@ impl SerialActor {
         let _ = self.connection.set_break(false);
     }
 }
+
+#[tokio::test]
+async fn actor_forwards_scripted_data() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Both `AsyncWriteExt` and `SerialConnection` provide `write_all`
+    let (connection, mut device) = tokio::io::duplex(64);
+    let (command_tx, command_rx) = tokio::sync::mpsc::channel::<SerialMessage>(10);
+    let (broadcast_tx, mut event_rx) = tokio::sync::broadcast::channel::<SerialEvent>(10);
+    let actor = tokio::spawn(SerialActor::new(connection, command_rx, broadcast_tx).run());
+
+    // Device -> broadcast channel
+    AsyncWriteExt::write_all(&mut device, b"Switch>")
+        .await
+        .unwrap();
+    match event_rx.recv().await {
+        Ok(SerialEvent::Data(data)) => assert_eq!(&data[..], b"Switch>"),
+        other => panic!("Expected data, got: {other:?}"),
+    }
+
+    // Command channel -> device
+    command_tx
+        .send(SerialMessage::Write(b"enable\r".to_vec()))
+        .await
+        .unwrap();
+    let mut buf = [0u8; 7];
+    device.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"enable\r");
+
+    // Closing the device's end closes the connection
+    drop(device);
+    assert!(matches!(
+        event_rx.recv().await,
+        Ok(SerialEvent::ConnectionClosed)
+    ));
+    actor.await.unwrap();
+}
+
+#[tokio::test]
+async fn actor_reconnects() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (connection, device) = tokio::io::duplex(64);
+    let (reopened, mut device_rx) = tokio::io::duplex(64);
+    let mut reopened = Some(reopened);
+    let reconnect = Reconnect {
+        open: Box::new(move || {
+            reopened
+                .take()
+                .ok_or_else(|| std::io::Error::other("Not found"))
+        }),
+        retries: 3,
+        interval: std::time::Duration::from_millis(1),
+        on_reconnect: vec![b"terminal length 0\r".to_vec()],
+    };
+    let (command_tx, command_rx) = tokio::sync::mpsc::channel::<SerialMessage>(10);
+    let (broadcast_tx, mut event_rx) = tokio::sync::broadcast::channel::<SerialEvent>(10);
+    let actor = SerialActor::new(connection, command_rx, broadcast_tx).with_reconnect(reconnect);
+    let actor = tokio::spawn(actor.run());
+
+    drop(device);
+    assert!(matches!(
+        event_rx.recv().await,
+        Ok(SerialEvent::Reconnecting)
+    ));
+    assert!(matches!(
+        event_rx.recv().await,
+        Ok(SerialEvent::Reconnected)
+    ));
+    let mut buf = [0u8; 18];
+    device_rx.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"terminal length 0\r");
+
+    AsyncWriteExt::write_all(&mut device_rx, b"Switch>")
+        .await
+        .unwrap();
+    assert!(
+        matches!(event_rx.recv().await, Ok(SerialEvent::Data(data)) if &data[..] == b"Switch>")
+    );
+
+    // Gives up once the retries are used up
+    drop(device_rx);
+    assert!(matches!(
+        event_rx.recv().await,
+        Ok(SerialEvent::Reconnecting)
+    ));
+    assert!(matches!(
+        event_rx.recv().await,
+        Ok(SerialEvent::ConnectionClosed)
+    ));
+    actor.await.unwrap();
+    drop(command_tx);
+}
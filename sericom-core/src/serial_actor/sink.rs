@@ -0,0 +1,264 @@
+use super::SerialEvent;
+
+/// The most data held before it is sent to the sink's thread.
+const BATCH_BYTES: usize = 4096;
+/// How often the data held is sent to the sink's thread.
+const BATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+/// How often the sink is flushed while data is being written, or while it is idle.
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// A destination for the data received from the serial connection, i.e. a file,
+/// a TCP socket or a named pipe, driven by [`run_output_sink()`].
+///
+/// The methods are called from a blocking thread, so they are free to block
+/// on i/o. An error is logged and the sink keeps receiving data.
+pub trait OutputSink: Send + 'static {
+    /// Called before anything else, i.e. to create a file or connect to a socket.
+    /// An error stops the sink.
+    fn on_open(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Writes a batch of the data received.
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()>;
+
+    /// Called for each [`SerialEvent::Error`].
+    fn on_error(&mut self, _error: &str) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Called once the connection was closed, with [`SerialEvent::ConnectionClosed`].
+    /// Isn't called when the session ends before the connection is closed.
+    fn on_close(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Flushes the data written so far, called every 200ms and before the sink
+    /// is dropped.
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Formats the events that [`run_output_sink_with()`] receives into the data
+/// written to its [`OutputSink`], i.e. to strip the colors or timestamp the lines.
+///
+/// The methods append to `out`, which is sent to the sink in batches. A format
+/// may also hold output back and append it later on.
+pub trait SinkFormat: Send + 'static {
+    /// Formats the data received from the device.
+    fn data(&mut self, data: &[u8], out: &mut Vec<u8>);
+
+    /// Formats the data the user sent to the device, see [`run_output_sink_with()`].
+    fn sent(&mut self, _data: &[u8], _out: &mut Vec<u8>) {}
+
+    /// Formats the events other than [`SerialEvent::Data`] and [`SerialEvent::ConnectionClosed`],
+    /// before an error is passed to [`OutputSink::on_error()`].
+    fn event(&mut self, _event: &SerialEvent, _out: &mut Vec<u8>) {}
+
+    /// Called once the connection was closed, for the output that is still held back.
+    fn finish(&mut self, _out: &mut Vec<u8>) {}
+}
+
+/// The [`SinkFormat`] of [`run_output_sink()`], passing the data received on as-is.
+#[derive(Debug, Default)]
+pub struct RawFormat;
+
+impl SinkFormat for RawFormat {
+    fn data(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        out.extend_from_slice(data);
+    }
+}
+
+/// What is sent to the thread of an [`OutputSink`], see [`spawn_sink()`].
+#[derive(Debug)]
+pub(crate) enum SinkMessage {
+    Data(Vec<u8>),
+    Error(String),
+    Close,
+}
+
+/// Sends the [`SerialEvent`]s received on `rx` to `sink`, until the connection
+/// is closed or the session ends.
+///
+/// The data is batched, sent to `sink` once 4 KiB are held or every
+/// 200ms, and `sink` is periodically flushed.
+pub async fn run_output_sink<S: OutputSink>(
+    rx: tokio::sync::broadcast::Receiver<SerialEvent>,
+    sink: S,
+) {
+    run_output_sink_with(rx, sink, RawFormat, None).await;
+}
+
+/// Like [`run_output_sink()`], with the data formatted by `format` as it is
+/// received rather than once it is batched, i.e. so that timestamps are accurate.
+///
+/// When given a `sent_rx`, the data the user sends to the device is formatted
+/// with [`SinkFormat::sent()`] and interleaved with the data received.
+pub async fn run_output_sink_with<S: OutputSink, F: SinkFormat>(
+    mut rx: tokio::sync::broadcast::Receiver<SerialEvent>,
+    sink: S,
+    mut format: F,
+    mut sent_rx: Option<tokio::sync::mpsc::Receiver<Vec<u8>>>,
+) {
+    let (sink_tx, sink_handle) = spawn_sink(sink);
+
+    let mut write_buf = Vec::with_capacity(BATCH_BYTES);
+    // The first batch is sent after an interval rather than right away
+    let mut batch_timer =
+        tokio::time::interval_at(tokio::time::Instant::now() + BATCH_INTERVAL, BATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                match event {
+                    Ok(SerialEvent::Data(data)) => {
+                        format.data(&data, &mut write_buf);
+                        if write_buf.len() >= BATCH_BYTES
+                            && sink_tx.send(SinkMessage::Data(std::mem::take(&mut write_buf))).is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Ok(SerialEvent::Error(e)) => {
+                        format.event(&SerialEvent::Error(e.clone()), &mut write_buf);
+                        if !write_buf.is_empty() {
+                            let _ = sink_tx.send(SinkMessage::Data(std::mem::take(&mut write_buf)));
+                        }
+                        if sink_tx.send(SinkMessage::Error(e)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(SerialEvent::ConnectionClosed) => {
+                        format.finish(&mut write_buf);
+                        if !write_buf.is_empty() {
+                            let _ = sink_tx.send(SinkMessage::Data(std::mem::take(&mut write_buf)));
+                        }
+                        let _ = sink_tx.send(SinkMessage::Close);
+                        break;
+                    }
+                    Ok(event) => format.event(&event, &mut write_buf),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        eprintln!("Output lagged, skipped {skipped} messages");
+                    }
+                    Err(_) => break,
+                }
+            }
+            sent = recv_sent(&mut sent_rx) => match sent {
+                Some(sent) => format.sent(&sent, &mut write_buf),
+                None => sent_rx = None,
+            },
+            _ = batch_timer.tick() => {
+                if !write_buf.is_empty()
+                    && sink_tx.send(SinkMessage::Data(std::mem::take(&mut write_buf))).is_err()
+                {
+                    break;
+                }
+            }
+        }
+    }
+    if !write_buf.is_empty() {
+        let _ = sink_tx.send(SinkMessage::Data(write_buf));
+    }
+    drop(sink_tx);
+    let _ = sink_handle.await;
+}
+
+/// Receives from `sent_rx`, or waits forever when there isn't one.
+async fn recv_sent(sent_rx: &mut Option<tokio::sync::mpsc::Receiver<Vec<u8>>>) -> Option<Vec<u8>> {
+    match sent_rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Runs `sink` on a blocking thread, calling its methods for the [`SinkMessage`]s
+/// sent until the sender is dropped.
+pub(crate) fn spawn_sink<S: OutputSink>(
+    mut sink: S,
+) -> (
+    std::sync::mpsc::Sender<SinkMessage>,
+    tokio::task::JoinHandle<()>,
+) {
+    use std::sync::mpsc::RecvTimeoutError;
+
+    let (sink_tx, sink_rx) = std::sync::mpsc::channel::<SinkMessage>();
+    let handle = tokio::task::spawn_blocking(move || {
+        if let Err(e) = sink.on_open() {
+            eprintln!("{e}");
+            return;
+        }
+        let mut last_flush = std::time::Instant::now();
+        loop {
+            let result = match sink_rx.recv_timeout(FLUSH_INTERVAL) {
+                Ok(SinkMessage::Data(data)) => sink.write(&data),
+                Ok(SinkMessage::Error(e)) => sink.on_error(&e),
+                Ok(SinkMessage::Close) => sink.on_close(),
+                Err(RecvTimeoutError::Timeout) => Ok(()),
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to write the output: {e}");
+            }
+            if last_flush.elapsed() >= FLUSH_INTERVAL {
+                let _ = sink.flush();
+                last_flush = std::time::Instant::now();
+            }
+        }
+        let _ = sink.flush();
+    });
+    (sink_tx, handle)
+}
+
+/// An [`OutputSink`] that records what it is called with, for tests.
+#[cfg(test)]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Recorder(pub(crate) std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+#[cfg(test)]
+impl OutputSink for Recorder {
+    fn on_open(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap().push("open".to_string());
+        Ok(())
+    }
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let data = String::from_utf8_lossy(data).to_string();
+        self.0.lock().unwrap().push(data);
+        Ok(())
+    }
+    fn on_error(&mut self, error: &str) -> std::io::Result<()> {
+        self.0.lock().unwrap().push(format!("error: {error}"));
+        Ok(())
+    }
+    fn on_close(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap().push("close".to_string());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn output_sink_events() {
+    let (event_tx, rx) = tokio::sync::broadcast::channel(16);
+    let recorder = Recorder::default();
+    let task = tokio::spawn(run_output_sink(rx, recorder.clone()));
+    event_tx
+        .send(SerialEvent::Data(b"ab".to_vec().into()))
+        .unwrap();
+    event_tx
+        .send(SerialEvent::Status("9600 8N1".into()))
+        .unwrap();
+    event_tx
+        .send(SerialEvent::Data(b"c".to_vec().into()))
+        .unwrap();
+    event_tx
+        .send(SerialEvent::Error("Device unplugged".to_string()))
+        .unwrap();
+    event_tx
+        .send(SerialEvent::Data(b"d".to_vec().into()))
+        .unwrap();
+    event_tx.send(SerialEvent::ConnectionClosed).unwrap();
+    task.await.unwrap();
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        ["open", "abc", "error: Device unplugged", "d", "close"]
+    );
+}
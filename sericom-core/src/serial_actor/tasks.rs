@@ -1,9 +1,7 @@
 use super::*;
+use crate::configs::{KeyAction, KeySpec};
 use crate::screen_buffer::*;
-use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind},
-    terminal,
-};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
 use std::{
     fs::File,
     io::{BufWriter, Write},
@@ -20,56 +18,205 @@ const UTF_UP_KEY: &str = "\u{001B}\u{005B}\u{0041}";
 const UTF_DOWN_KEY: &str = "\u{001B}\u{005B}\u{0042}";
 const UTF_LEFT_KEY: &str = "\u{001B}\u{005B}\u{0044}";
 const UTF_RIGHT_KEY: &str = "\u{001B}\u{005B}\u{0043}";
+const UTF_INSERT_KEY: &str = "\u{001B}[2~";
+const UTF_PAGE_UP_KEY: &str = "\u{001B}[5~";
+const UTF_PAGE_DOWN_KEY: &str = "\u{001B}[6~";
+
+/// How long the terminal's size must stay the same before the screen is resized.
+const RESIZE_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(100);
+
+const COLOR_PROMPT: &str = "Text color (Enter to set, Esc to cancel): ";
+const INVALID_COLOR_PROMPT: &str = "Unknown color, try again (i.e. 'dark-cyan'): ";
+const FILTER_PROMPT: &str = "Only show lines matching (regex, !regex to hide, empty to show all): ";
+const INVALID_FILTER_PROMPT: &str = "Invalid regex, try again: ";
+const SEARCH_PROMPT: &str = "Search (Enter to find, Alt+n/Alt+N for next/previous): ";
+const DUMP_PROMPT: &str = "Save the history to (path, Enter to save, Esc to cancel): ";
+const INVALID_DUMP_PROMPT: &str = "Invalid path, try again: ";
+
+/// A prompt opened over the last line of the screen to read a line of input.
+#[derive(Clone, Copy, Debug)]
+enum InputPrompt {
+    /// Sets the text color, opened with [`Keybindings::color`][crate::configs::Keybindings::color].
+    Color,
+    /// Sets the display filter, opened with
+    /// [`Keybindings::display_filter`][crate::configs::Keybindings::display_filter].
+    DisplayFilter,
+    /// Searches the history, opened with [`Keybindings::search`][crate::configs::Keybindings::search].
+    Search,
+    /// Saves the history to a file, opened with
+    /// [`Keybindings::dump_scrollback`][crate::configs::Keybindings::dump_scrollback].
+    DumpScrollback,
+}
+
+impl InputPrompt {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Color => COLOR_PROMPT,
+            Self::DisplayFilter => FILTER_PROMPT,
+            Self::Search => SEARCH_PROMPT,
+            Self::DumpScrollback => DUMP_PROMPT,
+        }
+    }
+
+    fn invalid_label(self) -> &'static str {
+        match self {
+            Self::Color => INVALID_COLOR_PROMPT,
+            Self::DisplayFilter => INVALID_FILTER_PROMPT,
+            // Any text can be searched for
+            Self::Search => SEARCH_PROMPT,
+            Self::DumpScrollback => INVALID_DUMP_PROMPT,
+        }
+    }
+
+    /// Returns the [`UICommand`] for the text entered, `None` if it is invalid.
+    fn submit(self, input: &str) -> Option<UICommand> {
+        match self {
+            Self::Color => crate::cli::color_parser(input)
+                .ok()
+                .map(UICommand::SetForeground),
+            Self::DisplayFilter => parse_display_filter(input)
+                .ok()
+                .map(UICommand::SetDisplayFilter),
+            Self::Search => Some(UICommand::Search(input.to_string())),
+            Self::DumpScrollback => {
+                use crate::path_utils::ExpandPaths;
+                (!input.is_empty())
+                    .then(|| PathBuf::from(input).get_expanded_path())
+                    .flatten()
+                    .map(UICommand::DumpScrollback)
+            }
+        }
+    }
+}
+
+/// Starts the timer that renders the [`ScreenBuffer`] once per
+/// [`ScreenBuffer::render_interval()`] while it changes, see [`run_stdout_output`].
+/// The first frame is rendered right away.
+fn start_render_timer(
+    render_timer: &mut Option<tokio::time::Interval>,
+    screen_buffer: &ScreenBuffer,
+) {
+    if render_timer.is_none() && screen_buffer.needs_render() {
+        let mut timer = tokio::time::interval(screen_buffer.render_interval());
+        timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        *render_timer = Some(timer);
+    }
+}
+
+/// Parses the display filter entered with [`InputPrompt::DisplayFilter`]. A leading
+/// `!` hides the matching lines instead, and an empty `input` removes the filter.
+fn parse_display_filter(input: &str) -> Result<crate::configs::LineFilter, regex::Error> {
+    use crate::configs::{LineFilter, Pattern};
+
+    let (pattern, exclude) = match input.strip_prefix('!') {
+        Some(pattern) => (pattern, true),
+        None => (input, false),
+    };
+    if pattern.is_empty() {
+        return Ok(LineFilter::default());
+    }
+    let pattern = Some(Pattern(regex::Regex::new(pattern)?));
+    Ok(match exclude {
+        true => LineFilter {
+            include: None,
+            exclude: pattern,
+        },
+        false => LineFilter {
+            include: pattern,
+            exclude: None,
+        },
+    })
+}
 
 /// Responsible for receiving incoming data from the [`SerialActor`] and
 /// rendering terminal output via the [`ScreenBuffer`].
+///
+/// The data received is added to the [`ScreenBuffer`] as it arrives, while the
+/// screen is only rendered by a timer at [`Appearance::max_fps`][crate::configs::Appearance::max_fps],
+/// so that busy ports don't redraw the screen for every chunk of data.
 #[instrument(skip_all, name = "Stdout")]
 pub async fn run_stdout_output(
     mut con_rx: tokio::sync::broadcast::Receiver<SerialEvent>,
     mut ui_rx: tokio::sync::mpsc::Receiver<UICommand>,
+    ui_state: std::sync::Arc<UIState>,
 ) {
-    let (width, height) = terminal::size().unwrap_or((80, 24));
-    let mut screen_buffer = ScreenBuffer::new(width, height);
-    let mut data_buffer = Vec::with_capacity(2048);
+    let (width, height) = terminal_size();
+    let mut screen_buffer = ScreenBuffer::new(width, height).with_ui_state(ui_state);
+    if crate::configs::get_config().defaults.no_scrollback {
+        screen_buffer.disable_scrollback();
+    }
     let mut render_timer: Option<tokio::time::Interval> = None;
+    let mut throttle = crate::configs::get_config()
+        .defaults
+        .display_rate_limit
+        .filter(|&kb_per_sec| kb_per_sec > 0)
+        .map(DisplayThrottle::new);
+    let mut throttle_timer: Option<tokio::time::Interval> = None;
+    let mut throughput = Throughput::default();
+    let mut show_throughput = crate::configs::get_config().appearance.throughput;
+    let show_status = crate::configs::get_config().appearance.status_line;
+    let mut throughput_timer = tokio::time::interval(Throughput::INTERVAL);
+    let defaults = &crate::configs::get_config().defaults;
+    let mut notifier = Notifier::new(&defaults.notify_on, defaults.notify_command.as_deref());
+    // The latest size the terminal was resized to and when to apply it
+    let mut pending_resize: Option<((u16, u16), tokio::time::Instant)> = None;
 
     loop {
         tokio::select! {
             serial_event = con_rx.recv() => {
                 match serial_event {
                     Ok(SerialEvent::Data(data)) => {
-                        data_buffer.extend_from_slice(&data);
-
-                        if data_buffer.len() > 1024 || data.contains(&b'\n') {
-                            screen_buffer.add_data(&data_buffer);
-                            data_buffer.clear();
-
-                            if screen_buffer.should_render_now() {
-                                screen_buffer.render().ok();
-                                render_timer = None;
-                            } else if render_timer.is_none() {
-                                render_timer = Some(tokio::time::interval(tokio::time::Duration::from_millis(16)));
-                            }
-                        } else {
-                            screen_buffer.add_data(&data_buffer);
-                            data_buffer.clear();
-
-                            if screen_buffer.should_render_now() {
-                                screen_buffer.render().ok();
-                            } else if render_timer.is_none() {
-                                render_timer = Some(tokio::time::interval(tokio::time::Duration::from_millis(16)));
+                        throughput.push(data.len());
+                        if notifier.as_mut().is_some_and(|n| n.push(&data)) {
+                            screen_buffer.alert();
+                        }
+                        let data: std::borrow::Cow<[u8]> = match throttle {
+                            Some(ref mut throttle) => {
+                                throttle.push(&data);
+                                if throttle.is_throttling() && throttle_timer.is_none() {
+                                    throttle_timer = Some(tokio::time::interval(DisplayThrottle::INTERVAL));
+                                }
+                                throttle.take(std::time::Instant::now()).into()
                             }
+                            None => data[..].into(),
+                        };
+                        screen_buffer.add_data(&data);
+                        if let Some(ref throttle) = throttle {
+                            throttle.show_notice(&mut screen_buffer);
                         }
+                        // Left to the render timer, so that a burst of data is drawn once
+                        start_render_timer(&mut render_timer, &screen_buffer);
                     }
                     Ok(SerialEvent::Error(e)) => {
                         let error_msg = format!("[ERROR] {e}\r\n");
-                        error!("Added data: {:?}", data_buffer);
+                        error!("Serial error: {e}");
                         screen_buffer.add_data(error_msg.as_bytes());
                         screen_buffer.render().ok();
                         render_timer = None;
                     }
-                    Ok(SerialEvent::ConnectionClosed) => break,
-                    Err(_) => break,
+                    Ok(SerialEvent::Reconnecting) => {
+                        screen_buffer.set_notice("Connection lost, reconnecting...");
+                        screen_buffer.render().ok();
+                    }
+                    Ok(SerialEvent::Reconnected) => {
+                        screen_buffer.set_notice("Reconnected");
+                        screen_buffer.render().ok();
+                    }
+                    Ok(SerialEvent::Status(status)) => {
+                        if show_status {
+                            screen_buffer.set_status(status);
+                            screen_buffer.render().ok();
+                        }
+                    }
+                    Ok(SerialEvent::ConnectionClosed) | Err(_) => {
+                        // Shows the data still held back, as no more is displayed after this
+                        if let Some(ref mut throttle) = throttle {
+                            screen_buffer.add_data(&throttle.take_all());
+                        }
+                        screen_buffer.flush();
+                        screen_buffer.render().ok();
+                        break;
+                    }
                 }
             }
             ui_command = ui_rx.recv() => {
@@ -81,6 +228,12 @@ pub async fn run_stdout_output(
                     Some(UICommand::ScrollDown(lines)) => {
                         screen_buffer.scroll_down(lines);
                     }
+                    Some(UICommand::PageUp) => {
+                        screen_buffer.page_up();
+                    }
+                    Some(UICommand::PageDown) => {
+                        screen_buffer.page_down();
+                    }
                     Some(UICommand::ScrollTop) => {
                         screen_buffer.scroll_to_top();
                     }
@@ -99,11 +252,97 @@ pub async fn run_stdout_output(
                     Some(UICommand::ClearBuffer) => {
                         screen_buffer.clear_buffer();
                     }
+                    Some(UICommand::ClearScreen) => {
+                        screen_buffer.clear_screen_home();
+                    }
+                    Some(UICommand::CancelSelection) => {
+                        screen_buffer.clear_selection();
+                    }
+                    Some(UICommand::ToggleTimestamps) => {
+                        screen_buffer.toggle_timestamps();
+                    }
+                    Some(UICommand::ToggleControlChars) => {
+                        screen_buffer.toggle_control_chars();
+                    }
+                    Some(UICommand::ToggleThroughput) => {
+                        show_throughput = !show_throughput;
+                        screen_buffer.set_sparkline(show_throughput.then(|| throughput.sparkline()));
+                    }
+                    Some(UICommand::ShowPrompt(prompt)) => {
+                        screen_buffer.set_prompt(Some(prompt));
+                    }
+                    Some(UICommand::HidePrompt) => {
+                        screen_buffer.set_prompt(None);
+                    }
+                    Some(UICommand::SetForeground(color)) => {
+                        screen_buffer.set_foreground(color);
+                    }
+                    Some(UICommand::Reset) => {
+                        screen_buffer.reset();
+                    }
+                    Some(UICommand::SetDisplayFilter(filter)) => {
+                        screen_buffer.set_display_filter(filter);
+                    }
+                    Some(UICommand::ShowNotice(notice)) => {
+                        screen_buffer.set_notice(notice);
+                    }
+                    Some(UICommand::Search(query)) => {
+                        screen_buffer.search(query);
+                    }
+                    Some(UICommand::SearchNext) => {
+                        screen_buffer.search_next();
+                    }
+                    Some(UICommand::SearchPrev) => {
+                        screen_buffer.search_prev();
+                    }
+                    Some(UICommand::ToggleHelp) => {
+                        screen_buffer.toggle_help();
+                    }
+                    Some(UICommand::ToggleHexView) => {
+                        screen_buffer.toggle_hex_view();
+                    }
+                    Some(UICommand::DumpScrollback(path)) => {
+                        screen_buffer.dump_scrollback(&path);
+                    }
+                    Some(UICommand::Resize(width, height)) => {
+                        // Bursts of resizes, i.e. while dragging the window's edge,
+                        // are applied once they settle
+                        let deadline = tokio::time::Instant::now() + RESIZE_DEBOUNCE;
+                        pending_resize = Some(((width, height), deadline));
+                        continue;
+                    }
                     None => break,
                 }
                 screen_buffer.render().ok();
                 render_timer = None;
             }
+            _ = async {
+                match pending_resize {
+                    Some((_, deadline)) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending::<()>().await,
+                }
+            } => {
+                if let Some(((width, height), _)) = pending_resize.take() {
+                    screen_buffer.resize(width.max(MIN_TERMINAL_SIZE.0), height.max(MIN_TERMINAL_SIZE.1));
+                    screen_buffer.render().ok();
+                }
+            }
+            _ = async {
+                match screen_buffer.flash_deadline() {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending::<()>().await,
+                }
+            } => {
+                screen_buffer.end_flash();
+                screen_buffer.render().ok();
+            }
+            _ = throughput_timer.tick() => {
+                throughput.tick();
+                if show_throughput {
+                    screen_buffer.set_sparkline(Some(throughput.sparkline()));
+                    screen_buffer.render().ok();
+                }
+            }
             _ = async {
                 if let Some(ref mut timer) = render_timer {
                     timer.tick().await;
@@ -113,34 +352,366 @@ pub async fn run_stdout_output(
             } => {
                 if screen_buffer.should_render_now() {
                     screen_buffer.render().ok();
+                } else if !screen_buffer.needs_render() {
+                    // Nothing was received for a whole frame
                     render_timer = None;
                 }
             }
+            _ = async {
+                if let Some(ref mut timer) = throttle_timer {
+                    timer.tick().await;
+                } else {
+                    std::future::pending::<()>().await
+                }
+            } => {
+                if let Some(ref mut throttle) = throttle {
+                    let data = throttle.take(std::time::Instant::now());
+                    if !data.is_empty() {
+                        screen_buffer.add_data(&data);
+                    }
+                    throttle.show_notice(&mut screen_buffer);
+                    if !throttle.is_throttling() {
+                        throttle_timer = None;
+                    }
+                    start_render_timer(&mut render_timer, &screen_buffer);
+                }
+            }
+        }
+    }
+}
+
+/// The lines typed between <kbd>Enter</kbd> presses for `--local-history`,
+/// kept in memory for the session only.
+///
+/// Recalling a line erases the one being typed with backspaces before
+/// sending the recalled line, without an <kbd>Enter</kbd> so that it can be
+/// edited before being sent.
+#[derive(Debug, Default)]
+struct InputHistory {
+    lines: std::collections::VecDeque<String>,
+    /// The line as the device has it, assuming it echoes what is typed.
+    current: String,
+    /// The index into `lines` of the line that was recalled last.
+    recalled: Option<usize>,
+}
+
+impl InputHistory {
+    /// The number of lines kept, older lines are dropped.
+    const CAPACITY: usize = 100;
+
+    /// Updates the current line with a key that was sent to the device.
+    fn record(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(c) => self.current.push(c),
+            KeyCode::Backspace => {
+                self.current.pop();
+            }
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut self.current);
+                self.recalled = None;
+                if line.trim().is_empty() || self.lines.back() == Some(&line) {
+                    return;
+                }
+                if self.lines.len() == Self::CAPACITY {
+                    self.lines.pop_front();
+                }
+                self.lines.push_back(line);
+            }
+            // The device's line editing can't be followed through other keys
+            KeyCode::Tab | KeyCode::Up | KeyCode::Down | KeyCode::Esc => self.discard_line(),
+            _ => {}
+        }
+    }
+
+    /// Forgets the current line without adding it to the history, i.e. when
+    /// the device cancels it with <kbd>Ctrl</kbd> + <kbd>c</kbd>.
+    fn discard_line(&mut self) {
+        self.current.clear();
+        self.recalled = None;
+    }
+
+    /// Returns the data that replaces the current line with the line before
+    /// the one recalled last.
+    fn older(&mut self) -> Option<Vec<u8>> {
+        let idx = match self.recalled {
+            Some(0) => return None,
+            Some(idx) => idx - 1,
+            None => self.lines.len().checked_sub(1)?,
+        };
+        Some(self.recall(idx))
+    }
+
+    /// Returns the data that replaces the current line with the line after
+    /// the one recalled last.
+    fn newer(&mut self) -> Option<Vec<u8>> {
+        let idx = self.recalled.filter(|&idx| idx + 1 < self.lines.len())? + 1;
+        Some(self.recall(idx))
+    }
+
+    fn recall(&mut self, idx: usize) -> Vec<u8> {
+        let line = self.lines[idx].clone();
+        let mut data = UTF_BKSP.repeat(self.current.chars().count());
+        data.push_str(&line);
+        self.current = line;
+        self.recalled = Some(idx);
+        data.into_bytes()
+    }
+}
+
+/// Counts the bytes received per second over the last [`Throughput::SAMPLES`]
+/// seconds, drawn as a sparkline with [`UICommand::ToggleThroughput`].
+#[derive(Debug, Default)]
+struct Throughput {
+    counts: std::collections::VecDeque<usize>,
+    current: usize,
+}
+
+impl Throughput {
+    const INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+    const SAMPLES: usize = 20;
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    fn push(&mut self, bytes: usize) {
+        self.current += bytes;
+    }
+
+    /// Ends the current second.
+    fn tick(&mut self) {
+        if self.counts.len() == Self::SAMPLES {
+            self.counts.pop_front();
+        }
+        self.counts.push_back(std::mem::take(&mut self.current));
+    }
+
+    /// Returns the counts as bars scaled to the largest count, followed by
+    /// the rate of the last second.
+    fn sparkline(&self) -> String {
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        let mut sparkline: String = self
+            .counts
+            .iter()
+            .map(|&count| match count {
+                0 => ' ',
+                count => Self::BARS[count * (Self::BARS.len() - 1) / max],
+            })
+            .collect();
+        let last = self.counts.back().copied().unwrap_or(0);
+        let rate = if last >= 1024 {
+            format!(" {:.1} KB/s", last as f64 / 1024.0)
+        } else {
+            format!(" {last} B/s")
+        };
+        sparkline.push_str(&rate);
+        sparkline
+    }
+}
+
+/// Limits the rate at which the data received from the device is displayed,
+/// see [`Defaults::display_rate_limit`][crate::configs::Defaults::display_rate_limit].
+///
+/// Data that can't be displayed yet is held until more can be displayed. When
+/// more than a couple of seconds worth of data is held, the oldest data is
+/// skipped so that the screen keeps up with the device.
+#[derive(Debug)]
+struct DisplayThrottle {
+    bytes_per_sec: usize,
+    pending: std::collections::VecDeque<u8>,
+    /// The number of bytes that may currently be displayed.
+    budget: usize,
+    last_refill: Option<std::time::Instant>,
+    /// The number of bytes that were skipped since throttling began.
+    skipped: usize,
+}
+
+impl DisplayThrottle {
+    /// How often held data is displayed while throttling.
+    const INTERVAL: tokio::time::Duration = tokio::time::Duration::from_millis(50);
+    /// The number of seconds worth of data that is held before skipping data.
+    const MAX_PENDING_SECS: usize = 2;
+
+    fn new(kb_per_sec: u64) -> Self {
+        let bytes_per_sec = (kb_per_sec as usize).saturating_mul(1024);
+        Self {
+            bytes_per_sec,
+            pending: std::collections::VecDeque::new(),
+            budget: bytes_per_sec / 10,
+            last_refill: None,
+            skipped: 0,
+        }
+    }
+
+    /// Holds `data` to be displayed.
+    fn push(&mut self, data: &[u8]) {
+        self.pending.extend(data);
+        let max_pending = self.bytes_per_sec.saturating_mul(Self::MAX_PENDING_SECS);
+        if self.pending.len() > max_pending {
+            let excess = self.pending.len() - max_pending;
+            self.pending.drain(..excess);
+            self.skipped += excess;
+        }
+    }
+
+    /// Returns as much of the held data as may be displayed at `now`.
+    fn take(&mut self, now: std::time::Instant) -> Vec<u8> {
+        if let Some(last) = self.last_refill {
+            let elapsed = now.duration_since(last).as_secs_f64();
+            let refill = (elapsed * self.bytes_per_sec as f64) as usize;
+            // Allow bursts of up to a quarter of a second worth of data
+            self.budget = (self.budget + refill).min(self.bytes_per_sec / 4);
+        }
+        self.last_refill = Some(now);
+
+        let len = self.budget.min(self.pending.len());
+        self.budget -= len;
+        if self.pending.len() == len {
+            self.skipped = 0;
+        }
+        self.pending.drain(..len).collect()
+    }
+
+    /// Returns all of the held data, regardless of the rate.
+    fn take_all(&mut self) -> Vec<u8> {
+        self.skipped = 0;
+        self.pending.drain(..).collect()
+    }
+
+    /// Returns whether there is data being held back from the display.
+    fn is_throttling(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Shows a notice on the screen while throttling.
+    fn show_notice(&self, screen_buffer: &mut ScreenBuffer) {
+        if !self.is_throttling() {
+            return;
+        }
+        let kb_per_sec = self.bytes_per_sec / 1024;
+        let notice = match self.skipped {
+            0 => format!("Display throttled to {kb_per_sec} KB/s"),
+            skipped => format!("Display throttled to {kb_per_sec} KB/s, skipped {skipped} bytes"),
+        };
+        screen_buffer.set_notice(notice);
+    }
+}
+
+/// Configures how [`run_stdin_input`] handles the user's input.
+///
+/// By default, everything typed is sent to the device, no history of the lines
+/// typed is kept and the DTR and RTS toggles start from both lines being set.
+#[derive(Clone, Copy, Debug)]
+pub struct InputOptions {
+    read_only: bool,
+    local_history: bool,
+    dtr: bool,
+    rts: bool,
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputOptions {
+    /// Constructs the default `InputOptions`.
+    pub fn new() -> Self {
+        Self {
+            read_only: false,
+            local_history: false,
+            dtr: true,
+            rts: true,
         }
     }
+
+    /// Sends nothing to the device: [`SerialMessage::Write`]s, [`SerialMessage::SendBreak`]s
+    /// and changes to the control lines are dropped while the [`UICommand`]s and
+    /// quitting still work.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Keeps the lines typed so that they can be re-sent with
+    /// [`Keybindings::history_prev`][crate::configs::Keybindings::history_prev]
+    /// and [`Keybindings::history_next`][crate::configs::Keybindings::history_next].
+    pub fn local_history(mut self, local_history: bool) -> Self {
+        self.local_history = local_history;
+        self
+    }
+
+    /// Sets the states of the DTR and RTS lines that the toggles start from, i.e.
+    /// those left by a [`DeviceReset`].
+    pub fn control_lines(mut self, dtr: bool, rts: bool) -> Self {
+        self.dtr = dtr;
+        self.rts = rts;
+        self
+    }
 }
 
 /// Responsible for spawning a blocking task with [`tokio::task::spawn_blocking()`]
-/// and processing user input from stdin.
+/// and processing user input from stdin, configured by `options`.
 ///
 /// Sends data via [`SerialMessage`] to the serial connection and
 /// [`UICommand`]s to the [`ScreenBuffer`] for processing user actions like
 /// scrolling, copying, clearing the screen, etc.
+///
+/// Keys that act on the UI depending on its current state, i.e. <kbd>Esc</kbd>
+/// cancelling an active selection, are decided with `ui_state`.
+///
+/// When given a `sent_tx`, everything the user sends to the device is
+/// also sent through it, i.e. to [`run_file_output`] for `--echo-sent`.
+///
+/// Everything sent to the device, other than [`SerialMessage::Shutdown`], goes
+/// through a single channel so that it reaches the device in the order it was
+/// typed. The channel has a capacity of [`Defaults::command_channel_capacity`][crate::configs::Defaults]
+/// and input waits for space in the channel rather than being dropped.
 pub async fn run_stdin_input(
     command_tx: tokio::sync::mpsc::Sender<SerialMessage>,
     ui_tx: tokio::sync::mpsc::Sender<UICommand>,
+    ui_state: std::sync::Arc<UIState>,
+    sent_tx: Option<tokio::sync::mpsc::Sender<Vec<u8>>>,
+    options: InputOptions,
 ) {
-    let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::channel::<String>(10);
+    let capacity = crate::configs::get_config()
+        .defaults
+        .command_channel_capacity;
+    let (stdin_tx, stdin_rx) = tokio::sync::mpsc::channel::<SerialMessage>(capacity);
     let command_tx_clone = command_tx.clone();
 
-    tokio::task::spawn_blocking(move || stdin_input_loop(stdin_tx, command_tx_clone, ui_tx));
+    tokio::task::spawn_blocking(move || {
+        stdin_input_loop(stdin_tx, command_tx_clone, ui_tx, ui_state, options)
+    });
 
-    while let Some(data) = stdin_rx.recv().await {
-        if command_tx
-            .send(SerialMessage::Write(data.into_bytes()))
-            .await
-            .is_err()
+    forward_input(stdin_rx, command_tx, sent_tx, options.read_only).await;
+}
+
+/// Forwards the user's input to the [`SerialActor`] in the order it was received,
+/// sending the written data through `sent_tx` as well. Drops the input that
+/// would reach the device when `read_only`.
+async fn forward_input(
+    mut stdin_rx: tokio::sync::mpsc::Receiver<SerialMessage>,
+    command_tx: tokio::sync::mpsc::Sender<SerialMessage>,
+    mut sent_tx: Option<tokio::sync::mpsc::Sender<Vec<u8>>>,
+    read_only: bool,
+) {
+    while let Some(msg) = stdin_rx.recv().await {
+        if read_only
+            && matches!(
+                msg,
+                SerialMessage::Write(_)
+                    | SerialMessage::SendBreak
+                    | SerialMessage::SetDtr(_)
+                    | SerialMessage::SetRts(_)
+            )
         {
+            continue;
+        }
+        if let (Some(tx), SerialMessage::Write(data)) = (&sent_tx, &msg)
+            && tx.send(data.clone()).await.is_err()
+        {
+            sent_tx = None;
+        }
+        if command_tx.send(msg).await.is_err() {
             break;
         }
     }
@@ -148,12 +719,208 @@ pub async fn run_stdin_input(
 
 #[instrument(skip_all, name = "Stdin Input")]
 fn stdin_input_loop(
-    stdin_tx: tokio::sync::mpsc::Sender<String>,
+    stdin_tx: tokio::sync::mpsc::Sender<SerialMessage>,
     command_tx: tokio::sync::mpsc::Sender<SerialMessage>,
     ui_tx: tokio::sync::mpsc::Sender<UICommand>,
+    ui_state: std::sync::Arc<UIState>,
+    options: InputOptions,
 ) {
-    while let Ok(event) = event::read() {
+    let config = crate::configs::get_config();
+    let fkey_macros = crate::configs::function_key_macros(&config.macros);
+    let mut key_actions = config.keybindings.actions();
+    let mut history = options.local_history.then(InputHistory::default);
+    if history.is_none() {
+        // Leave the keys to the device when there is no history to recall
+        key_actions
+            .retain(|_, action| !matches!(action, KeyAction::HistoryPrev | KeyAction::HistoryNext));
+    }
+    // The prompt that is open and the text typed into it
+    let mut prompt: Option<(InputPrompt, String)> = None;
+    // A large paste waiting for the user to confirm sending it
+    let mut pending_paste: Option<Vec<u8>> = None;
+    // The paste being sent in chunks, the next paste is sent after it
+    let mut paste_task: Option<tokio::task::JoinHandle<bool>> = None;
+    // Whether the list of keys is shown, it's hidden by the next key pressed
+    let mut help_shown = false;
+    // The states of the control lines, which are set when opening the port
+    // or left by a device reset
+    let (mut dtr, mut rts) = (options.dtr, options.rts);
+    // For the tasks that send to the device without blocking the keys
+    let runtime = tokio::runtime::Handle::current();
+
+    loop {
+        // Poll so that the loop can exit when the session has been shutdown
+        // by something other than the user, i.e. `--max-duration`
+        match event::poll(std::time::Duration::from_millis(100)) {
+            Ok(true) => {}
+            Ok(false) if command_tx.is_closed() => break,
+            Ok(false) => continue,
+            Err(_) => break,
+        }
+        let Ok(event) = event::read() else {
+            break;
+        };
         tracing::debug!("Read: '{:?}'", event);
+        if let Some(paste) = pending_paste.as_ref() {
+            if let Event::Key(KeyEvent {
+                code,
+                kind: crossterm::event::KeyEventKind::Press,
+                ..
+            }) = event
+            {
+                let confirmed = matches!(code, KeyCode::Char('y' | 'Y') | KeyCode::Enter);
+                if ui_tx.blocking_send(UICommand::HidePrompt).is_err() {
+                    break;
+                }
+                if confirmed {
+                    let previous = paste_task.take();
+                    let paste =
+                        send_paste(stdin_tx.clone(), paste.clone(), &config.defaults, previous);
+                    paste_task = Some(runtime.spawn(paste));
+                }
+                pending_paste = None;
+            }
+            continue;
+        }
+        if let Some((kind, input)) = prompt.as_mut() {
+            // Keys pressed with Ctrl or Alt, i.e. `Ctrl+C`, are ignored rather than typed
+            if let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind: crossterm::event::KeyEventKind::Press,
+                ..
+            }) = event
+                && !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+            {
+                let mut label = kind.label();
+                match code {
+                    KeyCode::Char(c) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Esc => prompt = None,
+                    KeyCode::Enter => match kind.submit(input.trim()) {
+                        Some(ui_command) => {
+                            let _ = ui_tx.blocking_send(ui_command);
+                            prompt = None;
+                        }
+                        None => {
+                            label = kind.invalid_label();
+                            input.clear();
+                        }
+                    },
+                    _ => {}
+                }
+                let ui_command = match &prompt {
+                    Some((_, input)) => UICommand::ShowPrompt(format!("{label}{input}")),
+                    None => UICommand::HidePrompt,
+                };
+                if ui_tx.blocking_send(ui_command).is_err() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if help_shown
+            && let Event::Key(KeyEvent {
+                kind: crossterm::event::KeyEventKind::Press,
+                ..
+            }) = event
+        {
+            help_shown = false;
+            if ui_tx.blocking_send(UICommand::ToggleHelp).is_err() {
+                break;
+            }
+            continue;
+        }
+        if let Event::Key(key) = &event
+            && key.kind == crossterm::event::KeyEventKind::Press
+            && let Some(&action) = key_actions.get(&KeySpec::from(key))
+            // Macros bound to a function key take precedence over the keybindings
+            && !matches!(key.code, KeyCode::F(f_code) if fkey_macros.contains_key(&f_code))
+        {
+            let ui_command = match action {
+                KeyAction::Quit => {
+                    let _ = command_tx.blocking_send(SerialMessage::Shutdown);
+                    break;
+                }
+                KeyAction::Break => {
+                    let _ = stdin_tx.blocking_send(SerialMessage::SendBreak);
+                    continue;
+                }
+                KeyAction::Clear => match config.keybindings.ctrl_l {
+                    crate::configs::ClearAction::ClearScreen => UICommand::ClearScreen,
+                    crate::configs::ClearAction::ClearBuffer => UICommand::ClearBuffer,
+                },
+                KeyAction::ScrollTop => UICommand::ScrollTop,
+                KeyAction::ScrollBottom => UICommand::ScrollBottom,
+                KeyAction::Copy => UICommand::CopySelection,
+                KeyAction::Help => {
+                    help_shown = true;
+                    UICommand::ToggleHelp
+                }
+                KeyAction::ToggleDtr | KeyAction::ToggleRts => {
+                    let (name, state, msg) = match action {
+                        KeyAction::ToggleDtr => {
+                            dtr = !dtr;
+                            ("DTR", dtr, SerialMessage::SetDtr(dtr))
+                        }
+                        _ => {
+                            rts = !rts;
+                            ("RTS", rts, SerialMessage::SetRts(rts))
+                        }
+                    };
+                    if stdin_tx.blocking_send(msg).is_err() {
+                        break;
+                    }
+                    let state = if state { "on" } else { "off" };
+                    UICommand::ShowNotice(format!("{name} {state}"))
+                }
+                KeyAction::Search
+                | KeyAction::DisplayFilter
+                | KeyAction::Color
+                | KeyAction::DumpScrollback => {
+                    let kind = match action {
+                        KeyAction::Search => InputPrompt::Search,
+                        KeyAction::DisplayFilter => InputPrompt::DisplayFilter,
+                        KeyAction::Color => InputPrompt::Color,
+                        _ => InputPrompt::DumpScrollback,
+                    };
+                    prompt = Some((kind, String::new()));
+                    UICommand::ShowPrompt(kind.label().to_string())
+                }
+                KeyAction::SearchNext => UICommand::SearchNext,
+                KeyAction::SearchPrev => UICommand::SearchPrev,
+                KeyAction::ToggleTimestamps => UICommand::ToggleTimestamps,
+                KeyAction::ToggleGraph => UICommand::ToggleThroughput,
+                KeyAction::ToggleControlChars => UICommand::ToggleControlChars,
+                KeyAction::ToggleHexView => UICommand::ToggleHexView,
+                KeyAction::Reset => {
+                    let reset = config.defaults.reset_sequence.as_bytes();
+                    if !reset.is_empty() {
+                        let _ = stdin_tx.blocking_send(SerialMessage::Write(reset.to_vec()));
+                    }
+                    UICommand::Reset
+                }
+                KeyAction::HistoryPrev | KeyAction::HistoryNext => {
+                    let Some(history) = history.as_mut() else {
+                        continue;
+                    };
+                    let recalled = match action {
+                        KeyAction::HistoryPrev => history.older(),
+                        _ => history.newer(),
+                    };
+                    if let Some(data) = recalled
+                        && stdin_tx.blocking_send(SerialMessage::Write(data)).is_err()
+                    {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let _ = ui_tx.blocking_send(ui_command);
+            continue;
+        }
         match event {
             // Match function keys
             Event::Key(KeyEvent {
@@ -165,32 +932,18 @@ fn stdin_input_loop(
                 if kind != crossterm::event::KeyEventKind::Press {
                     continue;
                 }
-                match f_code {
-                    1 => {
-                        let _ = ui_tx.blocking_send(UICommand::ScrollTop);
-                    }
-                    2 => {
-                        let _ = ui_tx.blocking_send(UICommand::ScrollBottom);
-                    }
-                    _ => {}
-                };
+                // Sent from a task so that the keys keep working during the delays,
+                // `forward_input` sends the commands through `sent_tx`
+                if let Some(&m) = fkey_macros.get(&f_code) {
+                    runtime.spawn(run_macro(stdin_tx.clone(), None, m));
+                }
                 continue;
             }
-            // Match Alt + Code
+            // Alt keys that aren't bound to an action aren't sent to the device
             Event::Key(KeyEvent {
-                code,
                 modifiers: KeyModifiers::ALT,
-                kind,
                 ..
-            }) => {
-                if kind != crossterm::event::KeyEventKind::Press {
-                    continue;
-                }
-                if let KeyCode::Char('b') = code {
-                    let _ = command_tx.blocking_send(SerialMessage::SendBreak);
-                };
-                continue;
-            }
+            }) => continue,
             // Match Control + Code
             Event::Key(KeyEvent {
                 code,
@@ -201,19 +954,12 @@ fn stdin_input_loop(
                 if kind != crossterm::event::KeyEventKind::Press {
                     continue;
                 }
-                match code {
-                    KeyCode::Char('c') => {
-                        let _ = stdin_tx.blocking_send(UTF_CTRL_C.to_string());
-                    }
-                    KeyCode::Char('l') => {
-                        let _ = ui_tx.blocking_send(UICommand::ClearBuffer);
-                    }
-                    KeyCode::Char('q') => {
-                        let _ = command_tx.blocking_send(SerialMessage::Shutdown);
-                        break;
-                    }
-                    _ => {}
-                };
+                if let Some(history) = history.as_mut() {
+                    history.discard_line();
+                }
+                if code == KeyCode::Char('c') {
+                    let _ = stdin_tx.blocking_send(SerialMessage::Write(UTF_CTRL_C.into()));
+                }
                 continue;
             }
             // Match every other key
@@ -226,6 +972,11 @@ fn stdin_input_loop(
                 if kind != crossterm::event::KeyEventKind::Press {
                     continue;
                 }
+                // Esc cancels the selection instead of being sent to the device
+                if code == KeyCode::Esc && ui_state.selection_active() {
+                    let _ = ui_tx.blocking_send(UICommand::CancelSelection);
+                    continue;
+                }
                 let data = match code {
                     KeyCode::Tab => UTF_TAB.to_string(),
                     KeyCode::Delete => UTF_DEL.to_string(),
@@ -233,14 +984,35 @@ fn stdin_input_loop(
                     KeyCode::Down => UTF_DOWN_KEY.to_string(),
                     KeyCode::Left => UTF_LEFT_KEY.to_string(),
                     KeyCode::Right => UTF_RIGHT_KEY.to_string(),
+                    KeyCode::Home => config.keybindings.home.clone(),
+                    KeyCode::End => config.keybindings.end.clone(),
+                    KeyCode::Insert => UTF_INSERT_KEY.to_string(),
+                    KeyCode::PageUp | KeyCode::PageDown
+                        if config.keybindings.page_keys == crate::configs::PageKeys::Scroll =>
+                    {
+                        let ui_command = match code {
+                            KeyCode::PageUp => UICommand::PageUp,
+                            _ => UICommand::PageDown,
+                        };
+                        let _ = ui_tx.blocking_send(ui_command);
+                        continue;
+                    }
+                    KeyCode::PageUp => UTF_PAGE_UP_KEY.to_string(),
+                    KeyCode::PageDown => UTF_PAGE_DOWN_KEY.to_string(),
                     KeyCode::Enter => '\r'.to_string(),
                     KeyCode::Backspace => UTF_BKSP.to_string(),
                     KeyCode::Esc => UTF_ESC.to_string(),
                     KeyCode::Char(c) => c.to_string(),
                     _ => continue,
                 };
+                if let Some(history) = history.as_mut() {
+                    history.record(code);
+                }
 
-                if stdin_tx.blocking_send(data).is_err() {
+                if stdin_tx
+                    .blocking_send(SerialMessage::Write(data.into_bytes()))
+                    .is_err()
+                {
                     break;
                 }
             }
@@ -250,6 +1022,7 @@ fn stdin_input_loop(
                 let ui_command = match kind {
                     MouseEventKind::ScrollUp => UICommand::ScrollUp(1),
                     MouseEventKind::ScrollDown => UICommand::ScrollDown(1),
+                    _ if config.defaults.mouse != crate::configs::MouseMode::Full => continue,
                     MouseEventKind::Down(_) => UICommand::StartSelection((column, row).into()),
                     MouseEventKind::Drag(_) => UICommand::UpdateSelection((column, row).into()),
                     MouseEventKind::Up(_) => UICommand::CopySelection,
@@ -260,105 +1033,1731 @@ fn stdin_input_loop(
                 }
             }
             Event::Paste(text) => {
-                if stdin_tx.blocking_send(text).is_err() {
-                    break;
+                if let Some(history) = history.as_mut() {
+                    history.discard_line();
                 }
-            }
-            _ => {} // Ignore other events
-        }
-    }
-}
+                let text = if config.defaults.sanitize_paste {
+                    sanitize_paste(&text)
+                } else {
+                    text
+                };
+                let text = text.into_bytes();
+                if config
+                    .defaults
+                    .paste_confirm_bytes
+                    .is_some_and(|max| text.len() > max)
+                {
+                    let prompt = format!(
+                        "Paste {} bytes? (y to send, any other key to cancel) ",
+                        text.len()
+                    );
+                    if ui_tx.blocking_send(UICommand::ShowPrompt(prompt)).is_err() {
+                        break;
+                    }
+                    pending_paste = Some(text);
+                    continue;
+                }
+                let previous = paste_task.take();
+                let paste = send_paste(stdin_tx.clone(), text, &config.defaults, previous);
+                paste_task = Some(runtime.spawn(paste));
+            }
+            Event::Resize(width, height) => {
+                let sent = ui_tx.blocking_send(UICommand::Resize(width, height));
+                if sent.is_err() {
+                    break;
+                }
+            }
+            _ => {} // Ignore other events
+        }
+    }
+}
 
-/// Responsible for spawning a blocking task with [`tokio::task::spawn_blocking()`]
-/// and forwarding the incoming data received from the [`SerialActor`] to the blocking
-/// task to write to a file.
-#[instrument(name = "File output", skip(file_rx))]
+/// Responsible for formatting the incoming data received from the [`SerialActor`]
+/// and forwarding it to an [`OutputSink`] that writes it to a file, with
+/// [`run_output_sink_with()`] and the file's [`SinkFormat`].
+///
+/// When [`Defaults::idle_gap_ms`][crate::configs::Defaults] is set, a `--- idle 12s ---`
+/// line is written before data that is received after the device has been quiet
+/// for longer than the threshold. When [`Defaults::trim_trailing_ws`][crate::configs::Defaults]
+/// and [`Defaults::file_strip_ansi`][crate::configs::Defaults] are set, trailing
+/// spaces and tabs are removed from each line, a raw capture is written as-is.
+/// Tabs are written as-is unless [`Defaults::expand_tabs_in_file`][crate::configs::Defaults]
+/// is set.
+/// When [`Defaults::timestamp_output`][crate::configs::Defaults] is set, each line
+/// is prefixed with the time it started being received, i.e. `[12:03:44.123] `.
+///
+/// When given a `filter`, only the complete lines that match it are written
+/// and no idle gaps are marked.
+///
+/// When given a `sent_rx`, each line the user sends to the device is written
+/// on its own line prefixed with `>>> `.
+///
+/// When `mask_passwords` is set, what is typed after a password prompt is
+/// replaced with asterisks, see `--insecure-log-passwords`.
+#[instrument(name = "File output", skip(file_rx, filter, sent_rx))]
 pub async fn run_file_output(
-    mut file_rx: tokio::sync::broadcast::Receiver<SerialEvent>,
+    file_rx: tokio::sync::broadcast::Receiver<SerialEvent>,
     file_path: PathBuf,
+    filter: Option<crate::configs::LineFilter>,
+    sent_rx: Option<tokio::sync::mpsc::Receiver<Vec<u8>>>,
+    mask_passwords: bool,
+    on_error_only: bool,
 ) {
-    let (write_tx, write_rx) = std::sync::mpsc::channel::<Vec<u8>>();
-    info!("Creating file: '{}'", file_path.display());
-    let write_handle = tokio::task::spawn_blocking(move || {
-        let file = match File::create(&file_path) {
-            Ok(f) => f,
-            Err(e) => {
-                eprintln!("Failed to create file '{}': {e}", file_path.display());
-                return;
+    // The file may only be created once an error triggers it
+    let started = chrono::Utc::now();
+    let file_bom = crate::configs::get_config().defaults.file_bom;
+    let file_mode = crate::configs::get_config().defaults.file_mode;
+    let max_file_bytes = crate::configs::get_config().defaults.max_file_bytes;
+    let max_files = crate::configs::get_config().defaults.max_files;
+    let mut file_sink = FileSink::new(file_path, started);
+    file_sink.bom = file_bom;
+    file_sink.append = file_mode == crate::configs::FileMode::Append;
+    file_sink.create_on_write = on_error_only;
+    file_sink.max_bytes = max_file_bytes;
+    file_sink.max_files = max_files;
+
+    let format = FileFormat::new(filter, mask_passwords, on_error_only);
+    super::run_output_sink_with(file_rx, file_sink, format, sent_rx).await;
+}
+
+/// The [`SinkFormat`][super::SinkFormat] of [`run_file_output`], see there
+/// for the formatting that is done.
+///
+/// With `--output-on-error-only`, the output is held in [`DeferredOutput`]
+/// until an error pattern is matched or an error occurs.
+#[derive(Debug)]
+struct FileFormat {
+    filtered_lines: Option<FilteredLines>,
+    idle_gap: Option<std::time::Duration>,
+    trimmer: Option<TrailingWhitespace>,
+    tabs: Option<TabExpander>,
+    ansi: Option<AnsiStripper>,
+    timestamps: Option<LineTimestamps>,
+    password_mask: Option<PasswordMask>,
+    sent_lines: SentLines,
+    deferred: Option<DeferredOutput>,
+    error_patterns: Option<LinePatterns>,
+    last_data: Option<tokio::time::Instant>,
+    ends_with_newline: bool,
+}
+
+impl FileFormat {
+    fn new(
+        filter: Option<crate::configs::LineFilter>,
+        mask_passwords: bool,
+        on_error_only: bool,
+    ) -> Self {
+        let defaults = &crate::configs::get_config().defaults;
+        let filtered_lines = filter.filter(|f| !f.is_empty()).map(FilteredLines::new);
+        let idle_gap = defaults
+            .idle_gap_ms
+            .filter(|&ms| ms > 0 && filtered_lines.is_none())
+            .map(std::time::Duration::from_millis);
+        let login = crate::configs::get_config()
+            .active_profile()
+            .and_then(|profile| profile.login.as_ref());
+        Self {
+            filtered_lines,
+            idle_gap,
+            // Only the plain text of the clean-text file is trimmed
+            trimmer: (defaults.trim_trailing_ws && defaults.file_strip_ansi)
+                .then(TrailingWhitespace::default),
+            tabs: defaults.expand_tabs_in_file.then(TabExpander::default),
+            ansi: defaults.file_strip_ansi.then(AnsiStripper::default),
+            timestamps: defaults.timestamp_output.then(LineTimestamps::default),
+            password_mask: mask_passwords.then(|| PasswordMask::new(login)),
+            sent_lines: SentLines::default(),
+            deferred: on_error_only.then(DeferredOutput::default),
+            error_patterns: on_error_only.then(|| LinePatterns::new(&defaults.error_patterns)),
+            last_data: None,
+            ends_with_newline: true,
+        }
+    }
+
+    /// Appends `formatted` to `out`, or holds it back until [`Self::trigger()`] is called.
+    fn emit(&mut self, formatted: Vec<u8>, out: &mut Vec<u8>) {
+        match self.deferred {
+            Some(ref mut deferred) => deferred.push(formatted),
+            None => out.extend_from_slice(&formatted),
+        }
+    }
+
+    /// Appends the deferred output, if any, to `out` and appends all output from now on.
+    fn trigger(&mut self, out: &mut Vec<u8>) {
+        self.error_patterns = None;
+        if let Some(deferred) = self.deferred.take() {
+            info!("Error occurred, writing the deferred output");
+            for chunk in deferred.chunks {
+                out.extend_from_slice(&chunk);
+            }
+        }
+    }
+}
+
+impl super::SinkFormat for FileFormat {
+    fn data(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        if self
+            .error_patterns
+            .as_mut()
+            .is_some_and(|p| p.matches(data))
+        {
+            self.trigger(out);
+        }
+        let mut formatted = Vec::with_capacity(data.len());
+        let now = tokio::time::Instant::now();
+        if let (Some(threshold), Some(last)) = (self.idle_gap, self.last_data) {
+            let gap = now.duration_since(last);
+            if gap > threshold {
+                let newline = if self.ends_with_newline { "" } else { "\r\n" };
+                let marker = format!("{newline}--- idle {} ---\r\n", format_gap(gap));
+                if let Some(ref mut timestamps) = self.timestamps {
+                    timestamps.new_line();
+                }
+                formatted.extend_from_slice(marker.as_bytes());
             }
+        }
+        self.last_data = Some(now);
+        self.ends_with_newline = data.last() == Some(&b'\n');
+        let data: std::borrow::Cow<[u8]> = match self.ansi {
+            Some(ref mut ansi) => ansi.strip(data).into(),
+            None => data.into(),
         };
-        let mut writer = BufWriter::with_capacity(8 * 1024, file);
-        let mut last_flush = std::time::Instant::now();
-
-        writeln!(writer, "Session started at: {}", chrono::Utc::now()).ok();
-        while let Ok(data) = write_rx.recv() {
-            writer.write_all(&data).ok();
-            let now = std::time::Instant::now();
-            if now.duration_since(last_flush) > std::time::Duration::from_millis(200)
-                || writer.buffer().len() > 4 * 1024
-            {
-                let _ = writer.flush();
-                last_flush = now;
+        let data: std::borrow::Cow<[u8]> = match self.password_mask {
+            Some(ref mut mask) => mask.mask(&data).into(),
+            None => data,
+        };
+        let data: std::borrow::Cow<[u8]> = match self.filtered_lines {
+            Some(ref mut lines) => lines.filter(&data).into(),
+            None => data,
+        };
+        let data: std::borrow::Cow<[u8]> = match self.tabs {
+            Some(ref mut tabs) => tabs.expand(&data).into(),
+            None => data,
+        };
+        match (&mut self.trimmer, &mut self.timestamps) {
+            (Some(trimmer), Some(timestamps)) => {
+                let mut trimmed = Vec::with_capacity(data.len());
+                trimmer.trim_into(&data, &mut trimmed);
+                timestamps.stamp_into(&trimmed, &mut formatted, &line_timestamp());
+            }
+            (Some(trimmer), None) => trimmer.trim_into(&data, &mut formatted),
+            (None, Some(timestamps)) => {
+                timestamps.stamp_into(&data, &mut formatted, &line_timestamp());
             }
+            (None, None) => formatted.extend_from_slice(&data),
         }
-        let _ = writer.flush();
-    });
+        self.emit(formatted, out);
+    }
 
-    let data_streamer = tokio::spawn(async move {
-        let mut write_buf = Vec::with_capacity(4096);
-        let mut batch_timer = tokio::time::interval(tokio::time::Duration::from_millis(200));
+    fn sent(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        let mut formatted = Vec::new();
+        for line in self.sent_lines.push(data) {
+            let line = match self.password_mask {
+                Some(ref mut mask) => mask.mask_sent(line),
+                None => line,
+            };
+            if !self.ends_with_newline {
+                formatted.extend_from_slice(b"\r\n");
+            }
+            if let Some(ref mut timestamps) = self.timestamps {
+                LineTimestamps::write_stamp(&mut formatted, &line_timestamp());
+                timestamps.new_line();
+            }
+            formatted.extend_from_slice(b">>> ");
+            formatted.extend_from_slice(&line);
+            formatted.extend_from_slice(b"\r\n");
+            self.ends_with_newline = true;
+        }
+        if !formatted.is_empty() {
+            self.emit(formatted, out);
+        }
+    }
 
-        loop {
-            tokio::select! {
-                event = file_rx.recv() => {
-                    match event {
-                        Ok(SerialEvent::Data(data)) => {
-                            write_buf.extend_from_slice(&data);
-                            if write_buf.len() >= 4096 && write_tx.send(std::mem::take(&mut write_buf)).is_err() {
-                                    break;
-                            }
-                        }
-                        Ok(SerialEvent::Error(e)) => {
-                            if !write_buf.is_empty() {
-                                if write_tx.send(std::mem::take(&mut write_buf)).is_err() {
-                                    break;
-                                }
-                                write_buf.clear();
-                            }
-                            let error_msg = format!("\r\n[ERROR {}] {e}\r\n", chrono::Utc::now());
-                            let _ = write_tx.send(error_msg.into_bytes());
-                        }
-                        Ok(SerialEvent::ConnectionClosed) => {
-                            if !write_buf.is_empty() {
-                                if write_tx.send(std::mem::take(&mut write_buf)).is_err() {
-                                    break;
-                                }
-                                write_buf.clear();
-                            }
-                            let close_msg = format!("\r\n[CLOSED {}] Connection closed.\r\n", chrono::Utc::now());
-                            let _ = write_tx.send(close_msg.into_bytes());
+    fn event(&mut self, event: &SerialEvent, out: &mut Vec<u8>) {
+        let (tag, msg) = match event {
+            SerialEvent::Error(_) => return self.trigger(out),
+            SerialEvent::Reconnecting => ("RECONNECTING", "Connection lost, reconnecting."),
+            SerialEvent::Reconnected => ("RECONNECTED", "Reconnected."),
+            _ => return,
+        };
+        let line = format!("\r\n[{tag} {}] {msg}\r\n", chrono::Utc::now());
+        self.emit(line.into_bytes(), out);
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        if let Some(ref mut lines) = self.filtered_lines {
+            let rest = lines.finish();
+            self.emit(rest, out);
+        }
+    }
+}
+
+/// The [`OutputSink`][super::OutputSink] of [`run_file_output`]. With `create_on_write`,
+/// for `--output-on-error-only`, the file is only created once output is written to it.
+/// With `append`, an existing file is added to instead of being overwritten.
+///
+/// With `max_bytes`, the file is closed once it grows past that size and the output
+/// continues in `<path>.1`, `<path>.2`, etc.
+#[derive(Debug)]
+struct FileSink {
+    path: PathBuf,
+    /// When the session started, written in the file's header.
+    started: chrono::DateTime<chrono::Utc>,
+    bom: bool,
+    append: bool,
+    create_on_write: bool,
+    max_bytes: Option<u64>,
+    /// The most files written, including the first one, see [`FileSink::rotate_if_full()`].
+    max_files: Option<u32>,
+    writer: Option<BufWriter<File>>,
+    /// The size of the file currently written to.
+    file_bytes: u64,
+    /// The size of the file currently written to once its header was written,
+    /// to tell whether any output was written to it.
+    opened_bytes: u64,
+    /// The suffix of the current file, `0` for the first one.
+    rotations: u32,
+    /// The numbered files written to by this session, oldest first.
+    rotated: std::collections::VecDeque<PathBuf>,
+}
+
+impl FileSink {
+    fn new(path: PathBuf, started: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            path,
+            started,
+            bom: false,
+            append: false,
+            create_on_write: false,
+            max_bytes: None,
+            max_files: None,
+            writer: None,
+            file_bytes: 0,
+            opened_bytes: 0,
+            rotations: 0,
+            rotated: std::collections::VecDeque::new(),
+        }
+    }
+
+    /// Returns the path of the file currently written to.
+    fn current_path(&self) -> PathBuf {
+        match self.rotations {
+            0 => self.path.clone(),
+            n => {
+                let mut path = self.path.clone().into_os_string();
+                path.push(format!(".{n}"));
+                PathBuf::from(path)
+            }
+        }
+    }
+
+    fn writer(&mut self) -> std::io::Result<&mut BufWriter<File>> {
+        if self.writer.is_none() {
+            let path = self.current_path();
+            info!("Creating file: '{}'", path.display());
+            let file = match self.append {
+                true => std::fs::OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&path),
+                false => File::create(&path),
+            }
+            .map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("Failed to create file '{}': {e}", path.display()),
+                )
+            })?;
+            self.file_bytes = file.metadata()?.len();
+            // An empty file starts like a new one, i.e. with the BOM
+            let resumed = self.append && self.file_bytes > 0;
+            let mut header = Vec::new();
+            match resumed {
+                true => write_resumed_header(&mut header, self.started)?,
+                false => write_file_header(&mut header, self.bom, self.started)?,
+            }
+            let mut writer = BufWriter::with_capacity(8 * 1024, file);
+            writer.write_all(&header)?;
+            self.file_bytes += header.len() as u64;
+            // A file resumed with `append` may already be full
+            self.opened_bytes = match resumed {
+                true => 0,
+                false => self.file_bytes,
+            };
+            self.writer = Some(writer);
+        }
+        Ok(self.writer.as_mut().expect("Created above"))
+    }
+
+    /// Closes the current file and moves on to the next one once writing `len`
+    /// more bytes would grow it past `max_bytes`. A file always gets at least
+    /// one write, so a single large write isn't rotated forever.
+    ///
+    /// Numbered files that already exist, i.e. from an earlier session, are
+    /// skipped. Past `max_files`, the oldest numbered file of this session is
+    /// deleted; the first file is kept as it is the one given to the exit script.
+    fn rotate_if_full(&mut self, len: usize) -> std::io::Result<()> {
+        let Some(max_bytes) = self.max_bytes else {
+            return Ok(());
+        };
+        let has_data = self.writer.is_some() && self.file_bytes > self.opened_bytes;
+        if has_data && self.file_bytes + len as u64 > max_bytes {
+            if let Some(mut writer) = self.writer.take() {
+                writer.flush()?;
+            }
+            self.rotations += 1;
+            while self.current_path().exists() {
+                self.rotations += 1;
+            }
+            info!("Rotating to file: '{}'", self.current_path().display());
+            self.rotated.push_back(self.current_path());
+            let max_rotated = self
+                .max_files
+                .map_or(usize::MAX, |n| (n as usize).saturating_sub(1));
+            while self.rotated.len() > max_rotated {
+                let Some(oldest) = self.rotated.pop_front() else {
+                    break;
+                };
+                info!("Deleting the oldest file: '{}'", oldest.display());
+                if let Err(e) = std::fs::remove_file(&oldest) {
+                    tracing::warn!("Failed to delete '{}': {e}", oldest.display());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl super::OutputSink for FileSink {
+    fn on_open(&mut self) -> std::io::Result<()> {
+        if !self.create_on_write {
+            self.writer()?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.rotate_if_full(data.len())?;
+        self.writer()?.write_all(data)?;
+        self.file_bytes += data.len() as u64;
+        Ok(())
+    }
+
+    fn on_error(&mut self, error: &str) -> std::io::Result<()> {
+        let error_msg = format!("\r\n[ERROR {}] {error}\r\n", chrono::Utc::now());
+        self.write(error_msg.as_bytes())
+    }
+
+    fn on_close(&mut self) -> std::io::Result<()> {
+        // Without any output, there is no file to close
+        if self.writer.is_none() && self.rotations == 0 {
+            return Ok(());
+        }
+        let close_msg = format!("\r\n[CLOSED {}] Connection closed.\r\n", chrono::Utc::now());
+        self.write(close_msg.as_bytes())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        if self.writer.is_none() && self.create_on_write {
+            info!("No errors occurred, discarding: '{}'", self.path.display());
+        }
+    }
+}
+
+/// The UTF-8 byte order mark, written at the start of files with
+/// [`file_bom`][crate::configs::Defaults::file_bom].
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Writes the line that starts every file with the time the session `started`,
+/// preceded by [`UTF8_BOM`] when `bom` is set.
+fn write_file_header<W: Write>(
+    writer: &mut W,
+    bom: bool,
+    started: chrono::DateTime<chrono::Utc>,
+) -> std::io::Result<()> {
+    if bom {
+        writer.write_all(UTF8_BOM)?;
+    }
+    writeln!(writer, "Session started at: {started}")
+}
+
+/// Writes the line separating the sessions of a file that is appended to,
+/// see [`FileMode::Append`][crate::configs::FileMode::Append].
+fn write_resumed_header<W: Write>(
+    writer: &mut W,
+    started: chrono::DateTime<chrono::Utc>,
+) -> std::io::Result<()> {
+    writeln!(writer, "\r\n--- Session resumed at {started} ---")
+}
+
+/// The output of a session held in memory for `--output-on-error-only`, dropping
+/// the oldest output past [`DeferredOutput::MAX_LEN`].
+#[derive(Debug, Default)]
+struct DeferredOutput {
+    chunks: std::collections::VecDeque<Vec<u8>>,
+    len: usize,
+}
+
+impl DeferredOutput {
+    /// The most output that is held in memory, in bytes.
+    const MAX_LEN: usize = 16 * 1024 * 1024;
+
+    fn push(&mut self, data: Vec<u8>) {
+        self.len += data.len();
+        self.chunks.push_back(data);
+        while self.len > Self::MAX_LEN
+            && let Some(chunk) = self.chunks.pop_front()
+        {
+            self.len -= chunk.len();
+        }
+    }
+}
+
+/// Matches the lines received from the device against patterns, i.e. the
+/// [`error_patterns`][crate::configs::Defaults::error_patterns].
+///
+/// Lines that are split between chunks of data are held until they are complete.
+#[derive(Debug)]
+struct LinePatterns {
+    patterns: &'static [crate::configs::Pattern],
+    line: Vec<u8>,
+}
+
+impl LinePatterns {
+    /// The longest line that is matched, the rest of the line is ignored.
+    const MAX_LINE: usize = 4096;
+
+    fn new(patterns: &'static [crate::configs::Pattern]) -> Self {
+        Self {
+            patterns,
+            line: Vec::new(),
+        }
+    }
+
+    /// Returns whether a line completed by `data` matches one of the patterns.
+    fn matches(&mut self, data: &[u8]) -> bool {
+        self.matching_line(data).is_some()
+    }
+
+    /// Returns the first line completed by `data` that matches one of the patterns.
+    fn matching_line(&mut self, data: &[u8]) -> Option<String> {
+        if self.patterns.is_empty() {
+            return None;
+        }
+        let mut matched = None;
+        for &byte in data {
+            if byte == b'\n' {
+                let line = String::from_utf8_lossy(&self.line);
+                let line = line.trim_end_matches('\r');
+                if matched.is_none() && self.patterns.iter().any(|p| p.0.is_match(line)) {
+                    matched = Some(line.to_string());
+                }
+                self.line.clear();
+            } else if self.line.len() < Self::MAX_LINE {
+                self.line.push(byte);
+            }
+        }
+        matched
+    }
+}
+
+/// Alerts the user when a line received from the device matches
+/// [`notify_on`][crate::configs::Defaults::notify_on], see [`run_stdout_output`].
+#[derive(Debug)]
+struct Notifier {
+    stripper: AnsiStripper,
+    lines: LinePatterns,
+    command: Option<&'static std::path::Path>,
+    /// The last notify command, one isn't run while the last one is still running.
+    running: Option<tokio::process::Child>,
+}
+
+impl Notifier {
+    /// Returns `None` when there are no `patterns`.
+    fn new(
+        patterns: &'static [crate::configs::Pattern],
+        command: Option<&'static std::path::Path>,
+    ) -> Option<Self> {
+        (!patterns.is_empty()).then(|| Self {
+            stripper: AnsiStripper::default(),
+            lines: LinePatterns::new(patterns),
+            command,
+            running: None,
+        })
+    }
+
+    /// Returns whether a line completed by `data` matches, running the notify
+    /// command for the first line that does.
+    fn push(&mut self, data: &[u8]) -> bool {
+        let text = self.stripper.strip(data);
+        let Some(line) = self.lines.matching_line(&text) else {
+            return false;
+        };
+        let still_running = self
+            .running
+            .as_mut()
+            .is_some_and(|child| matches!(child.try_wait(), Ok(None)));
+        if let Some(command) = self.command
+            && !still_running
+        {
+            match crate::cli::spawn_notify_command(command, &line) {
+                Ok(child) => self.running = Some(child),
+                Err(e) => tracing::warn!("Failed to run the notify command: {e}"),
+            }
+        }
+        true
+    }
+}
+
+/// Collects the data sent by the user into the lines (commands) that were sent,
+/// applying backspaces and dropping control characters and escape sequences.
+#[derive(Debug, Default)]
+struct SentLines {
+    line: Vec<u8>,
+    in_escape: bool,
+}
+
+impl SentLines {
+    /// Returns the lines that were completed with a carriage return or newline.
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut lines = Vec::new();
+        for &byte in data {
+            if self.in_escape {
+                // Escape sequences end with a byte within `@`..=`~`, which
+                // doesn't include the `[` and `O` that start them
+                self.in_escape = !(0x40..=0x7E).contains(&byte) || byte == b'[' || byte == b'O';
+                continue;
+            }
+            match byte {
+                b'\r' | b'\n' => {
+                    if !self.line.is_empty() {
+                        lines.push(std::mem::take(&mut self.line));
+                    }
+                }
+                0x08 | 0x7F => {
+                    // Remove the whole last UTF-8 character
+                    while let Some(b) = self.line.pop() {
+                        if b & 0xC0 != 0x80 {
                             break;
                         }
-                        Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
-                            eprintln!("File writer lagged, skipped {skipped} messages");
-                            continue; // Don't break on lag
-                        }
-                        _ => break,
                     }
                 }
-                _ = batch_timer.tick() => {
-                    if !write_buf.is_empty() && write_tx.send(std::mem::take(&mut write_buf)).is_err() {
-                            break;
+                0x1B => self.in_escape = true,
+                b'\t' => self.line.push(byte),
+                b if b < 0x20 => {}
+                b => self.line.push(b),
+            }
+        }
+        lines
+    }
+}
+
+/// Only lets the lines that match a [`LineFilter`][crate::configs::LineFilter]
+/// through to the file.
+///
+/// Lines that are split between chunks of data are held until they are complete,
+/// a line longer than [`LinePatterns::MAX_LINE`] is matched by its start.
+#[derive(Debug)]
+struct FilteredLines {
+    filter: crate::configs::LineFilter,
+    partial: Vec<u8>,
+    /// Whether the rest of a line that was too long to hold is let through.
+    long_line: Option<bool>,
+}
+
+impl FilteredLines {
+    fn new(filter: crate::configs::LineFilter) -> Self {
+        Self {
+            filter,
+            partial: Vec::new(),
+            long_line: None,
+        }
+    }
+
+    /// Returns the complete lines of `data` that match the filter.
+    fn filter(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for line in data.split_inclusive(|&b| b == b'\n') {
+            let complete = line.ends_with(b"\n");
+            if let Some(matched) = self.long_line {
+                if matched {
+                    out.extend_from_slice(line);
+                }
+                if complete {
+                    self.long_line = None;
+                }
+                continue;
+            }
+            self.partial.extend_from_slice(line);
+            if complete {
+                self.push_if_matches(&mut out);
+            } else if self.partial.len() >= LinePatterns::MAX_LINE {
+                self.long_line = Some(self.push_if_matches(&mut out));
+            }
+        }
+        out
+    }
+
+    /// Returns the last, incomplete, line if it matches the filter.
+    fn finish(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.long_line = None;
+        if !self.partial.is_empty() {
+            self.push_if_matches(&mut out);
+        }
+        out
+    }
+
+    /// Moves the held line to `out` if it matches the filter, returning whether it did.
+    fn push_if_matches(&mut self, out: &mut Vec<u8>) -> bool {
+        let line = String::from_utf8_lossy(&self.partial);
+        let matched = self.filter.matches(line.trim_end_matches(['\r', '\n']));
+        if matched {
+            out.append(&mut self.partial);
+        }
+        self.partial.clear();
+        matched
+    }
+}
+
+/// Expands tabs into spaces up to the next tab stop as data is written to a file,
+/// matching how tabs are displayed on the screen.
+#[derive(Debug, Default)]
+struct TabExpander {
+    column: usize,
+}
+
+impl TabExpander {
+    fn expand(&mut self, data: &[u8]) -> Vec<u8> {
+        let tab_width = crate::screen_buffer::TAB_WIDTH as usize;
+        let mut out = Vec::with_capacity(data.len());
+        for &byte in data {
+            match byte {
+                b'\t' => {
+                    let spaces = tab_width - self.column % tab_width;
+                    out.extend(std::iter::repeat_n(b' ', spaces));
+                    self.column += spaces;
+                }
+                b'\r' | b'\n' => {
+                    self.column = 0;
+                    out.push(byte);
+                }
+                _ => {
+                    // Only count the first byte of multi-byte UTF-8 characters
+                    if byte & 0xC0 != 0x80 {
+                        self.column += 1;
                     }
+                    out.push(byte);
                 }
             }
         }
-        if !write_buf.is_empty() {
-            let _ = write_tx.send(std::mem::take(&mut write_buf));
+        out
+    }
+}
+
+/// Where an [`AnsiStripper`] is within the data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum StripState {
+    #[default]
+    Text,
+    /// Just received an ESC (0x1B)
+    Esc,
+    /// Within an `ESC [` (CSI) sequence
+    Csi,
+    /// Within a string such as an `ESC ]` (OSC) title, ended by BEL or `ESC \`
+    String,
+    /// Received an ESC within a string, which ends it when followed by `\`
+    StringEsc,
+}
+
+/// Removes escape sequences and control characters, other than newlines, carriage
+/// returns and tabs, from data written to a file, see
+/// [`Defaults::file_strip_ansi`][crate::configs::Defaults::file_strip_ansi].
+///
+/// This is separate from the [`ScreenBuffer`]'s parsing so that the file and the
+/// screen don't interfere with each other. Sequences that are split between
+/// chunks of data are removed as well.
+#[derive(Debug, Default)]
+struct AnsiStripper {
+    state: StripState,
+}
+
+impl AnsiStripper {
+    fn strip(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        for &byte in data {
+            self.state = match (self.state, byte) {
+                (StripState::Text, 0x1B) => StripState::Esc,
+                (StripState::Text, b'\r' | b'\n' | b'\t') => {
+                    out.push(byte);
+                    StripState::Text
+                }
+                (StripState::Text, b) if b < 0x20 || b == 0x7F => StripState::Text,
+                (StripState::Text, b) => {
+                    out.push(b);
+                    StripState::Text
+                }
+                (StripState::Esc, b'[') => StripState::Csi,
+                // OSC, DCS, SOS, PM and APC are strings
+                (StripState::Esc, b']' | b'P' | b'X' | b'^' | b'_') => StripState::String,
+                // Intermediate bytes, i.e. the `(` in `ESC ( B`
+                (StripState::Esc, 0x20..=0x2F) => StripState::Esc,
+                (StripState::Esc, _) => StripState::Text,
+                // CSI sequences end with a byte within `@`..=`~`
+                (StripState::Csi, 0x40..=0x7E) => StripState::Text,
+                (StripState::Csi, _) => StripState::Csi,
+                (StripState::String, 0x07) => StripState::Text,
+                (StripState::String | StripState::StringEsc, 0x1B) => StripState::StringEsc,
+                (StripState::StringEsc, b'\\') => StripState::Text,
+                (StripState::String | StripState::StringEsc, _) => StripState::String,
+            };
+        }
+        out
+    }
+}
+
+/// Removes the spaces and tabs at the end of lines as data is written to a file.
+///
+/// Whitespace is held back until it is known whether more text follows it on the
+/// same line, so lines that are split between chunks of data are trimmed correctly.
+#[derive(Debug, Default)]
+struct TrailingWhitespace {
+    pending: Vec<u8>,
+}
+
+impl TrailingWhitespace {
+    /// Appends `data` to `out` without the whitespace at the end of its lines.
+    fn trim_into(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        for &byte in data {
+            match byte {
+                b' ' | b'\t' => self.pending.push(byte),
+                b'\r' | b'\n' => {
+                    self.pending.clear();
+                    out.push(byte);
+                }
+                _ => {
+                    out.append(&mut self.pending);
+                    out.push(byte);
+                }
+            }
+        }
+    }
+}
+
+/// Prefixes the lines written to a file with the time they started being received.
+///
+/// Lines are split between chunks of data, so whether the next byte starts a new
+/// line is kept across chunks and lines are only stamped after a `\n`.
+#[derive(Debug)]
+struct LineTimestamps {
+    at_line_start: bool,
+}
+
+impl Default for LineTimestamps {
+    fn default() -> Self {
+        Self {
+            at_line_start: true,
+        }
+    }
+}
+
+impl LineTimestamps {
+    /// Appends `data` to `out`, prefixing the lines that start within it with `time`.
+    fn stamp_into(&mut self, data: &[u8], out: &mut Vec<u8>, time: &str) {
+        for &byte in data {
+            if self.at_line_start {
+                Self::write_stamp(out, time);
+                self.at_line_start = false;
+            }
+            out.push(byte);
+            self.at_line_start = byte == b'\n';
+        }
+    }
+
+    /// Marks that a line was completed by something else than the device's data,
+    /// i.e. an idle marker, so that the device's next line is stamped.
+    fn new_line(&mut self) {
+        self.at_line_start = true;
+    }
+
+    fn write_stamp(out: &mut Vec<u8>, time: &str) {
+        out.push(b'[');
+        out.extend_from_slice(time.as_bytes());
+        out.extend_from_slice(b"] ");
+    }
+}
+
+/// The local time used by [`LineTimestamps`], i.e. `12:03:44.123`.
+fn line_timestamp() -> String {
+    chrono::Local::now().format("%H:%M:%S%.3f").to_string()
+}
+
+/// Formats the length of an idle gap for the file output, i.e. `12s` or `750ms`.
+fn format_gap(gap: std::time::Duration) -> String {
+    if gap.as_secs() > 0 {
+        format!("{}s", gap.as_secs())
+    } else {
+        format!("{}ms", gap.as_millis())
+    }
+}
+
+/// The end of the text received from the device, used to match prompts against
+/// i.e. `Password:`.
+#[derive(Debug, Default)]
+struct PromptTail {
+    tail: String,
+}
+
+impl PromptTail {
+    /// The amount of received text that is kept around to match prompts against.
+    const MAX_TAIL: usize = 256;
+
+    fn push(&mut self, data: &[u8]) {
+        self.tail
+            .push_str(&String::from_utf8_lossy(data).to_lowercase());
+        if self.tail.len() > Self::MAX_TAIL {
+            let mut cut = self.tail.len() - Self::MAX_TAIL;
+            while !self.tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.tail.drain(..cut);
+        }
+    }
+
+    /// Returns whether the received text ends with `prompt`, ignoring trailing
+    /// whitespace. `prompt` must be lowercase.
+    fn ends_with(&self, prompt: &str) -> bool {
+        self.tail.trim_end().ends_with(prompt)
+    }
+
+    fn clear(&mut self) {
+        self.tail.clear();
+    }
+}
+
+/// Masks what is typed after a password prompt with asterisks as data is
+/// written to a file, unless `--insecure-log-passwords` is given.
+///
+/// After a password prompt is received, the characters received up to the end
+/// of the line (i.e. the device echoing the password) and the next line sent
+/// by the user are masked.
+#[derive(Debug)]
+struct PasswordMask {
+    prompts: Vec<String>,
+    tail: PromptTail,
+    masking: bool,
+}
+
+impl PasswordMask {
+    /// Prompts that are masked along with the password prompt of the active
+    /// profile's [`Login`][crate::configs::Login].
+    const PROMPTS: [&str; 2] = ["password:", "passphrase:"];
+
+    fn new(login: Option<&crate::configs::Login>) -> Self {
+        let mut prompts: Vec<String> = Self::PROMPTS.iter().map(|p| p.to_string()).collect();
+        if let Some(login) = login {
+            prompts.push(login.password_prompt.to_lowercase());
+        }
+        Self {
+            prompts,
+            tail: PromptTail::default(),
+            masking: false,
+        }
+    }
+
+    /// Masks the received data that follows a password prompt.
+    fn mask(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        for &byte in data {
+            match byte {
+                b'\r' | b'\n' => {
+                    self.masking = false;
+                    out.push(byte);
+                }
+                // Only one `*` for multi-byte UTF-8 characters
+                b if self.masking && b & 0xC0 == 0x80 => {}
+                b if self.masking && b >= 0x20 && b != 0x7F => out.push(b'*'),
+                b => out.push(b),
+            }
+        }
+        self.tail.push(data);
+        if self
+            .prompts
+            .iter()
+            .any(|prompt| self.tail.ends_with(prompt))
+        {
+            self.masking = true;
+            self.tail.clear();
+        }
+        out
+    }
+
+    /// Masks a line sent by the user if it was sent after a password prompt.
+    fn mask_sent(&mut self, line: Vec<u8>) -> Vec<u8> {
+        if !std::mem::take(&mut self.masking) {
+            return line;
+        }
+        let len = String::from_utf8_lossy(&line).chars().count();
+        vec![b'*'; len]
+    }
+}
+
+/// Performs the [`DeviceReset`] sequence of the DTR and RTS lines via `command_tx`,
+/// for `--device-reset`.
+#[instrument(name = "Device reset", skip(command_tx))]
+pub async fn run_device_reset(
+    command_tx: tokio::sync::mpsc::Sender<SerialMessage>,
+    reset: DeviceReset,
+) {
+    for &(dtr, rts, hold) in reset.steps() {
+        let lines = [SerialMessage::SetDtr(dtr), SerialMessage::SetRts(rts)];
+        for msg in lines {
+            if command_tx.send(msg).await.is_err() {
+                return;
+            }
+        }
+        tokio::time::sleep(hold).await;
+    }
+}
+
+/// Sends the commands of a [`Macro`][crate::configs::Macro] to the device via
+/// `command_tx`, for `--run-macro` and the macros bound to function keys, waiting
+/// [`Macro::delay_ms`][crate::configs::Macro::delay_ms] between commands.
+///
+/// When given a `sent_tx`, the commands are also sent through it like the
+/// lines typed, see [`run_stdin_input`].
+#[instrument(name = "Run macro", skip_all)]
+pub async fn run_macro(
+    command_tx: tokio::sync::mpsc::Sender<SerialMessage>,
+    mut sent_tx: Option<tokio::sync::mpsc::Sender<Vec<u8>>>,
+    m: &'static crate::configs::Macro,
+) {
+    for (i, line) in m.lines().enumerate() {
+        if i > 0 && !m.delay().is_zero() {
+            tokio::time::sleep(m.delay()).await;
+        }
+        let line = line.into_bytes();
+        if let Some(tx) = &sent_tx
+            && tx.send(line.clone()).await.is_err()
+        {
+            sent_tx = None;
+        }
+        if command_tx.send(SerialMessage::Write(line)).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Responsible for logging in to the device when a [`Login`][crate::configs::Login]
+/// is enabled for the active [`Profile`][crate::configs::Profile].
+///
+/// Watches the data received from the [`SerialActor`] for the configured prompts
+/// and responds with the username and password via `command_tx`. Returns once the
+/// password has been sent or the connection has been closed.
+#[instrument(name = "Auto login", skip_all)]
+pub async fn run_auto_login(
+    mut login_rx: tokio::sync::broadcast::Receiver<SerialEvent>,
+    command_tx: tokio::sync::mpsc::Sender<SerialMessage>,
+    login: &'static crate::configs::Login,
+) {
+    let username_prompt = login.username_prompt.to_lowercase();
+    let password_prompt = login.password_prompt.to_lowercase();
+    let mut username_sent = login.username.is_none();
+    let mut tail = PromptTail::default();
+
+    loop {
+        match login_rx.recv().await {
+            Ok(SerialEvent::Data(data)) => {
+                tail.push(&data);
+
+                if !username_sent
+                    && tail.ends_with(&username_prompt)
+                    && let Some(username) = &login.username
+                {
+                    info!("Sending username");
+                    let msg = SerialMessage::Write(format!("{username}\r").into_bytes());
+                    if command_tx.send(msg).await.is_err() {
+                        break;
+                    }
+                    username_sent = true;
+                    tail.clear();
+                } else if tail.ends_with(&password_prompt) {
+                    // Intentionally does not log the password
+                    info!("Sending password");
+                    let msg = SerialMessage::Write(format!("{}\r", login.password).into_bytes());
+                    let _ = command_tx.send(msg).await;
+                    break;
+                }
+            }
+            Ok(SerialEvent::ConnectionClosed) => break,
+            Ok(_) => {}
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(_) => break,
+        }
+    }
+}
+
+/// Sends pasted `text` to the device, split into chunks of
+/// [`paste_chunk_bytes`][crate::configs::Defaults::paste_chunk_bytes] that are
+/// [`paste_chunk_delay_ms`][crate::configs::Defaults::paste_chunk_delay_ms] apart.
+///
+/// Waits for the `previous` paste to be sent first so that the pastes don't
+/// interleave. Returns `false` when the channel is closed.
+async fn send_paste(
+    stdin_tx: tokio::sync::mpsc::Sender<SerialMessage>,
+    text: Vec<u8>,
+    defaults: &crate::configs::Defaults,
+    previous: Option<tokio::task::JoinHandle<bool>>,
+) -> bool {
+    if let Some(previous) = previous
+        && !previous.await.unwrap_or(false)
+    {
+        return false;
+    }
+    let Some(chunk_size) = defaults.paste_chunk_bytes.filter(|&size| size < text.len()) else {
+        return stdin_tx.send(SerialMessage::Write(text)).await.is_ok();
+    };
+    let delay = std::time::Duration::from_millis(defaults.paste_chunk_delay_ms);
+    for (i, chunk) in text.chunks(chunk_size).enumerate() {
+        if i > 0 {
+            tokio::time::sleep(delay).await;
+        }
+        if stdin_tx
+            .send(SerialMessage::Write(chunk.to_vec()))
+            .await
+            .is_err()
+        {
+            return false;
+        }
+    }
+    true
+}
+
+/// Removes the control characters from pasted text, except for the
+/// newlines and tabs, so that it can't inject escape sequences.
+fn sanitize_paste(text: &str) -> String {
+    text.chars()
+        .filter(|&c| !c.is_control() || matches!(c, '\r' | '\n' | '\t'))
+        .collect()
+}
+
+/// Sends a carriage return to the device and waits up to `timeout` for it to
+/// send anything back, showing a notice on the screen when it doesn't.
+///
+/// Returns whether the device responded.
+#[instrument(name = "Response Check", skip(event_rx, command_tx, ui_tx))]
+pub async fn run_response_check(
+    mut event_rx: tokio::sync::broadcast::Receiver<SerialEvent>,
+    command_tx: tokio::sync::mpsc::Sender<SerialMessage>,
+    ui_tx: tokio::sync::mpsc::Sender<UICommand>,
+    timeout: std::time::Duration,
+) -> bool {
+    if command_tx
+        .send(SerialMessage::Write(b"\r".to_vec()))
+        .await
+        .is_err()
+    {
+        return false;
+    }
+    let response = tokio::time::timeout(timeout, async {
+        loop {
+            match event_rx.recv().await {
+                Ok(SerialEvent::Data(_))
+                | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                    return true;
+                }
+                Ok(SerialEvent::ConnectionClosed) | Err(_) => return false,
+                Ok(_) => continue,
+            }
+        }
+    })
+    .await;
+    let responded = response.unwrap_or(false);
+    if !responded {
+        error!("No response from device within {}s", timeout.as_secs());
+        let notice = format!("No response from device within {}s", timeout.as_secs());
+        let _ = ui_tx.send(UICommand::ShowNotice(notice)).await;
+    }
+    responded
+}
+
+#[test]
+fn throttle_display() {
+    use std::time::{Duration, Instant};
+
+    let mut throttle = DisplayThrottle::new(1);
+    let start = Instant::now();
+    throttle.push(&[b'a'; 200]);
+    // The first 10th of a second is available right away
+    assert_eq!(throttle.take(start).len(), 102);
+    assert!(throttle.is_throttling());
+    assert!(throttle.take(start).is_empty());
+
+    assert_eq!(throttle.take(start + Duration::from_millis(50)).len(), 51);
+    assert_eq!(throttle.take(start + Duration::from_secs(1)).len(), 47);
+    assert!(!throttle.is_throttling());
+
+    // Over 2 seconds worth of data skips the oldest data
+    throttle.push(&[b'b'; 3000]);
+    assert_eq!(throttle.skipped, 3000 - 2048);
+    assert_eq!(throttle.take_all().len(), 2048);
+    assert!(!throttle.is_throttling());
+}
+
+#[test]
+fn filter_file_lines() {
+    let filter = crate::configs::LineFilter {
+        include: Some(crate::configs::Pattern(regex::Regex::new("ERR").unwrap())),
+        exclude: None,
+    };
+    let mut lines = FilteredLines::new(filter);
+    assert_eq!(lines.filter(b"ok\r\nERR 1\r\nER"), b"ERR 1\r\n");
+    assert_eq!(lines.filter(b"R 2\r\nok"), b"ERR 2\r\n");
+    assert!(lines.finish().is_empty());
+
+    // A line that is too long to hold is matched by its start
+    let long = "x".repeat(LinePatterns::MAX_LINE);
+    assert!(lines.filter(long.as_bytes()).is_empty());
+    assert!(lines.partial.is_empty());
+    assert_eq!(lines.filter(b"ERR\r\nERR 3\r\n"), b"ERR 3\r\n");
+    let long = format!("ERR {long}");
+    assert_eq!(lines.filter(long.as_bytes()), long.as_bytes());
+    assert_eq!(lines.filter(b" more\r\nok\r\n"), b" more\r\n");
+}
+
+#[test]
+fn throughput_sparkline() {
+    let mut throughput = Throughput::default();
+    for bytes in [0, 10, 40, 80, 2048] {
+        throughput.push(bytes);
+        throughput.tick();
+    }
+    assert_eq!(throughput.sparkline(), " ▁▁▁█ 2.0 KB/s");
+    for _ in 0..Throughput::SAMPLES {
+        throughput.tick();
+    }
+    assert_eq!(
+        throughput.sparkline(),
+        format!("{} 0 B/s", " ".repeat(Throughput::SAMPLES))
+    );
+}
+
+#[test]
+fn parse_display_filters() {
+    let filter = parse_display_filter("(?i)err").unwrap();
+    assert!(filter.matches("Gi1/0/1 input ERRORS") && !filter.matches("up"));
+    let filter = parse_display_filter("!debug").unwrap();
+    assert!(!filter.matches("debug: tick") && filter.matches("up"));
+    assert!(parse_display_filter("").unwrap().is_empty());
+    assert!(parse_display_filter("(").is_err());
+}
+
+#[test]
+fn sanitize_pasted_text() {
+    assert_eq!(
+        sanitize_paste("show ver\x1b[2J\x07\r\n\tenable\x7f"),
+        "show ver[2J\r\n\tenable"
+    );
+}
+
+#[tokio::test]
+async fn send_paste_in_chunks() {
+    let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::channel(8);
+    let mut defaults = crate::configs::Defaults {
+        paste_chunk_bytes: Some(4),
+        paste_chunk_delay_ms: 0,
+        ..Default::default()
+    };
+    let text = b"conf t\rend\r".to_vec();
+    assert!(send_paste(stdin_tx.clone(), text.clone(), &defaults, None).await);
+    let mut chunks = Vec::new();
+    while let Ok(SerialMessage::Write(chunk)) = stdin_rx.try_recv() {
+        chunks.push(chunk);
+    }
+    assert_eq!(chunks, [&b"conf"[..], b" t\re", b"nd\r"]);
+
+    defaults.paste_chunk_bytes = None;
+    assert!(send_paste(stdin_tx.clone(), text.clone(), &defaults, None).await);
+    assert!(
+        matches!(stdin_rx.try_recv(), Ok(SerialMessage::Write(text)) if text == b"conf t\rend\r")
+    );
+
+    // A paste waits for the one before it
+    defaults.paste_chunk_bytes = Some(4);
+    defaults.paste_chunk_delay_ms = 10;
+    let defaults: &'static crate::configs::Defaults = Box::leak(Box::new(defaults));
+    let first = tokio::spawn(send_paste(stdin_tx.clone(), text, defaults, None));
+    let second = send_paste(stdin_tx, b"exit\r".to_vec(), defaults, Some(first));
+    assert!(second.await);
+    let mut chunks = Vec::new();
+    while let Ok(SerialMessage::Write(chunk)) = stdin_rx.try_recv() {
+        chunks.push(chunk);
+    }
+    assert_eq!(chunks, [&b"conf"[..], b" t\re", b"nd\r", b"exit", b"\r"]);
+}
+
+#[tokio::test]
+async fn render_timer_starts_on_changes() {
+    crate::screen_buffer::init_test_config();
+    let mut buffer = ScreenBuffer::new(10, 2);
+    let mut render_timer = None;
+    start_render_timer(&mut render_timer, &buffer);
+    assert!(render_timer.is_none());
+
+    buffer.add_data(b"a");
+    start_render_timer(&mut render_timer, &buffer);
+    let period = render_timer.as_ref().map(|timer| timer.period());
+    assert_eq!(period, Some(std::time::Duration::from_secs(1) / 30));
+}
+
+#[test]
+fn file_header_bom() {
+    let mut file = Vec::new();
+    write_file_header(&mut file, true, chrono::Utc::now()).unwrap();
+    assert!(file.starts_with(b"\xEF\xBB\xBFSession started at: "));
+    assert!(std::str::from_utf8(&file).is_ok());
+
+    let mut file = Vec::new();
+    let started = chrono::Utc::now() - chrono::Duration::minutes(5);
+    write_file_header(&mut file, false, started).unwrap();
+    assert_eq!(file, format!("Session started at: {started}\n").as_bytes());
+}
+
+#[test]
+fn append_to_existing_file() {
+    use super::OutputSink;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("session.txt");
+    let session = |append: bool, data: &[u8]| {
+        let mut sink = FileSink::new(path.clone(), chrono::Utc::now());
+        sink.append = append;
+        sink.on_open().unwrap();
+        sink.write(data).unwrap();
+        sink.flush().unwrap();
+    };
+
+    // A file that doesn't exist yet gets the usual header
+    session(true, b"first\r\n");
+    session(true, b"second\r\n");
+    let text = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("Session started at: "));
+    assert_eq!(lines[1], "first");
+    assert_eq!(lines[2], "");
+    assert!(lines[3].starts_with("--- Session resumed at ") && lines[3].ends_with(" ---"));
+    assert_eq!(lines[4], "second");
+
+    session(false, b"third\r\n");
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("Session started at: "));
+    assert!(text.ends_with("\nthird\r\n"));
+}
+
+#[test]
+fn rotate_full_files() {
+    use super::OutputSink;
+
+    let dir = tempfile::tempdir().unwrap();
+    let dir = dir.path();
+    let path = dir.join("capture.txt");
+    let mut sink = FileSink::new(path, chrono::Utc::now());
+    sink.max_bytes = Some(128);
+    sink.on_open().unwrap();
+    // Larger than the limit, but the file has no output yet
+    sink.write(&[b'a'; 150]).unwrap();
+    sink.write(b"bb").unwrap();
+    sink.write(b"cc").unwrap();
+    sink.on_close().unwrap();
+    sink.flush().unwrap();
+
+    let read = |suffix: &str| std::fs::read_to_string(dir.join(format!("capture.txt{suffix}")));
+    let first = read("").unwrap();
+    assert!(first.starts_with("Session started at: "));
+    assert!(first.ends_with(&"a".repeat(150)));
+    let second = read(".1").unwrap();
+    assert!(second.starts_with("Session started at: "));
+    assert!(second.contains("\nbbcc\r\n[CLOSED "));
+    assert!(read(".2").is_err());
+
+    // The numbered files of the last session are kept, and of this session
+    // only the newest ones
+    let mut sink = FileSink::new(dir.join("capture.txt"), chrono::Utc::now());
+    sink.max_bytes = Some(128);
+    sink.max_files = Some(3);
+    sink.on_open().unwrap();
+    for chunk in ["one", "two", "three", "four"] {
+        sink.write(chunk.repeat(32).as_bytes()).unwrap();
+    }
+    sink.flush().unwrap();
+    assert!(read("").unwrap().ends_with(&"one".repeat(32)));
+    assert!(read(".1").unwrap().contains("\nbbcc\r\n[CLOSED "));
+    assert!(read(".2").is_err());
+    assert!(read(".3").unwrap().ends_with(&"three".repeat(32)));
+    assert!(read(".4").unwrap().ends_with(&"four".repeat(32)));
+    assert!(read(".5").is_err());
+}
+
+#[test]
+fn defer_output_until_error() {
+    use super::SinkFormat;
+    let mut format = FileFormat::new(None, false, true);
+    let mut out = Vec::new();
+    format.data(b"boot\r\n", &mut out);
+    assert!(out.is_empty());
+    format.event(&SerialEvent::Error("Broken pipe".to_string()), &mut out);
+    format.data(b"panic\r\n", &mut out);
+    assert_eq!(out, b"boot\r\npanic\r\n");
+
+    let mut deferred = DeferredOutput::default();
+    deferred.push(vec![0; DeferredOutput::MAX_LEN]);
+    deferred.push(vec![1; 8]);
+    assert_eq!(deferred.chunks, [vec![1; 8]]);
+}
+
+#[test]
+fn match_error_patterns() {
+    let patterns: &'static [crate::configs::Pattern] =
+        Box::leak(Box::new([crate::configs::Pattern(
+            regex::Regex::new("(?i)^kernel panic").unwrap(),
+        )]));
+    let mut errors = LinePatterns::new(patterns);
+    assert!(!errors.matches(b"booting\r\nKernel pa"));
+    assert!(errors.matches(b"nic - not syncing\r\n"));
+    assert!(!LinePatterns::new(&[]).matches(b"Kernel panic\r\n"));
+}
+
+#[test]
+fn notify_on_matching_lines() {
+    let patterns: &'static [crate::configs::Pattern] = Box::leak(Box::new([
+        crate::configs::Pattern(regex::Regex::new("(?i)completed").unwrap()),
+        crate::configs::Pattern(regex::Regex::new("^Error: ").unwrap()),
+    ]));
+    assert!(Notifier::new(&[], None).is_none());
+    let mut notifier = Notifier::new(patterns, None).unwrap();
+    // Escape sequences are removed before matching, even when split between chunks
+    assert!(!notifier.push(b"Upgrade \x1b[1;3"));
+    assert!(notifier.push(b"2mCOMPLETED\x1b[0m\r\n"));
+    assert!(!notifier.push(b"No Error: here\r\n"));
+    assert!(notifier.push(b"ok\r\nError: disk full\r\nError: again\r\n"));
+
+    let mut lines = LinePatterns::new(patterns);
+    assert_eq!(
+        lines
+            .matching_line(b"Error: one\r\nError: two\r\n")
+            .as_deref(),
+        Some("Error: one")
+    );
+    // The rest of the data was still split into lines
+    assert_eq!(
+        lines.matching_line(b"completed\n").as_deref(),
+        Some("completed")
+    );
+}
+
+#[test]
+fn mask_passwords() {
+    let mut mask = PasswordMask::new(None);
+    assert_eq!(
+        mask.mask(b"Username: admin\r\nPassword: "),
+        b"Username: admin\r\nPassword: "
+    );
+    assert_eq!(mask.mask("hunter2é".as_bytes()), b"********");
+    assert_eq!(mask.mask_sent(b"hunter2".to_vec()), b"*******");
+    assert_eq!(mask.mask(b"\r\nSwitch>show"), b"\r\nSwitch>show");
+    assert_eq!(mask.mask_sent(b"show version".to_vec()), b"show version");
+}
+
+#[test]
+fn collect_sent_lines() {
+    let mut sent = SentLines::default();
+    assert!(sent.push(b"show").is_empty());
+    assert_eq!(
+        sent.push(b" versiom\x08n\r"),
+        vec![b"show version".to_vec()]
+    );
+    assert!(sent.push(b"\r\x1b[A\x03").is_empty());
+    assert_eq!(
+        sent.push(b"enable\rconf t\r"),
+        vec![b"enable".to_vec(), b"conf t".to_vec()]
+    );
+}
+
+#[test]
+fn expand_file_tabs() {
+    let mut tabs = TabExpander::default();
+    assert_eq!(tabs.expand(b"a\tb"), b"a       b");
+    assert_eq!(
+        tabs.expand("é\tc\r\n\td".as_bytes()),
+        "é      c\r\n        d".as_bytes()
+    );
+}
+
+#[test]
+fn trim_trailing_whitespace() {
+    let mut trimmer = TrailingWhitespace::default();
+    let mut out = Vec::new();
+    trimmer.trim_into(b"Port   Status  \t\r\nGi0/1  ", &mut out);
+    trimmer.trim_into(b"  up   \r\n   \n", &mut out);
+    assert_eq!(out, b"Port   Status\r\nGi0/1    up\r\n\n");
+}
+
+#[test]
+fn trim_trailing_whitespace_in_file() {
+    use super::{OutputSink, SinkFormat};
+
+    let dir = tempfile::tempdir().unwrap();
+    let capture = |mut format: FileFormat, name: &str| {
+        let path = dir.path().join(name);
+        let mut sink = FileSink::new(path.clone(), chrono::Utc::now());
+        sink.on_open().unwrap();
+        let mut out = Vec::new();
+        format.data(b"\x1b[32mup\x1b[0m  \t\r\nGi0/1  ", &mut out);
+        format.data(b"\x1b[K\r\n", &mut out);
+        sink.write(&out).unwrap();
+        sink.flush().unwrap();
+        let text = std::fs::read(&path).unwrap();
+        let header = text.iter().position(|&b| b == b'\n').unwrap();
+        text[header + 1..].to_vec()
+    };
+
+    // The clean-text file is trimmed
+    let mut clean = FileFormat::new(None, false, false);
+    clean.ansi = Some(AnsiStripper::default());
+    clean.trimmer = Some(TrailingWhitespace::default());
+    assert_eq!(capture(clean, "clean.txt"), b"up\r\nGi0/1\r\n");
+
+    // The raw capture is written as it was received
+    let raw = FileFormat::new(None, false, false);
+    assert_eq!(
+        capture(raw, "raw.txt"),
+        b"\x1b[32mup\x1b[0m  \t\r\nGi0/1  \x1b[K\r\n"
+    );
+}
+
+#[test]
+fn timestamp_lines_across_chunks() {
+    let mut timestamps = LineTimestamps::default();
+    let mut out = Vec::new();
+    timestamps.stamp_into(b"Booting...\r\nLoad", &mut out, "12:03:44.123");
+    timestamps.stamp_into(b"ing kernel\r\n", &mut out, "12:03:45.000");
+    timestamps.stamp_into(b"\r\nDone", &mut out, "12:03:46.500");
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "[12:03:44.123] Booting...\r\n[12:03:44.123] Loading kernel\r\n\
+        [12:03:46.500] \r\n[12:03:46.500] Done"
+    );
+}
+
+#[test]
+fn format_idle_gaps() {
+    use std::time::Duration;
+    assert_eq!(format_gap(Duration::from_millis(750)), "750ms");
+    assert_eq!(format_gap(Duration::from_millis(12_400)), "12s");
+}
+
+#[tokio::test]
+async fn require_device_response() {
+    use std::time::Duration;
+
+    let (event_tx, _) = tokio::sync::broadcast::channel::<SerialEvent>(8);
+    let (command_tx, mut command_rx) = tokio::sync::mpsc::channel::<SerialMessage>(8);
+    let (ui_tx, mut ui_rx) = tokio::sync::mpsc::channel::<UICommand>(8);
+
+    let check = tokio::spawn(run_response_check(
+        event_tx.subscribe(),
+        command_tx.clone(),
+        ui_tx.clone(),
+        Duration::from_secs(5),
+    ));
+    assert!(matches!(command_rx.recv().await, Some(SerialMessage::Write(data)) if data == b"\r"));
+    event_tx
+        .send(SerialEvent::Data(b"Switch>".as_slice().into()))
+        .unwrap();
+    assert!(check.await.unwrap());
+
+    let responded = run_response_check(
+        event_tx.subscribe(),
+        command_tx,
+        ui_tx,
+        Duration::from_millis(10),
+    )
+    .await;
+    assert!(!responded);
+    assert!(matches!(ui_rx.recv().await, Some(UICommand::ShowNotice(_))));
+}
+
+#[tokio::test]
+async fn type_ahead_during_output() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (connection, device) = tokio::io::duplex(1024);
+    let (mut device_rx, mut device_tx) = tokio::io::split(device);
+    let (command_tx, command_rx) = tokio::sync::mpsc::channel::<SerialMessage>(100);
+    let (broadcast_tx, _) = tokio::sync::broadcast::channel::<SerialEvent>(16);
+    let actor = tokio::spawn(SerialActor::new(connection, command_rx, broadcast_tx).run());
+
+    // The device sends large amounts of data while the user is typing
+    let output = tokio::spawn(async move {
+        let chunk = [b'x'; 512];
+        for _ in 0..2000 {
+            if device_tx.write_all(&chunk).await.is_err() {
+                break;
+            }
         }
-        drop(write_tx);
     });
 
-    let _ = data_streamer.await;
-    let _ = write_handle.await;
+    let (stdin_tx, stdin_rx) = tokio::sync::mpsc::channel::<SerialMessage>(10);
+    let (sent_tx, mut sent_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(10);
+    tokio::spawn(forward_input(
+        stdin_rx,
+        command_tx.clone(),
+        Some(sent_tx),
+        false,
+    ));
+    let keys: Vec<u8> = (0..5000).map(|i| b'a' + (i % 26) as u8).collect();
+    let typed = keys.clone();
+    let typing = tokio::spawn(async move {
+        for key in typed {
+            stdin_tx
+                .send(SerialMessage::Write(vec![key]))
+                .await
+                .unwrap();
+        }
+    });
+    let echoed = tokio::spawn(async move {
+        let mut echoed = Vec::new();
+        while let Some(data) = sent_rx.recv().await {
+            echoed.extend(data);
+        }
+        echoed
+    });
+
+    let mut received = vec![0u8; keys.len()];
+    device_rx.read_exact(&mut received).await.unwrap();
+    assert_eq!(received, keys);
+    typing.await.unwrap();
+    assert_eq!(echoed.await.unwrap(), keys);
+
+    command_tx.send(SerialMessage::Shutdown).await.unwrap();
+    actor.await.unwrap();
+    output.await.unwrap();
+}
+
+#[tokio::test]
+async fn read_only_drops_input() {
+    let (stdin_tx, stdin_rx) = tokio::sync::mpsc::channel::<SerialMessage>(10);
+    let (command_tx, mut command_rx) = tokio::sync::mpsc::channel::<SerialMessage>(10);
+    let (sent_tx, mut sent_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(10);
+    let forward = tokio::spawn(forward_input(stdin_rx, command_tx, Some(sent_tx), true));
+
+    stdin_tx
+        .send(SerialMessage::Write(b"reload\r".to_vec()))
+        .await
+        .unwrap();
+    stdin_tx.send(SerialMessage::SendBreak).await.unwrap();
+    stdin_tx.send(SerialMessage::Shutdown).await.unwrap();
+    drop(stdin_tx);
+    forward.await.unwrap();
+
+    assert!(matches!(
+        command_rx.recv().await,
+        Some(SerialMessage::Shutdown)
+    ));
+    assert!(command_rx.recv().await.is_none());
+    assert!(sent_rx.recv().await.is_none());
+}
+
+#[test]
+fn recall_input_history() {
+    let mut history = InputHistory::default();
+    assert!(history.older().is_none());
+    for line in ["show version", "", "show ip int br", "show ip int br"] {
+        line.chars().for_each(|c| history.record(KeyCode::Char(c)));
+        history.record(KeyCode::Enter);
+    }
+    assert_eq!(history.lines, ["show version", "show ip int br"]);
+
+    history.record(KeyCode::Char('x'));
+    assert_eq!(history.older().unwrap(), b"\x08show ip int br");
+    let erase_and_older = format!("{}show version", UTF_BKSP.repeat(14));
+    assert_eq!(history.older().unwrap(), erase_and_older.as_bytes());
+    assert!(history.older().is_none());
+    assert_eq!(
+        history.newer().unwrap(),
+        format!("{}show ip int br", UTF_BKSP.repeat(12)).as_bytes()
+    );
+    assert!(history.newer().is_none());
+
+    // Editing and sending a recalled line adds it as a new line
+    history.record(KeyCode::Backspace);
+    history.record(KeyCode::Enter);
+    assert_eq!(history.lines.back().unwrap(), "show ip int b");
+    assert!(history.recalled.is_none());
+}
+
+#[test]
+fn strip_ansi_from_file() {
+    let mut ansi = AnsiStripper::default();
+    let colored = b"\x1b[1;31mError\x1b[0m: link\x07 down\r\n\x1b]0;title\x07sw1#";
+    assert_eq!(ansi.strip(colored), b"Error: link down\r\nsw1#");
+    // Sequences split between chunks
+    assert_eq!(ansi.strip(b"a\x1b["), b"a");
+    assert_eq!(ansi.strip(b"2Kb\x1b]2;x\x1b"), b"b");
+    assert_eq!(ansi.strip("\\c\x1b(Bd\tüñ".as_bytes()), "cd\tüñ".as_bytes());
+}
+
+#[tokio::test]
+async fn reset_esp32_with_control_lines() {
+    let (command_tx, mut command_rx) = tokio::sync::mpsc::channel::<SerialMessage>(10);
+    run_device_reset(command_tx, DeviceReset::Esp32).await;
+    let mut lines = Vec::new();
+    while let Some(msg) = command_rx.recv().await {
+        lines.push(match msg {
+            SerialMessage::SetDtr(state) => ('D', state),
+            SerialMessage::SetRts(state) => ('R', state),
+            other => panic!("Expected a control line, got: {other:?}"),
+        });
+    }
+    assert_eq!(
+        lines,
+        [
+            ('D', false),
+            ('R', true),
+            ('D', true),
+            ('R', false),
+            ('D', false),
+            ('R', false)
+        ]
+    );
+    // The toggles start from the lines the reset left
+    assert_eq!(DeviceReset::Esp32.final_lines(), (false, false));
+}
+
+#[tokio::test]
+async fn run_macro_echoes_sent() {
+    let m: &'static crate::configs::Macro = Box::leak(Box::new(crate::configs::Macro {
+        key: None,
+        commands: vec!["terminal length 0".to_string(), "show version".to_string()],
+        delay_ms: 0,
+    }));
+    let (command_tx, mut command_rx) = tokio::sync::mpsc::channel(8);
+    let (sent_tx, mut sent_rx) = tokio::sync::mpsc::channel(8);
+    run_macro(command_tx, Some(sent_tx), m).await;
+    for expected in [&b"terminal length 0\r"[..], b"show version\r"] {
+        assert!(
+            matches!(command_rx.try_recv(), Ok(SerialMessage::Write(line)) if line == expected)
+        );
+        assert_eq!(sent_rx.try_recv().unwrap(), expected);
+    }
 }
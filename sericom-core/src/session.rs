@@ -0,0 +1,551 @@
+//! This module holds [`SessionBuilder`], which sets up the [`SerialActor`] and the
+//! tasks writing the session's output to files or other [`OutputSink`]s, without
+//! `sericom`'s terminal UI.
+//!
+//! It is what [`interactive_session`][crate::cli::interactive_session] uses, and
+//! allows other Rust programs to drive a device over a serial connection:
+//!
+//! ```no_run
+//! # async fn example() -> miette::Result<()> {
+//! use sericom_core::{serial_actor::SerialEvent, session::SessionBuilder};
+//!
+//! let mut session = SessionBuilder::new()
+//!     .port("/dev/ttyUSB0")
+//!     .baud(115200)
+//!     .file("session.txt")
+//!     .run()?;
+//! let mut events = session.subscribe();
+//! session.write("show version\r").await?;
+//! while let Ok(SerialEvent::Data(data)) = events.recv().await {
+//!     print!("{}", String::from_utf8_lossy(&data));
+//! }
+//! session.shutdown().await;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    configs::LineFilter,
+    screen_buffer::ScreenBuffer,
+    serial_actor::{
+        OutputSink, RawFormat, Reconnect, SerialActor, SerialConnection, SerialEvent,
+        SerialMessage, SinkFormat, run_output_sink_with, tasks,
+    },
+};
+use serial2_tokio::SerialPort;
+use std::path::PathBuf;
+use tokio::sync::{broadcast, mpsc};
+
+/// Returned by [`Session`] when the [`SerialActor`] has shut down and can no longer
+/// receive messages.
+#[derive(Debug, miette::Diagnostic, thiserror::Error)]
+#[error("The session has been shut down")]
+pub struct SessionClosed;
+
+/// Configures and launches a [`Session`].
+///
+/// Receivers for the [`SerialEvent`]s can be taken with [`SessionBuilder::subscribe()`]
+/// before the session is launched, so that none of the data received is missed.
+/// The channels' capacities are taken from the config's [`Defaults`][crate::configs::Defaults],
+/// or their default values when no config has been initialized.
+#[derive(Debug)]
+pub struct SessionBuilder {
+    port: Option<String>,
+    baud: u32,
+    port_settings: crate::cli::PortSettings,
+    file: Option<PathBuf>,
+    captures: Vec<(PathBuf, LineFilter)>,
+    sinks: Vec<SinkTask>,
+    scrollback: usize,
+    echo_sent: bool,
+    mask_passwords: bool,
+    output_on_error_only: bool,
+    exit_script: bool,
+    reconnect: bool,
+    read_only: bool,
+    command_tx: mpsc::Sender<SerialMessage>,
+    command_rx: mpsc::Receiver<SerialMessage>,
+    event_tx: broadcast::Sender<SerialEvent>,
+}
+
+impl Default for SessionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionBuilder {
+    /// Constructs a new `SessionBuilder` with a baud rate of `9600` and no files.
+    pub fn new() -> Self {
+        let defaults = &crate::configs::get_config().defaults;
+        let (command_tx, command_rx) = mpsc::channel(defaults.command_channel_capacity);
+        let (event_tx, _) = broadcast::channel(defaults.event_channel_capacity);
+        Self {
+            port: None,
+            baud: 9600,
+            port_settings: crate::cli::PortSettings::default(),
+            file: None,
+            captures: Vec::new(),
+            sinks: Vec::new(),
+            scrollback: defaults.scrollback,
+            echo_sent: false,
+            mask_passwords: true,
+            output_on_error_only: false,
+            exit_script: false,
+            reconnect: false,
+            read_only: false,
+            command_tx,
+            command_rx,
+            event_tx,
+        }
+    }
+
+    /// Sets the serial port opened by [`SessionBuilder::run()`], i.e. `/dev/ttyUSB0` or `COM4`.
+    pub fn port<S: Into<String>>(mut self, port: S) -> Self {
+        self.port = Some(port.into());
+        self
+    }
+
+    /// Sets the baud rate the port is opened with.
+    pub fn baud(mut self, baud: u32) -> Self {
+        self.baud = baud;
+        self
+    }
+
+    /// Sets the data bits, parity, stop bits and flow control the port is opened
+    /// with, defaults to 8N1 without flow control.
+    pub fn port_settings(mut self, port_settings: crate::cli::PortSettings) -> Self {
+        self.port_settings = port_settings;
+        self
+    }
+
+    /// Writes the session's output to the file at `path`.
+    pub fn file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Writes the lines of the session's output that pass `filter` to the file
+    /// at `path`, i.e. for a [`Capture`][crate::configs::Capture].
+    pub fn capture<P: Into<PathBuf>>(mut self, path: P, filter: LineFilter) -> Self {
+        self.captures.push((path.into(), filter));
+        self
+    }
+
+    /// Sends the session's output to `sink` as it was received, i.e. to a TCP
+    /// socket or a named pipe, see [`run_output_sink()`][crate::serial_actor::run_output_sink].
+    pub fn sink<S: OutputSink>(self, sink: S) -> Self {
+        self.sink_with(sink, RawFormat)
+    }
+
+    /// Sends the session's output to `sink`, formatted by `format`, see
+    /// [`run_output_sink_with()`].
+    pub fn sink_with<S: OutputSink, F: SinkFormat>(mut self, sink: S, format: F) -> Self {
+        let rx = self.subscribe();
+        let task = run_output_sink_with(rx, sink, format, None);
+        self.sinks.push(SinkTask(Box::pin(task)));
+        self
+    }
+
+    /// Also writes the lines sent to the device to the [`SessionBuilder::file()`],
+    /// see [`Session::sent_sender()`].
+    pub fn echo_sent(mut self, echo_sent: bool) -> Self {
+        self.echo_sent = echo_sent;
+        self
+    }
+
+    /// Whether what is typed after password prompts is masked in the files,
+    /// defaults to `true`.
+    pub fn mask_passwords(mut self, mask_passwords: bool) -> Self {
+        self.mask_passwords = mask_passwords;
+        self
+    }
+
+    /// Only writes the files when an error occurred during the session,
+    /// see [`Defaults::error_patterns`][crate::configs::Defaults::error_patterns].
+    pub fn output_on_error_only(mut self, output_on_error_only: bool) -> Self {
+        self.output_on_error_only = output_on_error_only;
+        self
+    }
+
+    /// Runs the config's [`Defaults::exit_script`][crate::configs::Defaults::exit_script]
+    /// once the [`SessionBuilder::file()`] has been written.
+    pub fn exit_script(mut self, exit_script: bool) -> Self {
+        self.exit_script = exit_script;
+        self
+    }
+
+    /// Reopens the [`SessionBuilder::port()`] with the same settings when the
+    /// connection is lost, see [`Reconnect`]. Only used by [`SessionBuilder::run()`]
+    /// and [`SessionBuilder::run_with_port()`].
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Doesn't write the [`Reconnect::on_reconnect`] commands, i.e. the config's
+    /// [`on_reconnect`][crate::configs::Defaults::on_reconnect], to the device after
+    /// reconnecting, for sessions that never send anything to the device.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sets the number of lines kept by [`SessionBuilder::screen_buffer()`], `0` for
+    /// no limit. Defaults to the config's [`Defaults::scrollback`][crate::configs::Defaults::scrollback].
+    pub fn scrollback(mut self, lines: usize) -> Self {
+        self.scrollback = lines;
+        self
+    }
+
+    /// Constructs a [`ScreenBuffer`] of `width` and `height` with the session's
+    /// [`SessionBuilder::scrollback()`], i.e. to be fed the [`SerialEvent::Data`]
+    /// of [`SessionBuilder::subscribe()`] without a terminal.
+    pub fn screen_buffer(&self, width: u16, height: u16) -> ScreenBuffer {
+        ScreenBuffer::new(width, height).with_scrollback(self.scrollback)
+    }
+
+    /// Returns a receiver for the [`SerialEvent`]s of the session.
+    pub fn subscribe(&self) -> broadcast::Receiver<SerialEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Returns a sender for the [`SerialMessage`]s to the session, i.e. for tasks
+    /// that are set up before the session is launched.
+    pub fn command_sender(&self) -> mpsc::Sender<SerialMessage> {
+        self.command_tx.clone()
+    }
+
+    /// Opens the [`SessionBuilder::port()`] and launches the session.
+    ///
+    /// Errors if no port was set or the port can't be opened.
+    pub fn run(self) -> miette::Result<Session> {
+        let Some(port) = self.port.as_deref() else {
+            miette::bail!("No port was set to open the session with");
+        };
+        let connection = crate::cli::open_connection(self.baud, port, &self.port_settings)?;
+        Ok(self.run_with_port(connection))
+    }
+
+    /// Launches the session over `connection`, a port that is already open.
+    ///
+    /// With [`SessionBuilder::reconnect()`], the [`SessionBuilder::port()`] is
+    /// reopened with the settings `connection` was opened with.
+    pub fn run_with_port(mut self, connection: SerialPort) -> Session {
+        let settings = connection.get_configuration().ok();
+        if let Some(baud) = settings.as_ref().and_then(|s| s.get_baud_rate().ok()) {
+            self.baud = baud;
+        }
+        let status = match (&self.port, &settings) {
+            (Some(port), Some(settings)) => Some(describe_port(port, settings)),
+            _ => None,
+        };
+        let reconnect = match (self.reconnect, self.port.clone(), settings) {
+            (true, Some(port), Some(settings)) => Some(Reconnect::new(move || {
+                let settings = settings.clone();
+                SerialPort::open(&port, move |_| Ok(settings))
+            })),
+            _ => None,
+        };
+        self.launch(connection, reconnect, status)
+    }
+
+    /// Launches the session over `connection`, i.e. a port that is already open
+    /// or an in-memory [`tokio::io::DuplexStream`].
+    pub fn run_with<C: SerialConnection + Send + 'static>(self, connection: C) -> Session {
+        self.launch(connection, None, None)
+    }
+
+    /// Launches the session over `connection`, reopening it with `reconnect` when
+    /// it is lost, i.e. for connections that [`SessionBuilder::reconnect()`] can't reopen.
+    pub fn run_with_reconnect<C: SerialConnection + Send + 'static>(
+        self,
+        connection: C,
+        reconnect: Reconnect<C>,
+    ) -> Session {
+        self.launch(connection, Some(reconnect), None)
+    }
+
+    fn launch<C: SerialConnection + Send + 'static>(
+        self,
+        connection: C,
+        mut reconnect: Option<Reconnect<C>>,
+        status: Option<String>,
+    ) -> Session {
+        if self.read_only
+            && let Some(ref mut reconnect) = reconnect
+        {
+            reconnect.on_reconnect.clear();
+        }
+        let mut session = Session {
+            command_tx: self.command_tx,
+            event_tx: self.event_tx,
+            sent_tx: None,
+            tasks: tokio::task::JoinSet::new(),
+        };
+
+        let (sent_tx, sent_rx) = match (&self.file, self.echo_sent) {
+            (Some(_), true) => {
+                let capacity = crate::configs::get_config()
+                    .defaults
+                    .command_channel_capacity;
+                let (tx, rx) = mpsc::channel::<Vec<u8>>(capacity);
+                (Some(tx), Some(rx))
+            }
+            _ => (None, None),
+        };
+        session.sent_tx = sent_tx;
+
+        let (mask_passwords, on_error_only) = (self.mask_passwords, self.output_on_error_only);
+        if let Some(file_path) = self.file {
+            let file_rx = session.subscribe();
+            let exit_script = self.exit_script;
+            let (port, baud, start) = (self.port.clone(), self.baud, chrono::Local::now());
+            session.spawn(async move {
+                tasks::run_file_output(
+                    file_rx,
+                    file_path.clone(),
+                    None,
+                    sent_rx,
+                    mask_passwords,
+                    on_error_only,
+                )
+                .await;
+                // The file isn't created when no errors occurred with `on_error_only`
+                if exit_script && file_path.exists() {
+                    let metadata = crate::cli::SessionMetadata {
+                        port,
+                        baud,
+                        start,
+                        end: chrono::Local::now(),
+                    };
+                    crate::cli::run_file_exit_script(
+                        crate::configs::get_config(),
+                        file_path,
+                        &metadata,
+                    );
+                }
+            });
+        }
+
+        for (capture_path, filter) in self.captures {
+            let capture_rx = session.subscribe();
+            session.spawn(tasks::run_file_output(
+                capture_rx,
+                capture_path,
+                Some(filter),
+                None,
+                mask_passwords,
+                on_error_only,
+            ));
+        }
+
+        for SinkTask(task) in self.sinks {
+            session.spawn(task);
+        }
+
+        let mut actor = SerialActor::new(connection, self.command_rx, session.event_tx.clone());
+        if let Some(reconnect) = reconnect {
+            actor = actor.with_reconnect(reconnect);
+        }
+        if let Some(status) = status {
+            actor = actor.with_status(status);
+        }
+        session.spawn(actor.run());
+        session
+    }
+}
+
+/// A task sending the session's output to an [`OutputSink`], see [`SessionBuilder::sink()`].
+/// It subscribes when the sink is added, so that none of the data received is missed.
+struct SinkTask(std::pin::Pin<Box<dyn Future<Output = ()> + Send>>);
+
+impl std::fmt::Debug for SinkTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SinkTask")
+    }
+}
+
+/// Describes `port` for [`SerialEvent::Status`], i.e. `/dev/ttyUSB0 115200 8N1`.
+fn describe_port(port: &str, settings: &serial2_tokio::Settings) -> String {
+    let mut description = port.to_string();
+    if let Ok(baud) = settings.get_baud_rate() {
+        description.push_str(&format!(" {baud}"));
+    }
+    if let (Ok(data_bits), Ok(parity), Ok(stop_bits)) = (
+        settings.get_char_size(),
+        settings.get_parity(),
+        settings.get_stop_bits(),
+    ) {
+        let parity = match parity {
+            serial2_tokio::Parity::None => 'N',
+            serial2_tokio::Parity::Odd => 'O',
+            serial2_tokio::Parity::Even => 'E',
+        };
+        description.push_str(&format!(" {data_bits}{parity}{stop_bits}"));
+    }
+    description
+}
+
+/// A running session, launched with [`SessionBuilder`].
+///
+/// The session runs until it is shut down with [`Session::shutdown()`] (or
+/// [`SerialMessage::Shutdown`]) or the connection is closed.
+#[derive(Debug)]
+pub struct Session {
+    command_tx: mpsc::Sender<SerialMessage>,
+    event_tx: broadcast::Sender<SerialEvent>,
+    sent_tx: Option<mpsc::Sender<Vec<u8>>>,
+    tasks: tokio::task::JoinSet<()>,
+}
+
+impl Session {
+    /// Writes `data` to the device.
+    pub async fn write<D: Into<Vec<u8>>>(&self, data: D) -> Result<(), SessionClosed> {
+        let data = data.into();
+        if let Some(ref sent_tx) = self.sent_tx {
+            let _ = sent_tx.send(data.clone()).await;
+        }
+        self.send(SerialMessage::Write(data)).await
+    }
+
+    /// Sends a 'break' signal to the device.
+    pub async fn send_break(&self) -> Result<(), SessionClosed> {
+        self.send(SerialMessage::SendBreak).await
+    }
+
+    /// Sets the Data Terminal Ready line.
+    pub async fn set_dtr(&self, state: bool) -> Result<(), SessionClosed> {
+        self.send(SerialMessage::SetDtr(state)).await
+    }
+
+    /// Sets the Request To Send line.
+    pub async fn set_rts(&self, state: bool) -> Result<(), SessionClosed> {
+        self.send(SerialMessage::SetRts(state)).await
+    }
+
+    async fn send(&self, msg: SerialMessage) -> Result<(), SessionClosed> {
+        self.command_tx.send(msg).await.map_err(|_| SessionClosed)
+    }
+
+    /// Returns a receiver for the [`SerialEvent`]s received from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<SerialEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Returns a sender for the [`SerialMessage`]s to the session.
+    pub fn command_sender(&self) -> mpsc::Sender<SerialMessage> {
+        self.command_tx.clone()
+    }
+
+    /// Returns a sender for the data sent to the device by other means than
+    /// [`Session::write()`], i.e. user input, so that it is written to the file
+    /// with [`SessionBuilder::echo_sent()`]. `None` when it isn't enabled.
+    pub fn sent_sender(&self) -> Option<mpsc::Sender<Vec<u8>>> {
+        self.sent_tx.clone()
+    }
+
+    /// Runs `task` as part of the session, [`Session::join()`] waits for it to finish.
+    pub fn spawn<F: Future<Output = ()> + Send + 'static>(&mut self, task: F) {
+        self.tasks.spawn(task);
+    }
+
+    /// Shuts down the session and waits for its tasks to finish.
+    pub async fn shutdown(self) {
+        let _ = self.send(SerialMessage::Shutdown).await;
+        self.join().await;
+    }
+
+    /// Waits for the session's tasks to finish.
+    pub async fn join(self) {
+        // Dropping the senders lets the tasks that wait on them finish
+        let Self { mut tasks, .. } = self;
+        while tasks.join_next().await.is_some() {}
+    }
+}
+
+#[tokio::test]
+async fn session_over_duplex() {
+    let (connection, mut device) = tokio::io::duplex(64);
+    let builder = SessionBuilder::new();
+    let mut events = builder.subscribe();
+    let session = builder.run_with(connection);
+
+    session.write("show version\r").await.unwrap();
+    let mut received = [0; 13];
+    tokio::io::AsyncReadExt::read_exact(&mut device, &mut received)
+        .await
+        .unwrap();
+    assert_eq!(&received, b"show version\r");
+
+    tokio::io::AsyncWriteExt::write_all(&mut device, b"Switch#")
+        .await
+        .unwrap();
+    assert!(matches!(events.recv().await, Ok(SerialEvent::Data(data)) if &data[..] == b"Switch#"));
+    session.shutdown().await;
+}
+
+#[tokio::test]
+async fn session_output_to_sink() {
+    use crate::serial_actor::Recorder;
+
+    let (connection, mut device) = tokio::io::duplex(64);
+    let recorder = Recorder::default();
+    let session = SessionBuilder::new()
+        .sink(recorder.clone())
+        .run_with(connection);
+    tokio::io::AsyncWriteExt::write_all(&mut device, b"Switch#")
+        .await
+        .unwrap();
+    // Closes the connection, which ends the session
+    drop(device);
+    session.join().await;
+    assert_eq!(*recorder.0.lock().unwrap(), ["open", "Switch#", "close"]);
+}
+
+#[tokio::test]
+async fn read_only_reconnect_writes_nothing() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (connection, device) = tokio::io::duplex(64);
+    let (reopened, mut device_rx) = tokio::io::duplex(64);
+    let mut reopened = Some(reopened);
+    let mut reconnect = Reconnect::new(move || {
+        reopened
+            .take()
+            .ok_or_else(|| std::io::Error::other("Not found"))
+    });
+    reconnect.retries = 1;
+    reconnect.interval = std::time::Duration::from_millis(1);
+    reconnect.on_reconnect = vec![b"terminal length 0\r".to_vec()];
+    let builder = SessionBuilder::new().read_only(true);
+    let mut events = builder.subscribe();
+    let session = builder.run_with_reconnect(connection, reconnect);
+
+    drop(device);
+    assert!(matches!(events.recv().await, Ok(SerialEvent::Reconnecting)));
+    assert!(matches!(events.recv().await, Ok(SerialEvent::Reconnected)));
+    AsyncWriteExt::write_all(&mut device_rx, b"Switch>")
+        .await
+        .unwrap();
+    assert!(matches!(events.recv().await, Ok(SerialEvent::Data(data)) if &data[..] == b"Switch>"));
+    let mut buf = [0u8; 1];
+    let read = tokio::time::timeout(
+        std::time::Duration::from_millis(20),
+        AsyncReadExt::read(&mut device_rx, &mut buf),
+    )
+    .await;
+    assert!(read.is_err(), "Expected nothing written, got: {read:?}");
+    session.shutdown().await;
+}
+
+#[test]
+fn screen_buffer_scrollback() {
+    let builder = SessionBuilder::new().scrollback(3);
+    let mut buffer = builder.screen_buffer(10, 2);
+    for i in 0..10 {
+        buffer.add_data(format!("{i}\r\n").as_bytes());
+    }
+    assert_eq!(buffer.line_text(0), "8");
+    assert_eq!(buffer.line_text(1), "9");
+}
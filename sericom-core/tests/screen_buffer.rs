@@ -0,0 +1,14 @@
+//! Runs in its own process, where the config is never initialized.
+
+use sericom_core::screen_buffer::ScreenBuffer;
+
+#[test]
+fn screen_buffer_without_config() {
+    // Falls back to `Config::default()` when the config isn't initialized
+    assert!(sericom_core::configs::CONFIG.get().is_none());
+    let mut buffer = ScreenBuffer::new(10, 2);
+    buffer.add_data(b"abc");
+    let mut frame = Vec::new();
+    buffer.render_to(&mut frame).unwrap();
+    assert!(String::from_utf8_lossy(&frame).contains("abc"));
+}
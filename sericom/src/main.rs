@@ -12,8 +12,11 @@ use crossterm::style::Stylize;
 use miette::{Context, IntoDiagnostic};
 use sericom_core::{
     cli::{
-        color_parser, get_settings, interactive_session, list_serial_ports, open_connection,
-        valid_baud_rate,
+        OutputFormat, PortSettings, SessionOptions, bridge, color_parser, custom_baud_rate,
+        data_bits_parser, device_reset_parser, flow_control_parser, get_settings,
+        interactive_session, list_serial_ports, open_connection, output_format_parser,
+        parity_parser, print_diagnostics, replay, send_file, stop_bits_parser, valid_baud_rate,
+        watch_settings,
     },
     configs::{get_config, initialize_config},
     path_utils::{is_script, validate_dir},
@@ -32,9 +35,10 @@ struct Cli {
     ///
     /// For Linux/MacOS something like `/dev/tty1`, Windows `COM1`.
     port: Option<String>,
-    /// Baud rate for the serial connection.
-    #[arg(short, long, value_parser = valid_baud_rate, default_value_t = 9600)]
-    baud: u32,
+    #[clap(flatten)]
+    baud: BaudArgs,
+    #[clap(flatten)]
+    port_settings: PortArgs,
     #[clap(flatten)]
     config_override: ConfigOverrides,
     /// Path to a file for the output.
@@ -43,6 +47,51 @@ struct Cli {
     /// Display debug output
     #[arg(short, long)]
     debug: bool,
+    /// End the session after it has been running for this many seconds
+    #[arg(long, requires_all = &["port"], value_parser = clap::value_parser!(u64).range(1..))]
+    max_duration: Option<u64>,
+    /// End the session once no data has been received from the device for this
+    /// many seconds, `0` never ends it
+    #[arg(long, value_name = "SECS", requires_all = &["port"])]
+    idle_timeout: Option<u64>,
+    /// Also write the lines sent to the device to the file, prefixed with `>>> `
+    #[arg(long, requires_all = &["port"])]
+    echo_sent: bool,
+    /// Send a carriage return once connected and warn if the device doesn't
+    /// respond within SECONDS (defaults to 5)
+    #[arg(long, value_name = "SECONDS", requires_all = &["port"], num_args = 0..=1,
+        default_missing_value = "5", value_parser = clap::value_parser!(u64).range(1..))]
+    require_response: Option<u64>,
+    /// Write what is typed after password prompts to the file instead of masking it
+    #[arg(long, requires_all = &["port"])]
+    insecure_log_passwords: bool,
+    /// Only write the files if the connection errored or a line matched one of
+    /// the `error-patterns`, otherwise discard the output
+    #[arg(long, requires_all = &["port"])]
+    output_on_error_only: bool,
+    /// Print a summary of the data received (byte counts and the first bytes) to
+    /// stderr, meant to be redirected i.e. `2> trace.txt`
+    #[arg(long, requires_all = &["port"])]
+    trace_stderr: bool,
+    /// Monitor the device without ever sending it anything; keys other than the
+    /// ones for sericom itself (scrolling, copying, quitting, ...) are ignored
+    #[arg(long, requires_all = &["port"], conflicts_with = "require_response")]
+    read_only: bool,
+    /// Reopen the port when the connection is lost (i.e. the adapter is unplugged),
+    /// see `reconnect-retries` and `reconnect-interval-ms` in the config
+    #[arg(long, requires_all = &["port"])]
+    reconnect: bool,
+    /// Keep the lines typed between Enter presses, Alt+Up/Alt+Down re-send them
+    /// (see `history-prev` and `history-next` in the config)
+    #[arg(long, requires_all = &["port"], conflicts_with = "read_only")]
+    local_history: bool,
+    /// Send the commands of the macro `[macros.<NAME>]` once connected
+    #[arg(long, value_name = "NAME", requires_all = &["port"], conflicts_with = "read_only")]
+    run_macro: Option<String>,
+    /// Reset the device with its DTR and RTS lines once connected, i.e. `esp32`
+    /// to enter the bootloader of an ESP32 development board
+    #[arg(long, value_name = "KIND", requires_all = &["port"], conflicts_with = "read_only", value_parser = device_reset_parser)]
+    device_reset: Option<sericom_core::serial_actor::DeviceReset>,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -53,19 +102,124 @@ enum Commands {
     /// Lists valid baud rates
     Bauds,
     /// Lists all available serial ports
-    Ports,
+    Ports {
+        /// `human` or `json`, a JSON array of the ports' paths
+        #[arg(long, value_parser = output_format_parser, default_value = "human")]
+        format: OutputFormat,
+    },
+    /// Prints build, platform and configuration info for troubleshooting
+    Doctor,
     /// Gets the settings for a serial port
     Settings {
-        #[arg(short, long, value_parser = valid_baud_rate, default_value_t = 9600)]
-        baud: u32,
+        #[clap(flatten)]
+        baud: BaudArgs,
+        /// Path to the port to open
+        #[arg(short, long)]
+        port: String,
+        #[clap(flatten)]
+        port_settings: PortArgs,
+        /// `human` or `json`, a JSON object of the settings and line states
+        #[arg(long, value_parser = output_format_parser, default_value = "human")]
+        format: OutputFormat,
+        /// Keep showing the modem control lines (CTS, DSR, RI and CD), highlighting
+        /// the ones that change, until Ctrl+C is pressed
+        #[arg(long, conflicts_with = "format")]
+        watch: bool,
+    },
+    /// Replays a file written by a session in the terminal, to scroll through it
+    Replay {
+        /// Path to the file to replay
+        file: PathBuf,
+        /// Play the file back at this many times the speed of a 9600 baud
+        /// connection, instead of showing it at once
+        #[arg(long, value_parser = positive_speed)]
+        speed: Option<f64>,
+    },
+    /// Bridges a serial port to a TCP socket, i.e. to reach a console over the network
+    Bridge {
+        #[clap(flatten)]
+        baud: BaudArgs,
         /// Path to the port to open
         #[arg(short, long)]
         port: String,
+        #[clap(flatten)]
+        port_settings: PortArgs,
+        /// The address to accept a client on, i.e. `0.0.0.0:7000`
+        #[arg(long, value_name = "ADDR:PORT")]
+        listen: String,
+    },
+    /// Sends the contents of a file to a serial port, line by line
+    SendFile {
+        #[clap(flatten)]
+        baud: BaudArgs,
+        /// Path to the port to open
+        #[arg(short, long)]
+        port: String,
+        #[clap(flatten)]
+        port_settings: PortArgs,
+        /// Path to the file to send
+        path: PathBuf,
+        /// Milliseconds to wait between lines, for devices that drop characters
+        #[arg(long, default_value_t = 0)]
+        delay_ms: u64,
+        /// Send the file's line endings as carriage returns (`\r`)
+        #[arg(long)]
+        crlf: bool,
     },
 }
 
+#[derive(Parser, Debug)]
+struct BaudArgs {
+    /// Baud rate for the serial connection.
+    #[arg(short, long, value_parser = valid_baud_rate, default_value_t = 9600)]
+    baud: u32,
+    /// A baud rate that isn't one of the common rates (i.e. 250000 or 1500000),
+    /// passed to the port as-is
+    #[arg(long, value_name = "N", value_parser = custom_baud_rate, conflicts_with = "baud")]
+    baud_custom: Option<u32>,
+}
+
+impl BaudArgs {
+    fn rate(&self) -> u32 {
+        self.baud_custom.unwrap_or(self.baud)
+    }
+}
+
+#[derive(Parser, Debug)]
+struct PortArgs {
+    /// Number of data bits: 5, 6, 7 or 8
+    #[arg(long, value_parser = data_bits_parser, default_value = "8")]
+    data_bits: serial2_tokio::CharSize,
+    /// Parity: none, odd or even
+    #[arg(long, value_parser = parity_parser, default_value = "none")]
+    parity: serial2_tokio::Parity,
+    /// Number of stop bits: 1 or 2
+    #[arg(long, value_parser = stop_bits_parser, default_value = "1")]
+    stop_bits: serial2_tokio::StopBits,
+    /// Flow control: none, software (XON/XOFF) or hardware (RTS/CTS)
+    #[arg(long, value_parser = flow_control_parser, default_value = "none")]
+    flow_control: serial2_tokio::FlowControl,
+}
+
+impl From<PortArgs> for PortSettings {
+    fn from(args: PortArgs) -> Self {
+        PortSettings {
+            data_bits: args.data_bits,
+            parity: args.parity,
+            stop_bits: args.stop_bits,
+            flow_control: args.flow_control,
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 struct ConfigOverrides {
+    /// Path to the config file to use
+    ///
+    /// Takes precedence over the `SERICOM_CONFIG` environment variable and
+    /// the default `~/.config/sericom/config.toml`
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
     /// Set the forground color for the text
     #[arg(short, long, requires_all = &["port"], value_parser = color_parser)]
     color: Option<sericom_core::configs::SeriColor>,
@@ -77,14 +231,39 @@ struct ConfigOverrides {
     /// Override the `exit-script` that's run after writing to a file
     #[arg(long, requires_all = &["port", "file"], value_parser = is_script)]
     exit_script: Option<PathBuf>,
+    /// Create the file within a subdirectory of the `out-dir` named after the current date
+    #[arg(long, requires_all = &["port", "file"])]
+    append_date_to_dir: bool,
+    /// Only keep the visible lines in memory and disable scrolling
+    #[arg(long, requires_all = &["port"])]
+    no_scrollback: bool,
+    /// The maximum number of lines kept in the session's history, 0 keeps every line
+    #[arg(long, value_name = "LINES", requires_all = &["port"], conflicts_with = "no_scrollback")]
+    scrollback: Option<usize>,
+    /// Prefix each line written to the files with the time it was received
+    #[arg(long, requires_all = &["port"])]
+    timestamps: bool,
+    /// Append to the file if it already exists instead of overwriting it
+    #[arg(long, requires_all = &["port", "file"])]
+    append: bool,
+    /// Use the settings of a profile defined in the config file
+    #[arg(short, long, requires_all = &["port"])]
+    profile: Option<String>,
 }
 
 impl From<ConfigOverrides> for sericom_core::configs::ConfigOverride {
     fn from(overrides: ConfigOverrides) -> Self {
         sericom_core::configs::ConfigOverride {
+            config_file: overrides.config,
             color: overrides.color,
             out_dir: overrides.out_dir,
             exit_script: overrides.exit_script,
+            profile: overrides.profile,
+            append_date_to_dir: overrides.append_date_to_dir,
+            no_scrollback: overrides.no_scrollback,
+            scrollback: overrides.scrollback,
+            timestamp_output: overrides.timestamps,
+            append_file: overrides.append,
         }
     }
 }
@@ -112,7 +291,7 @@ async fn main() -> miette::Result<()> {
     }
 
     if let Some(ref port) = cli.port {
-        let connection = open_connection(cli.baud, port)?;
+        let connection = open_connection(cli.baud.rate(), port, &cli.port_settings.into())?;
         let overrides: sericom_core::configs::ConfigOverride = cli.config_override.into();
 
         if let Some(Some(path)) = &cli.file
@@ -135,7 +314,24 @@ async fn main() -> miette::Result<()> {
         } else {
             None
         };
-        interactive_session(connection, cli.file, cli.debug, port).await?;
+        let options = SessionOptions {
+            max_duration: cli.max_duration.map(std::time::Duration::from_secs),
+            idle_timeout: cli
+                .idle_timeout
+                .filter(|&secs| secs > 0)
+                .map(std::time::Duration::from_secs),
+            echo_sent: cli.echo_sent,
+            require_response: cli.require_response.map(std::time::Duration::from_secs),
+            insecure_log_passwords: cli.insecure_log_passwords,
+            output_on_error_only: cli.output_on_error_only,
+            trace_stderr: cli.trace_stderr,
+            read_only: cli.read_only,
+            reconnect: cli.reconnect,
+            local_history: cli.local_history,
+            run_macro: cli.run_macro,
+            device_reset: cli.device_reset,
+        };
+        interactive_session(connection, cli.file, cli.debug, port, options).await?;
     } else if let Some(cmd) = cli.command {
         match cmd {
             Commands::Bauds => {
@@ -148,18 +344,69 @@ async fn main() -> miette::Result<()> {
                         .into_diagnostic()
                         .wrap_err("Failed to write to stdout.".red())?;
                 }
+                write!(
+                    stdout,
+                    "Other rates (i.e. 250000) can be used with `--baud-custom <N>` if the port supports them\r\n"
+                )
+                .into_diagnostic()
+                .wrap_err("Failed to write to stdout.".red())?;
+            }
+            Commands::Ports { format } => {
+                list_serial_ports(format)?;
+            }
+            Commands::Settings {
+                baud,
+                port,
+                port_settings,
+                format,
+                watch,
+            } => {
+                if watch {
+                    watch_settings(baud.rate(), &port, &port_settings.into())?;
+                } else {
+                    get_settings(baud.rate(), &port, &port_settings.into(), format)?;
+                }
             }
-            Commands::Ports => {
-                list_serial_ports()?;
+            Commands::Replay { file, speed } => {
+                initialize_config(cli.config_override.into())?;
+                replay(&file, speed).await?;
             }
-            Commands::Settings { baud, port } => {
-                get_settings(baud, &port)?;
+            Commands::SendFile {
+                baud,
+                port,
+                port_settings,
+                path,
+                delay_ms,
+                crlf,
+            } => {
+                let connection = open_connection(baud.rate(), &port, &port_settings.into())?;
+                let delay = std::time::Duration::from_millis(delay_ms);
+                send_file(connection, &path, delay, crlf).await?;
+            }
+            Commands::Bridge {
+                baud,
+                port,
+                port_settings,
+                listen,
+            } => {
+                let connection = open_connection(baud.rate(), &port, &port_settings.into())?;
+                bridge(connection, &listen).await?;
+            }
+            Commands::Doctor => {
+                print_diagnostics(env!("CARGO_PKG_VERSION"), cli.config_override.config)?;
             }
         }
     }
     Ok(())
 }
 
+fn positive_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("'{s}' isn't a positive number")),
+    }
+}
+
 fn init_tracing<S>(
     out_dir: &Path,
     port: S,